@@ -1,82 +1,29 @@
-use std::fs;
+use intcode::{parse_program_from_file, PatchSearch, Program, Target};
 
 
 fn main() {
-  let input_filename = "./input.txt";
+  let program = parse_program_from_file("./input.txt");
 
-  let raw_input = fs::read_to_string(input_filename)
-    .expect("Something went wrong reading the file");
-
-  let program: Vec<usize> = raw_input
-    .trim()
-    .split(',')
-    .map(|s| s.parse().unwrap())
-    .collect();
-
-  // solve_part_1(&program);
+  // Part 1 is a single run, with 12 at position 1 and 2 at position 2. The
+  //   answer is whatever it leaves at position 0.
   solve_part_2(&program);
 }
 
 
-fn solve_part_1(original_program: &Vec<usize>) {
-  let mut program = original_program.clone();
-
-  // In order to restore the program to the "1202 program alarm" state:
-  //  * Replace position 1 with "12"
-  //  * Replace position 2 with "2"
-  program[1] = 12;
-  program[2] = 2;
-
-  println!("program, before: {:?}", program);
-  run_intcode_program(&mut program);
-
-  println!("-----------------------------");
-  println!("program, after: {:?}", program);
-}
-
-
-fn solve_part_2(original_program: &Vec<usize>) {
-  for val1 in 0..100 {
-    for val2 in 0..100 {
-      let mut program = original_program.clone();
-
-      program[1] = val1;
-      program[2] = val2;
-      run_intcode_program(&mut program);
-
-      if program[0] == 19690720 {
-        println!("Found the solution!");
-        println!("program[1] = {:?}", val1);
-        println!("program[2] = {:?}", val2);
-        println!("(noun * 100) + verb = {:?}", (val1 * 100) + val2);
-      }
-    }
-  }
-}
-
-
-fn run_intcode_program(program: &mut Vec<usize>) {
-  let mut pos = 0;
-
-  while (pos + 4) <= program.len() {
-    let opcode = program[pos];
-    let input_pos_1 = program[pos+1];
-    let input_pos_2 = program[pos+2];
-    let output_pos = program[pos+3];
-
-    if opcode == 99 {
-      break;
-    }
-
-    let input1 = program[input_pos_1];
-    let input2 = program[input_pos_2];
-
-    program[output_pos] = match opcode {
-      1 => input1 + input2,
-      2 => input1 * input2,
-      _ => panic!("Invalid opcode {:?} at pos {}", opcode, pos),
-    };
-
-    pos += 4;
+fn solve_part_2(original_program: &[isize]) {
+  // Find the noun (position 1) and verb (position 2) that make the program
+  //   leave 19690720 at position 0
+  let search = PatchSearch::new(Target::Memory { address: 0, value: 19690720 })
+    .patch(1, 0..100)
+    .patch(2, 0..100);
+
+  match search.find(&Program::new(original_program)).as_deref() {
+    Some(&[(_, noun), (_, verb)]) => {
+      println!("Found the solution!");
+      println!("program[1] = {:?}", noun);
+      println!("program[2] = {:?}", verb);
+      println!("(noun * 100) + verb = {:?}", (noun * 100) + verb);
+    },
+    _ => println!("No solution found"),
   }
 }
@@ -1,134 +1,106 @@
-use itertools::Itertools;
-use std::fs;
+mod topology;
 
-mod intcode_computer;
+use std::env;
+use std::process;
 
-fn main() {
-  let program = parse_program_from_file("./puzzle-input.txt");
-  // solve_part_1(&program);
-  solve_part_2(&program);
-}
+use intcode::parse_program_from_file;
 
-#[allow(dead_code)]
-fn solve_part_1(program: &Vec<isize>) {
-  let phase_settings_values: Vec<isize> = vec![0,1,2,3,4];
-  let mut max = 0;
-
-  for phase_settings in phase_settings_values
-    .iter()
-    .permutations(phase_settings_values.len())
-  {
-    let output = compute_amplifiers(&program, &phase_settings);
-    if output > max {
-      max = output;
-    }
-  }
+use topology::Topology;
 
-  println!("max output: {:?}", max);
-}
+// `--series` and `--ring` try every ordering of the phase settings, so this
+//   many amplifiers already means 10! = 3628800 runs
+const MAX_AMPLIFIERS: isize = 10;
 
-#[allow(dead_code)]
-fn solve_part_2(program: &Vec<isize>) {
-  let phase_settings_values: Vec<isize> = vec![5,6,7,8,9];
-  let mut max = 0;
-
-  for phase_settings in phase_settings_values
-    .iter()
-    .permutations(phase_settings_values.len())
-  {
-    let output = compute_amplifiers_with_feedback(&program, &phase_settings);
-    if output > max {
-      max = output;
-    }
-  }
-
-  println!("max output: {:?}", max);
-}
+const USAGE: &str = "\
+Usage: day-07 [topology-file] [options]
+  --series N       N amplifiers in a row, instead of a topology file
+  --ring N         N amplifiers in a feedback loop, instead of a topology file
+  --phases 5,6,7   phase settings to search over (overrides the topology's)
+  --top K          show the K best configurations instead of just the best
 
+Defaults to topologies/feedback.toml (part 2). Use topologies/series.toml for part 1.";
 
-fn compute_amplifiers(
-  program: &Vec<isize>,
-  phase_settings: &Vec<&isize>,
-) -> isize {
-  // Initial input of 0, as per puzzle description
-  let mut current_input = 0;
-  let mut output = vec![];
+fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  let options = parse_args(&args).unwrap_or_else(|err| {
+    eprintln!("{}\n\n{}", err, USAGE);
+    process::exit(2);
+  });
 
-  for phase_setting_input in phase_settings {
-    let mut program = intcode_computer::Program::new(program.clone());
-    output = program.run(&vec![**phase_setting_input, current_input]);
-    current_input = output[0];
+  let program = parse_program_from_file("./puzzle-input.txt");
+  if options.top == 1 {
+    let (max, phase_settings) = options.topology
+      .max_signal(&program)
+      .unwrap_or_else(|err| panic!("Amplifiers failed: {}", err));
+    println!("max output: {:?}", max);
+    println!("phase settings: {:?}", phase_settings);
+  } else {
+    let results = options.topology
+      .search(&program, options.top)
+      .unwrap_or_else(|err| panic!("Amplifiers failed: {}", err));
+    for (rank, (signal, phase_settings)) in results.iter().enumerate() {
+      println!("{:>3}. {:>12}  {:?}", rank + 1, signal, phase_settings);
+    }
   }
-
-  *output.get(0).expect("Output should have a single value")
 }
 
+struct Options {
+  topology: Topology,
+  top: usize,
+}
 
-fn compute_amplifiers_with_feedback(
-  program: &Vec<isize>,
-  phase_settings: &Vec<&isize>,
-) -> isize {
-  // Initial input of 0, as per puzzle description
-  let mut current_input = 0;
-  let mut output = vec![];
-
-  let mut amplifier_programs = vec![
-    intcode_computer::Program::new(program.clone()),
-    intcode_computer::Program::new(program.clone()),
-    intcode_computer::Program::new(program.clone()),
-    intcode_computer::Program::new(program.clone()),
-    intcode_computer::Program::new(program.clone()),
-  ];
-  assert_eq!(amplifier_programs.len(), phase_settings.len());
-
-  let mut indices = (0..amplifier_programs.len()).cycle();
-
-  let mut counter = 0;
-  loop {
-    if amplifier_programs.iter().all(|x| x.is_halted()) {
-      // println!("All programs have now halted. Count = {:?}", counter);
-      break;
-    }
-
-    let index = indices.next().unwrap();
-    let program = amplifier_programs.get_mut(index).unwrap();
-
-    if program.is_halted() {
-      // println!("Continuing...");
-      continue;
-    }
-
-    let inputs =
-      // Only need the phase setting the first time each amplifier runs
-      if counter < 5 {
-        let phase_setting_input = phase_settings[index];
-        vec![*phase_setting_input, current_input]
-      }
-      else {
-        vec![current_input]
-      };
-
-    output = program.run(&inputs);
-    current_input = *output.get(0).expect("Program had no output.");
-
-    counter += 1;
-    if counter > 10_000 {
-      println!("Hit infite loop safety check. Exiting...");
-      break;
+fn parse_args(args: &[String]) -> Result<Options, String> {
+  let mut topology = None;
+  let mut phases: Option<Vec<isize>> = None;
+  let mut top = 1;
+
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+    match arg.as_str() {
+      "--series" | "--ring" => {
+        let count: isize = value()?.parse().map_err(|_| format!("Invalid count for {}", arg))?;
+        if count <= 0 {
+          return Err("Need at least one amplifier".to_string());
+        }
+        if count > MAX_AMPLIFIERS {
+          return Err(format!("Too many amplifiers for {} (at most {})", arg, MAX_AMPLIFIERS));
+        }
+        // Same phase settings as the puzzle, unless --phases says otherwise
+        topology = Some(if arg == "--series" {
+          Topology::series(count as usize, &(0..count).collect::<Vec<_>>())
+        } else {
+          Topology::ring(count as usize, &(5..5 + count).collect::<Vec<_>>())
+        });
+      },
+      "--phases" => {
+        let list = value()?;
+        phases = Some(
+          list.split(',')
+            .map(|phase| phase.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Invalid phase settings: {:?}", list))?
+        );
+      },
+      "--top" => {
+        top = value()?.parse().map_err(|_| "Invalid value for --top".to_string())?;
+        if top == 0 {
+          return Err("--top needs to be at least 1".to_string());
+        }
+      },
+      _ if arg.starts_with("--") => return Err(format!("Unknown option {:?}", arg)),
+      _ => topology = Some(Topology::from_file(arg)?),
     }
   }
 
-  *output.get(0).expect("Output should have a single value")
-}
-
-
-fn parse_program_from_file(filename: &str) -> Vec<isize> {
-  fs::read_to_string(&filename)
-    .expect(&format!("Problem reading file: {:?}", &filename))
-    .trim()
-    .split(',')
-    .map(|s| s.parse().unwrap())
-    .collect()
+  let mut topology = match topology {
+    Some(topology) => topology,
+    None => Topology::from_file("./topologies/feedback.toml")?,
+  };
+  if let Some(phases) = phases {
+    topology.set_phases(&phases)?;
+  }
+  Ok(Options { topology, top })
 }
 
 
@@ -167,13 +139,9 @@ mod tests {
       },
     ];
 
+    let topology = Topology::series(5, &[0,1,2,3,4]);
     for case in cases {
-      let output = compute_amplifiers(
-        &case.program,
-        // We need Vec<&isize> instead of Vec<isize>
-        // Not sure if there is a more idiomatic way of doing this
-        &case.phase_settings.iter().collect(),
-      );
+      let output = topology.run(&case.program, &case.phase_settings).unwrap();
       assert_eq!(output, case.output);
     }
   }
@@ -206,10 +174,21 @@ mod tests {
       },
     ];
 
+    let topology = Topology::ring(5, &[5,6,7,8,9]);
     for case in cases {
-      let phase_settings = case.phase_settings.iter().collect();
-      let output = compute_amplifiers_with_feedback(&case.program, &phase_settings);
+      let output = topology.run(&case.program, &case.phase_settings).unwrap();
       assert_eq!(output, case.output);
     }
   }
+  #[test]
+  fn rejects_bad_options() {
+    let error = |args: &[&str]| {
+      let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+      parse_args(&args).err().unwrap()
+    };
+    assert_eq!(error(&["--top", "0"]), "--top needs to be at least 1");
+    assert_eq!(error(&["--series", "11"]), "Too many amplifiers for --series (at most 10)");
+    assert_eq!(error(&["--ring", "0"]), "Need at least one amplifier");
+    assert!(parse_args(&["--ring".to_string(), "10".to_string()]).is_ok());
+  }
 }
@@ -0,0 +1,343 @@
+// Amplifier wirings, loaded from TOML files instead of being hard-coded.
+//   See topologies/ for examples. The format:
+//
+//   phases = [5, 6, 7, 8, 9]  # phase settings to search over
+//   signal = 0                # initial signal (optional, defaults to 0)
+//   input = "A"               # amplifier that gets the initial signal
+//   output = "A"              # where the final signal ends up
+//
+//   [[amplifier]]
+//   name = "A"
+//   phase = 7                 # optional, pins the phase setting
+//   outputs = ["B"]           # amplifiers (or plain channels) to send to
+//
+// Amplifiers without a pinned phase get one from `phases`, trying every
+//   permutation. Each amplifier reads from a channel with its own name, so
+//   `outputs` can point anywhere: the next amplifier, back around to the
+//   first one, several amplifiers at once, or a channel nobody reads from
+//   (like "thrusters") just to collect the result.
+
+use std::collections::HashSet;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use intcode::{Limits, Network, NetworkOutcome, Program};
+use itertools::Itertools;
+use serde::Deserialize;
+
+// The amplifier programs never go long without input or output, so anything
+//   that does is stuck
+const MAX_LOOP_ITERATIONS: usize = 10_000;
+
+// Number of permutations a search thread takes at a time
+const SEARCH_BATCH_SIZE: usize = 64;
+
+// A signal, and the phase settings that produced it
+pub type Configuration = (isize, Vec<isize>);
+
+#[derive(Debug, Deserialize)]
+pub struct Topology {
+  #[serde(default)]
+  phases: Vec<isize>,
+  #[serde(default)]
+  signal: isize,
+  input: String,
+  output: String,
+  #[serde(rename = "amplifier")]
+  amplifiers: Vec<Amplifier>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Amplifier {
+  name: String,
+  phase: Option<isize>,
+  #[serde(default)]
+  outputs: Vec<String>,
+}
+
+impl Topology {
+  pub fn parse(text: &str) -> Result<Topology, String> {
+    let topology: Topology = toml::from_str(text).map_err(|err| err.to_string())?;
+
+    let mut names = HashSet::new();
+    for amplifier in topology.amplifiers.iter() {
+      if !names.insert(&amplifier.name) {
+        return Err(format!("amplifier {:?} is listed twice", amplifier.name));
+      }
+    }
+    if !names.contains(&topology.input) {
+      return Err(format!("input {:?} isn't an amplifier", topology.input));
+    }
+    topology.check_phase_count()?;
+
+    Ok(topology)
+  }
+
+  // `count` amplifiers in a row, ending at "thrusters" (Day 7 part 1)
+  pub fn series(count: usize, phases: &[isize]) -> Topology {
+    let names: Vec<String> = (0..count).map(amplifier_name).collect();
+    Topology {
+      phases: phases.to_vec(),
+      signal: 0,
+      input: names[0].clone(),
+      output: "thrusters".to_string(),
+      amplifiers: names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| Amplifier {
+          name: name.clone(),
+          phase: None,
+          outputs: vec![names.get(index + 1).cloned().unwrap_or_else(|| "thrusters".to_string())],
+        })
+        .collect(),
+    }
+  }
+
+  // `count` amplifiers in a feedback loop (Day 7 part 2)
+  pub fn ring(count: usize, phases: &[isize]) -> Topology {
+    let mut topology = Topology::series(count, phases);
+    let first = topology.input.clone();
+    topology.amplifiers.last_mut().unwrap().outputs = vec![first.clone()];
+    topology.output = first;
+    topology
+  }
+
+  pub fn set_phases(&mut self, phases: &[isize]) -> Result<(), String> {
+    self.phases = phases.to_vec();
+    self.check_phase_count()
+  }
+
+  pub fn from_file(filename: &str) -> Result<Topology, String> {
+    let text = fs::read_to_string(filename)
+      .map_err(|err| format!("Problem reading {:?}: {}", filename, err))?;
+    Topology::parse(&text)
+  }
+
+  // Number of amplifiers that need a phase setting from `phases`
+  pub fn unpinned_count(&self) -> usize {
+    self.amplifiers.iter().filter(|amplifier| amplifier.phase.is_none()).count()
+  }
+
+  fn check_phase_count(&self) -> Result<(), String> {
+    if self.phases.len() < self.unpinned_count() {
+      return Err(format!(
+        "{} amplifiers need a phase setting, but there are only {} to choose from",
+        self.unpinned_count(),
+        self.phases.len(),
+      ));
+    }
+    Ok(())
+  }
+
+  // Runs the amplifiers once and returns the final signal. `phase_settings`
+  //   go to the unpinned amplifiers, in the order they're listed.
+  pub fn run(&self, program: &[isize], phase_settings: &[isize]) -> Result<isize, String> {
+    let mut network = Network::new();
+    let mut phase_settings = phase_settings.iter();
+
+    for amplifier in self.amplifiers.iter() {
+      let outputs: Vec<&str> = amplifier.outputs.iter().map(|output| output.as_str()).collect();
+      let mut program = Program::new(program);
+      program.set_limits(Limits { loop_iterations: Some(MAX_LOOP_ITERATIONS), ..Limits::default() });
+      network
+        .add_machine(&amplifier.name, program, &amplifier.name, &outputs)
+        .map_err(|err| err.to_string())?;
+
+      let phase = amplifier.phase
+        .or_else(|| phase_settings.next().copied())
+        .ok_or_else(|| format!("no phase setting for amplifier {:?}", amplifier.name))?;
+      network.send(&amplifier.name, phase);
+    }
+    network.send(&self.input, self.signal);
+
+    match network.run().map_err(|err| err.to_string())? {
+      NetworkOutcome::Halted => {},
+      NetworkOutcome::Deadlocked(waiting) => {
+        return Err(format!("amplifiers {:?} are stuck waiting for input", waiting));
+      },
+    }
+
+    network
+      .take(&self.output)
+      .last()
+      .copied()
+      .ok_or_else(|| format!("nothing was sent to {:?}", self.output))
+  }
+
+  // Tries every permutation of the phase settings. Returns the highest
+  //   signal along with the phase settings that produced it.
+  pub fn max_signal(&self, program: &[isize]) -> Result<Configuration, String> {
+    let mut best = self.search(program, 1)?;
+    best.pop().ok_or_else(|| "no phase settings to try".to_string())
+  }
+
+  // Like `max_signal`, but returns the `top` best configurations, highest
+  //   signal first. Ties are broken by phase settings, so the results don't
+  //   depend on thread timing.
+  // The permutations are spread across one thread per core. Each thread
+  //   grabs them in batches from the shared iterator, so the whole list never
+  //   has to be in memory at once (10 amplifiers is already 3.6 million).
+  pub fn search(&self, program: &[isize], top: usize) -> Result<Vec<Configuration>, String> {
+    // itertools' `Permutations` isn't fused, and calling `next` again after
+    //   it's done never ends. Hence the `fuse`.
+    let permutations = Mutex::new(
+      self.phases.iter().copied().permutations(self.unpinned_count()).fuse()
+    );
+    let failed = AtomicBool::new(false);
+    let thread_count = thread::available_parallelism().map_or(1, |count| count.get());
+
+    let results: Vec<Result<Vec<Configuration>, String>> = thread::scope(|scope| {
+      let handles: Vec<_> = (0..thread_count)
+        .map(|_| scope.spawn(|| {
+          let mut best = Vec::new();
+          while !failed.load(Ordering::Relaxed) {
+            let batch: Vec<Vec<isize>> =
+              permutations.lock().unwrap().by_ref().take(SEARCH_BATCH_SIZE).collect();
+            if batch.is_empty() {
+              break;
+            }
+            for phase_settings in batch {
+              match self.run(program, &phase_settings) {
+                Ok(signal) => best.push((signal, phase_settings)),
+                Err(err) => {
+                  failed.store(true, Ordering::Relaxed);
+                  return Err(err);
+                },
+              }
+            }
+            keep_top(&mut best, top);
+          }
+          Ok(best)
+        }))
+        .collect();
+      handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+
+    let mut best = Vec::new();
+    for result in results {
+      best.extend(result?);
+    }
+    keep_top(&mut best, top);
+    Ok(best)
+  }
+}
+
+// A, B, C, ..., Z, then amp26, amp27, ...
+fn amplifier_name(index: usize) -> String {
+  if index < 26 {
+    ((b'A' + index as u8) as char).to_string()
+  } else {
+    format!("amp{}", index)
+  }
+}
+
+fn keep_top(results: &mut Vec<Configuration>, top: usize) {
+  results.sort_by(|(signal_a, phases_a), (signal_b, phases_b)| {
+    signal_b.cmp(signal_a).then_with(|| phases_a.cmp(phases_b))
+  });
+  results.truncate(top);
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SERIES_EXAMPLE: [isize; 17] = [3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0];
+  const FEEDBACK_EXAMPLE: [isize; 29] = [
+    3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,
+    27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5,
+  ];
+
+  fn load(name: &str) -> Topology {
+    let filename = format!("{}/topologies/{}", env!("CARGO_MANIFEST_DIR"), name);
+    Topology::from_file(&filename).unwrap()
+  }
+
+  #[test]
+  fn finds_max_signal_for_day_7_examples() {
+    assert_eq!(
+      load("series.toml").max_signal(&SERIES_EXAMPLE),
+      Ok((43210, vec![4,3,2,1,0])),
+    );
+    assert_eq!(
+      load("feedback.toml").max_signal(&FEEDBACK_EXAMPLE),
+      Ok((139629729, vec![9,8,7,6,5])),
+    );
+  }
+
+  #[test]
+  fn runs_other_shapes() {
+    // The series example program just does signal * 10 + phase
+    let tree = load("tree.toml");
+    assert_eq!(tree.unpinned_count(), 3);
+    assert_eq!(tree.run(&SERIES_EXAMPLE, &[1, 2, 3]), Ok(124));
+
+    assert!(load("ring-of-3.toml").max_signal(&FEEDBACK_EXAMPLE).is_ok());
+  }
+
+  #[test]
+  fn generated_shapes_match_the_files() {
+    let series = Topology::series(5, &[0,1,2,3,4]);
+    assert_eq!(series.max_signal(&SERIES_EXAMPLE), Ok((43210, vec![4,3,2,1,0])));
+    let ring = Topology::ring(5, &[5,6,7,8,9]);
+    assert_eq!(ring.max_signal(&FEEDBACK_EXAMPLE), Ok((139629729, vec![9,8,7,6,5])));
+
+    let mut ring = Topology::ring(3, &[]);
+    assert!(ring.set_phases(&[1, 2]).is_err());
+    assert!(ring.set_phases(&[1, 2, 3, 4]).is_ok());
+
+    assert_eq!(amplifier_name(2), "C");
+    assert_eq!(amplifier_name(30), "amp30");
+  }
+
+  #[test]
+  fn search_returns_top_configurations() {
+    // signal * 10 + phase, so the digits of the signal are the phases
+    let results = Topology::series(3, &[1,2,3,4]).search(&SERIES_EXAMPLE, 3).unwrap();
+    assert_eq!(results, vec![
+      (432, vec![4,3,2]),
+      (431, vec![4,3,1]),
+      (423, vec![4,2,3]),
+    ]);
+
+    let all = Topology::series(3, &[1,2,3,4]).search(&SERIES_EXAMPLE, 100).unwrap();
+    assert_eq!(all.len(), 24);
+    assert_eq!(all.last(), Some(&(123, vec![1,2,3])));
+
+    // Errors from any thread come back out
+    let mut stuck = Topology::series(2, &[0,1]);
+    stuck.input = "B".to_string();
+    assert!(stuck.search(&SERIES_EXAMPLE, 1).is_err());
+  }
+
+  #[test]
+  fn rejects_bad_topologies() {
+    let error = |text: &str| Topology::parse(text).unwrap_err();
+
+    assert!(error("input = \"A\"").contains("missing field"));
+    assert_eq!(
+      error("input = \"B\"\noutput = \"A\"\n[[amplifier]]\nname = \"A\"\nphase = 1"),
+      "input \"B\" isn't an amplifier",
+    );
+    assert_eq!(
+      error("input = \"A\"\noutput = \"A\"\n[[amplifier]]\nname = \"A\"\n[[amplifier]]\nname = \"A\""),
+      "amplifier \"A\" is listed twice",
+    );
+    assert_eq!(
+      error("phases = [1]\ninput = \"A\"\noutput = \"x\"\n[[amplifier]]\nname = \"A\"\n[[amplifier]]\nname = \"B\""),
+      "2 amplifiers need a phase setting, but there are only 1 to choose from",
+    );
+
+    // Nothing ever feeds B, so it waits forever
+    let stuck = Topology::parse(
+      "input = \"A\"\noutput = \"x\"\n[[amplifier]]\nname = \"A\"\nphase = 0\n[[amplifier]]\nname = \"B\"\nphase = 0",
+    ).unwrap();
+    assert_eq!(
+      stuck.run(&SERIES_EXAMPLE, &[]),
+      Err("amplifiers [\"B\"] are stuck waiting for input".to_string()),
+    );
+  }
+}
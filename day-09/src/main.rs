@@ -1,6 +1,4 @@
-use std::fs;
-
-mod intcode_computer;
+use intcode::{parse_program_from_file, Program};
 
 fn main() {
   let input = parse_program_from_file("./puzzle-input.txt");
@@ -10,22 +8,14 @@ fn main() {
 
 #[allow(dead_code)]
 fn solve_part_1(input: &[isize]) {
-  let mut program = intcode_computer::Program::new(&input);
-  let output = program.run(&[1]);
+  let mut program = Program::new(input);
+  let output = program.run(&[1]).expect("Intcode program failed");
   println!("output: {:?}", output);
 }
 
 #[allow(dead_code)]
 fn solve_part_2(input: &[isize]) {
-  let mut program = intcode_computer::Program::new(&input);
-  let output = program.run(&[2]);
+  let mut program = Program::new(input);
+  let output = program.run(&[2]).expect("Intcode program failed");
   println!("output: {:?}", output);
 }
-fn parse_program_from_file(filename: &str) -> Vec<isize> {
-  fs::read_to_string(&filename)
-    .expect(&format!("Problem reading file: {:?}", &filename))
-    .trim()
-    .split(',')
-    .map(|s| s.parse().unwrap())
-    .collect()
-}
@@ -0,0 +1,115 @@
+// Throughput of the interpreter and the compiled tier on Day 9's BOOST
+//   program in sensor boost mode (input 2), which executes a few hundred
+//   thousand instructions. Also times the old string-formatting decoder
+//   against the one in instruction.rs.
+//
+// Run with `cargo bench -p intcode`. On my machine:
+//   decoding, string formatting:              ~60ns per instruction
+//   decoding, div/mod:                        ~16ns per instruction (~4x faster)
+//   interpreter with the div/mod decode:      ~8.5ms per run  (~44M instructions/sec)
+//   generic words, see word.rs:               ~11ms per run   (~33M instructions/sec)
+//   compiled tier, see compiler.rs:           ~6.0ms per run  (~60M instructions/sec)
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use intcode::{parse_program_from_file, Instruction, Opcode, Parameter, ParameterMode, Program};
+
+const RUNS: u32 = 20;
+// Times through the whole BOOST image for the decoder comparison
+const DECODE_PASSES: u32 = 1000;
+
+fn main() {
+  let boost = parse_program_from_file(
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../day-09/puzzle-input.txt"),
+  );
+
+  bench_decoders(&boost);
+  bench("interpreter", &boost, false);
+  bench("compiled", &boost, true);
+}
+
+// Decodes every cell of the program as if it were an instruction, with the
+//   old and the new decoder
+fn bench_decoders(boost: &[isize]) {
+  let read_param = |address: usize| move |i: usize| boost.get(address + 1 + i).copied().unwrap_or(0);
+  let decodes = boost.len() as u32 * DECODE_PASSES;
+
+  let t1 = Instant::now();
+  for _ in 0..DECODE_PASSES {
+    for (address, &value) in boost.iter().enumerate() {
+      black_box(string_decode(black_box(value), read_param(address)));
+    }
+  }
+  let string_elapsed = t1.elapsed();
+
+  let t1 = Instant::now();
+  for _ in 0..DECODE_PASSES {
+    for (address, &value) in boost.iter().enumerate() {
+      black_box(Instruction::decode(black_box(value), read_param(address)).ok());
+    }
+  }
+  let elapsed = t1.elapsed();
+
+  println!("decoding BOOST's {} cells {} times", boost.len(), DECODE_PASSES);
+  println!("  string formatting, Vec of params: {:.1?} per decode", string_elapsed / decodes);
+  println!(
+    "  div/mod, fixed-size param array:  {:.1?} per decode ({:.1}x faster)",
+    elapsed / decodes,
+    string_elapsed.as_secs_f64() / elapsed.as_secs_f64(),
+  );
+}
+
+// How the interpreter used to decode, before instruction.rs
+fn string_decode(value: isize, read_param: impl Fn(usize) -> isize) -> Option<(Opcode, Vec<Parameter>)> {
+  if value < 0 {
+    return None;
+  }
+  let opcode_data = value.to_string();
+  let opcode_num: isize = if opcode_data.len() == 1 {
+    value
+  } else {
+    opcode_data[opcode_data.len() - 2..].parse().unwrap()
+  };
+  let opcode = Opcode::from_value(opcode_num)?;
+
+  let mut modes: Vec<isize> = if opcode_data.len() > 1 {
+    opcode_data[..opcode_data.len() - 2]
+      .chars()
+      .map(|c| c.to_digit(10).unwrap() as isize)
+      .rev()
+      .collect()
+  } else {
+    vec![]
+  };
+  while modes.len() < opcode.num_params() {
+    modes.push(0);
+  }
+
+  let mut params = Vec::with_capacity(opcode.num_params());
+  for (i, &mode) in modes.iter().enumerate().take(opcode.num_params()) {
+    params.push(Parameter { value: read_param(i), mode: ParameterMode::from_digit(mode)? });
+  }
+  Some((opcode, params))
+}
+
+fn bench(name: &str, boost: &[isize], compiled: bool) {
+  let mut instruction_count = 0;
+  let t1 = Instant::now();
+  for _ in 0..RUNS {
+    let mut program = Program::new(boost);
+    if compiled {
+      program.enable_compiler();
+    }
+    program.run(&[2]).expect("BOOST program failed");
+    instruction_count += program.instruction_count();
+  }
+  let elapsed = t1.elapsed();
+
+  println!("BOOST (input 2), {} -- {:.2?} per run", name, elapsed / RUNS);
+  println!(
+    "  {} instructions per run, {:.1}M instructions/sec",
+    instruction_count / RUNS as usize,
+    instruction_count as f64 / elapsed.as_secs_f64() / 1_000_000.0,
+  );
+}
@@ -0,0 +1,320 @@
+// Assembles Intcode from a small assembly language, the same one the
+//   disassembler produces:
+//
+//   ; Outputs 10, 9, ..., 1, then halts
+//   loop:   out [counter]
+//           add [counter], #-1, [counter]
+//           jt [counter], #loop
+//           hlt
+//   counter: .data 10
+//
+// Operands are `#5` for immediate mode, `[12]` for position mode and `rb+3`
+//   (or `rb-3`) for relative mode. Anywhere a number can go, a label can go
+//   too, optionally with an offset (`[counter+1]`, `#loop-2`).
+//
+// Lines may also start with an address, like `12: add #1, #2, [0]`. This is
+//   what the disassembler emits, and it's checked against the actual address
+//   so that a listing can't silently drift out of place.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::instruction::{Instruction, Opcode, Parameter, ParameterMode};
+
+#[derive(Debug, PartialEq)]
+pub enum AssembleErrorKind {
+  UnknownMnemonic(String),
+  WrongOperandCount { mnemonic: String, expected: usize, found: usize },
+  InvalidOperand(String),
+  InvalidValue(String),
+  UndefinedLabel(String),
+  DuplicateLabel(String),
+  AddressMismatch { expected: usize, found: usize },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AssembleError {
+  // 1-based, to match what an editor shows
+  pub line: usize,
+  pub kind: AssembleErrorKind,
+}
+
+impl fmt::Display for AssembleError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: ", self.line)?;
+    match &self.kind {
+      AssembleErrorKind::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic {:?}", mnemonic),
+      AssembleErrorKind::WrongOperandCount { mnemonic, expected, found } => write!(
+        f, "{} takes {} operands, found {}", mnemonic, expected, found,
+      ),
+      AssembleErrorKind::InvalidOperand(operand) => write!(f, "invalid operand {:?}", operand),
+      AssembleErrorKind::InvalidValue(value) => write!(f, "invalid value {:?}", value),
+      AssembleErrorKind::UndefinedLabel(label) => write!(f, "undefined label {:?}", label),
+      AssembleErrorKind::DuplicateLabel(label) => write!(f, "label {:?} is already defined", label),
+      AssembleErrorKind::AddressMismatch { expected, found } => write!(
+        f, "line is marked as address {} but is actually at address {}", found, expected,
+      ),
+    }
+  }
+}
+
+impl Error for AssembleError {}
+
+// A number or a label reference, resolved in the second pass
+#[derive(Debug)]
+enum Value {
+  Literal(isize),
+  Label { name: String, offset: isize },
+}
+
+#[derive(Debug)]
+enum Item {
+  Instruction { opcode: Opcode, operands: Vec<(ParameterMode, Value)> },
+  Data(Vec<Value>),
+}
+
+pub fn assemble(source: &str) -> Result<Vec<isize>, AssembleError> {
+  let mut labels: HashMap<String, usize> = HashMap::new();
+  let mut items: Vec<(usize, Item)> = Vec::new();
+  let mut address = 0;
+
+  // First pass: parse every line and figure out where the labels point
+  for (index, raw_line) in source.lines().enumerate() {
+    let line_number = index + 1;
+    let error = |kind| AssembleError { line: line_number, kind };
+
+    let mut line = raw_line.split(';').next().unwrap().trim();
+
+    while let Some((prefix, rest)) = split_label(line) {
+      if prefix.chars().all(|c| c.is_ascii_digit()) {
+        let found = prefix
+          .parse()
+          .map_err(|_| error(AssembleErrorKind::InvalidValue(prefix.to_string())))?;
+        if found != address {
+          return Err(error(AssembleErrorKind::AddressMismatch { expected: address, found }));
+        }
+      } else if labels.insert(prefix.to_string(), address).is_some() {
+        return Err(error(AssembleErrorKind::DuplicateLabel(prefix.to_string())));
+      }
+      line = rest;
+    }
+
+    if line.is_empty() {
+      continue;
+    }
+
+    let item = parse_item(line).map_err(error)?;
+    let size = match &item {
+      Item::Instruction { opcode, .. } => opcode.num_params() + 1,
+      Item::Data(values) => values.len(),
+    };
+    items.push((line_number, item));
+    address += size;
+  }
+
+  // Second pass: resolve labels and encode
+  let mut program = Vec::with_capacity(address);
+  for (line_number, item) in items {
+    let resolve = |value: &Value| resolve_value(value, &labels)
+      .map_err(|kind| AssembleError { line: line_number, kind });
+
+    match item {
+      Item::Instruction { opcode, operands } => {
+        let mut params = Vec::with_capacity(operands.len());
+        for (mode, value) in operands.iter() {
+          params.push(Parameter { value: resolve(value)?, mode: *mode });
+        }
+        program.extend(Instruction::new(opcode, &params).encode());
+      },
+      Item::Data(values) => {
+        for value in values.iter() {
+          program.push(resolve(value)?);
+        }
+      },
+    }
+  }
+
+  Ok(program)
+}
+
+// The comma-separated format that `parse_program` reads
+pub fn format_program(values: &[isize]) -> String {
+  let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+  values.join(",")
+}
+
+// Splits `name: rest` into `(name, rest)`, if the line starts with a label or
+//   an address.
+fn split_label(line: &str) -> Option<(&str, &str)> {
+  let colon = line.find(':')?;
+  let prefix = line[..colon].trim();
+  let is_label = !prefix.is_empty()
+    && prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+  if is_label {
+    Some((prefix, line[colon + 1..].trim()))
+  } else {
+    None
+  }
+}
+
+fn parse_item(line: &str) -> Result<Item, AssembleErrorKind> {
+  let (mnemonic, rest) = match line.find(char::is_whitespace) {
+    Some(index) => (&line[..index], line[index..].trim()),
+    None => (line, ""),
+  };
+  let operands: Vec<&str> =
+    if rest.is_empty() {
+      vec![]
+    } else {
+      rest.split(',').map(|operand| operand.trim()).collect()
+    };
+
+  if mnemonic == ".data" {
+    return Ok(Item::Data(
+      operands.iter().map(|operand| parse_value(operand)).collect::<Result<_, _>>()?
+    ));
+  }
+
+  let opcode = Opcode::from_mnemonic(mnemonic)
+    .ok_or_else(|| AssembleErrorKind::UnknownMnemonic(mnemonic.to_string()))?;
+  if operands.len() != opcode.num_params() {
+    return Err(AssembleErrorKind::WrongOperandCount {
+      mnemonic: mnemonic.to_string(),
+      expected: opcode.num_params(),
+      found: operands.len(),
+    });
+  }
+
+  Ok(Item::Instruction {
+    opcode,
+    operands: operands.iter().map(|operand| parse_operand(operand)).collect::<Result<_, _>>()?,
+  })
+}
+
+fn parse_operand(operand: &str) -> Result<(ParameterMode, Value), AssembleErrorKind> {
+  let invalid = || AssembleErrorKind::InvalidOperand(operand.to_string());
+
+  if let Some(value) = operand.strip_prefix('#') {
+    Ok((ParameterMode::Immediate, parse_value(value)?))
+  } else if let Some(value) = operand.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+    Ok((ParameterMode::Position, parse_value(value.trim())?))
+  } else if let Some(offset) = operand.strip_prefix("rb") {
+    let offset = offset.trim();
+    if offset.is_empty() {
+      Ok((ParameterMode::Relative, Value::Literal(0)))
+    } else if offset.starts_with('+') || offset.starts_with('-') {
+      let offset: isize = offset.replace(' ', "").parse().map_err(|_| invalid())?;
+      Ok((ParameterMode::Relative, Value::Literal(offset)))
+    } else {
+      Err(invalid())
+    }
+  } else {
+    Err(invalid())
+  }
+}
+
+// Either a number, or a label with an optional `+N` / `-N` offset
+fn parse_value(text: &str) -> Result<Value, AssembleErrorKind> {
+  let invalid = || AssembleErrorKind::InvalidValue(text.to_string());
+
+  if let Ok(number) = text.parse() {
+    return Ok(Value::Literal(number));
+  }
+
+  let (name, offset) = match text.find(['+', '-']) {
+    Some(index) => {
+      let offset: isize = text[index..].replace(' ', "").parse().map_err(|_| invalid())?;
+      (text[..index].trim(), offset)
+    },
+    None => (text, 0),
+  };
+
+  let starts_with_letter = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+  if !starts_with_letter || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+    return Err(invalid());
+  }
+  Ok(Value::Label { name: name.to_string(), offset })
+}
+
+fn resolve_value(value: &Value, labels: &HashMap<String, usize>) -> Result<isize, AssembleErrorKind> {
+  match value {
+    Value::Literal(number) => Ok(*number),
+    Value::Label { name, offset } => labels
+      .get(name)
+      .map(|&address| address as isize + offset)
+      .ok_or_else(|| AssembleErrorKind::UndefinedLabel(name.clone())),
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{disassemble, format_listing, parse_program_from_file, Program};
+
+  #[test]
+  fn assembles_with_labels() {
+    let source = "
+      ; Outputs 3, 2, 1, then halts
+      loop:   out [counter]
+              add [counter], #-1, [counter]
+              jt [counter], #loop
+              hlt
+      counter: .data 3
+    ";
+    let program = assemble(source).unwrap();
+    assert_eq!(program, vec![4,10,1001,10,-1,10,1005,10,0,99,3]);
+    assert_eq!(Program::new(&program).run(&[]).unwrap(), vec![3, 2, 1]);
+  }
+
+  #[test]
+  fn relative_mode_and_label_offsets() {
+    let source = "
+      arb #5
+      in rb-5
+      out rb+0
+      out rb
+      out [end-1]
+      end: hlt
+    ";
+    let program = assemble(source).unwrap();
+    assert_eq!(program, vec![109,5,203,-5,204,0,204,0,4,9,99]);
+    assert_eq!(format_program(&program), "109,5,203,-5,204,0,204,0,4,9,99");
+  }
+
+  #[test]
+  fn reports_errors_with_line_numbers() {
+    let error = |source: &str| assemble(source).unwrap_err();
+
+    assert_eq!(error("hlt\nfoo #1"), AssembleError {
+      line: 2,
+      kind: AssembleErrorKind::UnknownMnemonic("foo".to_string()),
+    });
+    assert_eq!(error("add #1, #2").kind, AssembleErrorKind::WrongOperandCount {
+      mnemonic: "add".to_string(),
+      expected: 3,
+      found: 2,
+    });
+    assert_eq!(error("out 5").kind, AssembleErrorKind::InvalidOperand("5".to_string()));
+    assert_eq!(error("out #nope").kind, AssembleErrorKind::UndefinedLabel("nope".to_string()));
+    assert_eq!(error("a: hlt\na: hlt").kind, AssembleErrorKind::DuplicateLabel("a".to_string()));
+    assert_eq!(
+      error("0: out #1\n3: hlt").kind,
+      AssembleErrorKind::AddressMismatch { expected: 2, found: 3 },
+    );
+  }
+
+  #[test]
+  fn round_trips_with_the_disassembler() {
+    let puzzle_inputs = [
+      concat!(env!("CARGO_MANIFEST_DIR"), "/../day-05/puzzle-input.txt"),
+      concat!(env!("CARGO_MANIFEST_DIR"), "/../day-09/puzzle-input.txt"),
+    ];
+    for filename in puzzle_inputs.iter() {
+      let program = parse_program_from_file(filename);
+      let listing = format_listing(&disassemble(&program));
+      assert_eq!(assemble(&listing).unwrap(), program);
+    }
+  }
+}
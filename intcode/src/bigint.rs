@@ -0,0 +1,309 @@
+// Minimal arbitrary-precision integer, just enough to be an Intcode word
+//   (see word.rs): add, multiply, compare, and convert to and from text.
+//
+// Sign and magnitude, with the magnitude in base 2^32, least significant
+//   limb first. The magnitude never has leading zero limbs, and zero is
+//   never negative, so the derived equality is value equality.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Mul, Neg};
+use std::str::FromStr;
+
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct BigInt {
+  negative: bool,
+  magnitude: Vec<u32>,
+}
+
+impl BigInt {
+  pub fn zero() -> Self {
+    BigInt::default()
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.magnitude.is_empty()
+  }
+
+  pub fn is_negative(&self) -> bool {
+    self.negative
+  }
+
+  // `None` if it doesn't fit
+  pub fn to_i128(&self) -> Option<i128> {
+    if self.magnitude.len() > 4 {
+      return None;
+    }
+    let magnitude = self.magnitude.iter().rev().fold(0u128, |acc, &limb| (acc << 32) | limb as u128);
+    if self.negative {
+      0i128.checked_sub_unsigned(magnitude)
+    } else {
+      i128::try_from(magnitude).ok()
+    }
+  }
+
+  fn from_parts(negative: bool, mut magnitude: Vec<u32>) -> Self {
+    while magnitude.last() == Some(&0) {
+      magnitude.pop();
+    }
+    BigInt { negative: negative && !magnitude.is_empty(), magnitude }
+  }
+
+  // Divides the magnitude by `divisor` in place, returning the remainder
+  fn div_rem_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in magnitude.iter_mut().rev() {
+      let value = (remainder << 32) | *limb as u64;
+      *limb = (value / divisor as u64) as u32;
+      remainder = value % divisor as u64;
+    }
+    while magnitude.last() == Some(&0) {
+      magnitude.pop();
+    }
+    remainder as u32
+  }
+}
+
+impl From<i128> for BigInt {
+  fn from(value: i128) -> Self {
+    let mut magnitude = Vec::new();
+    let mut rest = value.unsigned_abs();
+    while rest > 0 {
+      magnitude.push(rest as u32);
+      rest >>= 32;
+    }
+    BigInt::from_parts(value < 0, magnitude)
+  }
+}
+
+impl From<i64> for BigInt {
+  fn from(value: i64) -> Self {
+    BigInt::from(value as i128)
+  }
+}
+
+impl From<isize> for BigInt {
+  fn from(value: isize) -> Self {
+    BigInt::from(value as i128)
+  }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+  a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+  let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+  let mut sum = Vec::with_capacity(long.len() + 1);
+  let mut carry = 0u64;
+  for (i, &limb) in long.iter().enumerate() {
+    let total = limb as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+    sum.push(total as u32);
+    carry = total >> 32;
+  }
+  if carry > 0 {
+    sum.push(carry as u32);
+  }
+  sum
+}
+
+// `a` has to be at least as big as `b`
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+  let mut difference = Vec::with_capacity(a.len());
+  let mut borrow = 0i64;
+  for (i, &limb) in a.iter().enumerate() {
+    let mut value = limb as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+    borrow = 0;
+    if value < 0 {
+      value += 1 << 32;
+      borrow = 1;
+    }
+    difference.push(value as u32);
+  }
+  difference
+}
+
+impl Add for &BigInt {
+  type Output = BigInt;
+
+  fn add(self, other: &BigInt) -> BigInt {
+    if self.negative == other.negative {
+      return BigInt::from_parts(self.negative, add_magnitudes(&self.magnitude, &other.magnitude));
+    }
+    match compare_magnitudes(&self.magnitude, &other.magnitude) {
+      Ordering::Less => {
+        BigInt::from_parts(other.negative, sub_magnitudes(&other.magnitude, &self.magnitude))
+      },
+      _ => BigInt::from_parts(self.negative, sub_magnitudes(&self.magnitude, &other.magnitude)),
+    }
+  }
+}
+
+impl Mul for &BigInt {
+  type Output = BigInt;
+
+  fn mul(self, other: &BigInt) -> BigInt {
+    let mut product = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+    for (i, &a) in self.magnitude.iter().enumerate() {
+      let mut carry = 0u64;
+      for (j, &b) in other.magnitude.iter().enumerate() {
+        let total = product[i + j] as u64 + a as u64 * b as u64 + carry;
+        product[i + j] = total as u32;
+        carry = total >> 32;
+      }
+      product[i + other.magnitude.len()] = carry as u32;
+    }
+    BigInt::from_parts(self.negative != other.negative, product)
+  }
+}
+
+impl Neg for BigInt {
+  type Output = BigInt;
+
+  fn neg(self) -> BigInt {
+    let negative = !self.negative;
+    BigInt::from_parts(negative, self.magnitude)
+  }
+}
+
+impl Ord for BigInt {
+  fn cmp(&self, other: &Self) -> Ordering {
+    match (self.negative, other.negative) {
+      (false, true) => Ordering::Greater,
+      (true, false) => Ordering::Less,
+      (false, false) => compare_magnitudes(&self.magnitude, &other.magnitude),
+      (true, true) => compare_magnitudes(&other.magnitude, &self.magnitude),
+    }
+  }
+}
+
+impl PartialOrd for BigInt {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl fmt::Display for BigInt {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.is_zero() {
+      return write!(f, "0");
+    }
+    // Nine decimal digits at a time, least significant first
+    let mut chunks = Vec::new();
+    let mut magnitude = self.magnitude.clone();
+    while !magnitude.is_empty() {
+      chunks.push(BigInt::div_rem_small(&mut magnitude, 1_000_000_000));
+    }
+
+    if self.negative {
+      write!(f, "-")?;
+    }
+    write!(f, "{}", chunks.last().unwrap())?;
+    for chunk in chunks.iter().rev().skip(1) {
+      write!(f, "{:09}", chunk)?;
+    }
+    Ok(())
+  }
+}
+
+// Same as Display, so test failures show the number
+impl fmt::Debug for BigInt {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseBigIntError;
+
+impl fmt::Display for ParseBigIntError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "invalid integer")
+  }
+}
+
+impl Error for ParseBigIntError {}
+
+impl FromStr for BigInt {
+  type Err = ParseBigIntError;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let (negative, digits) = match text.as_bytes().first() {
+      Some(b'-') => (true, &text[1..]),
+      Some(b'+') => (false, &text[1..]),
+      _ => (false, text),
+    };
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+      return Err(ParseBigIntError);
+    }
+
+    let mut magnitude: Vec<u32> = Vec::new();
+    for byte in digits.bytes() {
+      let mut carry = (byte - b'0') as u64;
+      for limb in magnitude.iter_mut() {
+        let total = *limb as u64 * 10 + carry;
+        *limb = total as u32;
+        carry = total >> 32;
+      }
+      if carry > 0 {
+        magnitude.push(carry as u32);
+      }
+    }
+    Ok(BigInt::from_parts(negative, magnitude))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn big(text: &str) -> BigInt {
+    text.parse().unwrap()
+  }
+
+  #[test]
+  fn parses_and_prints() {
+    for text in ["0", "1", "-1", "4294967296", "-1000000000", "123456789012345678901234567890"].iter() {
+      assert_eq!(big(text).to_string(), *text);
+    }
+    assert_eq!(big("-0"), BigInt::zero());
+    assert_eq!(big("+007").to_string(), "7");
+    assert_eq!("".parse::<BigInt>(), Err(ParseBigIntError));
+    assert_eq!("-".parse::<BigInt>(), Err(ParseBigIntError));
+    assert_eq!("12a".parse::<BigInt>(), Err(ParseBigIntError));
+  }
+
+  #[test]
+  fn adds_and_multiplies_with_signs() {
+    let a = big("1000000000000000000000");
+    let b = big("-999999999999999999999");
+    assert_eq!(&a + &b, big("1"));
+    assert_eq!(&b + &a, big("1"));
+    assert_eq!(&b + &b, big("-1999999999999999999998"));
+    assert_eq!(&a + &-a.clone(), BigInt::zero());
+    assert_eq!(&a * &b, big("-999999999999999999999000000000000000000000"));
+    assert_eq!(&b * &b, big("999999999999999999998000000000000000000001"));
+    assert_eq!(&a * &BigInt::zero(), BigInt::zero());
+    assert!(!(&b * &BigInt::zero()).is_negative());
+  }
+
+  #[test]
+  fn converts_and_compares() {
+    for &value in [0, 1, -1, i128::MAX, i128::MIN, 1 << 64, -(1 << 100)].iter() {
+      let converted = BigInt::from(value);
+      assert_eq!(converted.to_i128(), Some(value));
+      assert_eq!(converted.to_string(), value.to_string());
+    }
+    assert_eq!((&BigInt::from(i128::MAX) + &BigInt::from(1i128)).to_i128(), None);
+    assert_eq!((&BigInt::from(i128::MIN) + &BigInt::from(-1i128)).to_i128(), None);
+
+    let mut values = vec![big("5"), big("-100000000000000000000"), big("0"), big("-3"), big("100000000000000000000")];
+    values.sort();
+    assert_eq!(values, vec![
+      big("-100000000000000000000"), big("-3"), big("0"), big("5"), big("100000000000000000000"),
+    ]);
+  }
+}
@@ -0,0 +1,53 @@
+// Runs an ASCII Intcode program as an interactive text session, see
+//   terminal.rs. `--script` plays back lines of input before reading from
+//   stdin, and `--record` saves every line the program gets, for replaying.
+//
+// Usage: cargo run -p intcode --bin ascii -- day-25/input.txt --script moves.txt
+
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::process;
+
+use intcode::{parse_program_from_file, Program, SessionEnd, Terminal};
+
+const USAGE: &str = "Usage: ascii <program-file> [--script <file>] [--record <file>]";
+
+fn main() {
+  let mut args = env::args().skip(1);
+  let filename = args.next().unwrap_or_else(|| usage());
+  let mut terminal = Terminal::new(Program::new(&parse_program_from_file(&filename)));
+
+  while let Some(arg) = args.next() {
+    let path = args.next().unwrap_or_else(|| usage());
+    terminal = match arg.as_str() {
+      "--script" => terminal.with_script(
+        &fs::read_to_string(&path).unwrap_or_else(|_| panic!("Problem reading file: {:?}", path)),
+      ),
+      "--record" => terminal.record_to(Box::new(
+        File::create(&path).unwrap_or_else(|_| panic!("Problem creating file: {:?}", path)),
+      )),
+      _ => usage(),
+    };
+  }
+
+  let stdin = io::stdin();
+  let end = terminal.run(stdin.lock(), io::stdout()).expect("Problem talking to the terminal");
+  match end {
+    SessionEnd::Halted => (),
+    SessionEnd::OutOfInput => eprintln!("(out of input)"),
+    SessionEnd::BudgetExhausted(limit) => {
+      eprintln!("(stopped: {:?} budget exhausted)", limit);
+      process::exit(1);
+    },
+    SessionEnd::Failed(err) => {
+      eprintln!("(program failed: {})", err);
+      process::exit(1);
+    },
+  }
+}
+
+fn usage() -> ! {
+  eprintln!("{}", USAGE);
+  process::exit(2);
+}
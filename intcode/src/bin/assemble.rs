@@ -0,0 +1,31 @@
+// Assembles an Intcode assembly file (see assembler.rs for the syntax) and
+//   prints the comma-separated program.
+//
+// Usage: cargo run -p intcode --bin assemble -- program.asm > program.txt
+
+use std::env;
+use std::fs;
+use std::process;
+
+use intcode::{assemble, format_program};
+
+fn main() {
+  let filename = match env::args().nth(1) {
+    Some(filename) => filename,
+    None => {
+      eprintln!("Usage: assemble <source-file>");
+      process::exit(2);
+    },
+  };
+
+  let source = fs::read_to_string(&filename)
+    .unwrap_or_else(|_| panic!("Problem reading file: {:?}", filename));
+
+  match assemble(&source) {
+    Ok(program) => println!("{}", format_program(&program)),
+    Err(err) => {
+      eprintln!("{}: {}", filename, err);
+      process::exit(1);
+    },
+  }
+}
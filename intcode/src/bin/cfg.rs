@@ -0,0 +1,42 @@
+// Prints the control flow graph of an Intcode program in Graphviz format,
+//   plus a summary of anything suspicious.
+//
+// Usage: cargo run -p intcode --bin cfg -- day-09/puzzle-input.txt | dot -Tsvg > boost.svg
+
+use std::env;
+use std::process;
+
+use intcode::{parse_program_from_file, ControlFlowGraph, RegionKind};
+
+fn main() {
+  let filename = match env::args().nth(1) {
+    Some(filename) => filename,
+    None => {
+      eprintln!("Usage: cfg <program-file>");
+      process::exit(2);
+    },
+  };
+
+  let program = parse_program_from_file(&filename);
+  let graph = ControlFlowGraph::build(&program);
+  print!("{}", graph.to_dot());
+
+  // The summary goes to stderr, so stdout can be piped straight into `dot`
+  eprintln!("{} basic blocks", graph.blocks.len());
+  for block in graph.blocks.iter().filter(|block| block.indirect_jump) {
+    eprintln!("indirect jump at {}", block.instructions.last().unwrap().0);
+  }
+  for block in graph.blocks.iter().filter(|block| block.invalid_end) {
+    eprintln!("invalid instruction at {}", block.end);
+  }
+  for write in graph.self_modifying_writes.iter() {
+    eprintln!("self-modifying write at {} to {}", write.instruction, write.target);
+  }
+  for region in graph.regions.iter() {
+    let kind = match region.kind {
+      RegionKind::UnreachableCode => "unreachable code",
+      RegionKind::Data => "data",
+    };
+    eprintln!("{} at {}-{}", kind, region.start, region.end - 1);
+  }
+}
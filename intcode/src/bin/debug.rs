@@ -0,0 +1,28 @@
+// Interactive debugger for Intcode programs. Any extra arguments are queued up
+//   as inputs before the session starts.
+//
+// Usage: cargo run -p intcode --bin debug -- day-09/puzzle-input.txt 1
+
+use std::env;
+use std::io;
+use std::process;
+
+use intcode::{parse_program_from_file, Debugger, Program};
+
+fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  if args.is_empty() {
+    eprintln!("Usage: debug <program-file> [inputs...]");
+    process::exit(2);
+  }
+
+  let mut program = Program::new(&parse_program_from_file(&args[0]));
+  for arg in args[1..].iter() {
+    program.push_input(arg.parse().unwrap_or_else(|_| panic!("Invalid input: {:?}", arg)));
+  }
+
+  let stdin = io::stdin();
+  Debugger::new(program)
+    .run_repl(stdin.lock(), io::stdout())
+    .expect("Problem talking to the terminal");
+}
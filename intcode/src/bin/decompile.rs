@@ -0,0 +1,21 @@
+// Prints structured pseudocode for an Intcode program.
+//
+// Usage: cargo run -p intcode --bin decompile -- day-09/puzzle-input.txt
+
+use std::env;
+use std::process;
+
+use intcode::{decompile, format_decompiled, parse_program_from_file};
+
+fn main() {
+  let filename = match env::args().nth(1) {
+    Some(filename) => filename,
+    None => {
+      eprintln!("Usage: decompile <program-file>");
+      process::exit(2);
+    },
+  };
+
+  let program = parse_program_from_file(&filename);
+  print!("{}", format_decompiled(&decompile(&program)));
+}
@@ -0,0 +1,21 @@
+// Prints a disassembly listing of an Intcode program.
+//
+// Usage: cargo run -p intcode --bin disassemble -- day-05/puzzle-input.txt
+
+use std::env;
+use std::process;
+
+use intcode::{disassemble, format_listing, parse_program_from_file};
+
+fn main() {
+  let filename = match env::args().nth(1) {
+    Some(filename) => filename,
+    None => {
+      eprintln!("Usage: disassemble <program-file>");
+      process::exit(2);
+    },
+  };
+
+  let program = parse_program_from_file(&filename);
+  print!("{}", format_listing(&disassemble(&program)));
+}
@@ -0,0 +1,51 @@
+// Runs random programs on every way this crate has of running them, and
+//   reports the first disagreement, shrunk to a minimal counterexample. See
+//   fuzz.rs.
+//
+// Usage: cargo run --release -p intcode --bin fuzz -- --subset day5 --cases 100000
+//
+// Without `--subset`, fuzzes each day's subset in turn. Case `i` uses seed
+//   `seed + i`, so `--seed <n> --cases 1` reruns a single case.
+
+use std::env;
+use std::process;
+
+use intcode::{fuzz, Engine, FuzzOptions, Subset};
+
+const USAGE: &str =
+  "Usage: fuzz [--subset day2|day5|day9] [--cases <n>] [--seed <n>] [--budget <instructions>]";
+
+fn main() {
+  let mut args = env::args().skip(1);
+  let mut options = FuzzOptions::default();
+  let mut subsets = vec![Subset::Day2, Subset::Day5, Subset::Day9];
+
+  while let Some(arg) = args.next() {
+    let value = args.next().unwrap_or_else(|| usage());
+    match arg.as_str() {
+      "--subset" => subsets = vec![Subset::from_name(&value).unwrap_or_else(|| usage())],
+      "--cases" => options.cases = value.parse().unwrap_or_else(|_| usage()),
+      "--seed" => options.seed = value.parse().unwrap_or_else(|_| usage()),
+      "--budget" => options.budget = value.parse().unwrap_or_else(|_| usage()),
+      _ => usage(),
+    }
+  }
+
+  let engines = Engine::all();
+  for subset in subsets {
+    let options = FuzzOptions { subset, ..options };
+    match fuzz(&engines, options) {
+      Ok(()) => println!("{:?}: {} cases, no disagreements", subset, options.cases),
+      Err(divergence) => {
+        println!("{:?}:", subset);
+        print!("{}", divergence);
+        process::exit(1);
+      },
+    }
+  }
+}
+
+fn usage() -> ! {
+  eprintln!("{}", USAGE);
+  process::exit(2);
+}
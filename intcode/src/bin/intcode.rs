@@ -0,0 +1,195 @@
+// Runs any Intcode program from the command line, instead of a `main` with
+//   the input file and inputs hardcoded. Day 2's "1202 program alarm", for
+//   example:
+//
+//   cargo run -p intcode --bin intcode -- day-02/input.txt --set 1=12 --set 2=2 --read 0
+//
+// Outputs are printed one per line by default. `--format list` prints them
+//   comma separated on one line, `--format ascii` as text (see AsciiOutput),
+//   and `--format json` prints a single object with the outputs, how the run
+//   ended, and any `--read` addresses.
+//
+// A program stops after a million outputs unless `--max-outputs` says
+//   otherwise, so one that outputs forever doesn't run forever.
+//
+// Exit codes:
+//   0  the program halted
+//   1  the program failed (bad opcode, bad address, overflow, ...)
+//   2  bad arguments, or a file that can't be read
+//   3  the program needs more input than it was given
+//   4  the program ran out of budget (see `--max-instructions`)
+
+use std::env;
+use std::fs;
+use std::process;
+
+use intcode::{AsciiOutput, Limits, OutputDevice, Program, Yield};
+
+const DEFAULT_MAX_OUTPUTS: usize = 1_000_000;
+
+const USAGE: &str = "\
+Usage: intcode <program-file> [options]
+  --input <values>          (-i) queue up inputs, comma separated
+  --input-file <file>       queue up inputs from a file, comma or whitespace separated
+  --set <address>=<value>   change memory before running
+  --read <address>          show memory at address once the program stops
+  --format <format>         lines (default), list, ascii or json
+  --max-instructions <n>    stop after running n instructions
+  --max-outputs <n>         stop after n outputs (default 1000000)
+  --compile                 use the compiled tier";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+  Lines,
+  List,
+  Ascii,
+  Json,
+}
+
+fn main() {
+  let mut args = env::args().skip(1);
+  let filename = args.next().filter(|arg| !arg.starts_with('-')).unwrap_or_else(|| usage());
+
+  let mut inputs = Vec::new();
+  let mut patches = Vec::new();
+  let mut reads = Vec::new();
+  let mut format = Format::Lines;
+  let mut limits = Limits { outputs: Some(DEFAULT_MAX_OUTPUTS), ..Limits::default() };
+  let mut compile = false;
+
+  while let Some(arg) = args.next() {
+    if arg == "--compile" {
+      compile = true;
+      continue;
+    }
+    let value = args.next().unwrap_or_else(|| usage());
+    match arg.as_str() {
+      "--input" | "-i" => inputs.extend(parse_values(&value).unwrap_or_else(|| usage())),
+      "--input-file" => inputs.extend(parse_values(&read_file(&value)).unwrap_or_else(|| {
+        fail(&format!("Invalid inputs in {:?}", value))
+      })),
+      "--set" => patches.push(parse_patch(&value).unwrap_or_else(|| usage())),
+      "--read" => reads.push(value.parse::<usize>().unwrap_or_else(|_| usage())),
+      "--format" => format = match value.as_str() {
+        "lines" => Format::Lines,
+        "list" => Format::List,
+        "ascii" => Format::Ascii,
+        "json" => Format::Json,
+        _ => usage(),
+      },
+      "--max-instructions" => limits.instructions = Some(value.parse().unwrap_or_else(|_| usage())),
+      "--max-outputs" => limits.outputs = Some(value.parse().unwrap_or_else(|_| usage())),
+      _ => usage(),
+    }
+  }
+
+  let values = parse_values(&read_file(&filename))
+    .filter(|values| !values.is_empty())
+    .unwrap_or_else(|| fail(&format!("Invalid program in {:?}", filename)));
+  let mut program = Program::new(&values);
+  for (address, value) in patches {
+    program.write(address, value);
+  }
+  for input in inputs {
+    program.push_input(input);
+  }
+  program.set_limits(limits);
+  if compile {
+    program.enable_compiler();
+  }
+
+  // Lines and text are printed as they come, in case the program runs for a
+  //   while
+  let mut ascii = AsciiOutput::stdout();
+  let mut outputs = Vec::new();
+  let (ending, exit_code) = loop {
+    match program.resume() {
+      Ok(Yield::Output(value)) => {
+        match format {
+          Format::Lines => println!("{}", value),
+          Format::Ascii => ascii.write(value).expect("Problem writing output"),
+          Format::List | Format::Json => (),
+        }
+        outputs.push(value);
+      },
+      Ok(Yield::Halted) => break ("halted".to_string(), 0),
+      Ok(Yield::NeedsInput) => break ("needs input".to_string(), 3),
+      Ok(Yield::BudgetExhausted(limit)) => break (format!("{} exhausted", limit), 4),
+      Err(err) => break (err.to_string(), 1),
+    }
+  };
+
+  let join = |values: &[isize]| values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(",");
+  if format == Format::Json {
+    let memory: Vec<String> =
+      reads.iter().map(|&address| format!("\"{}\":{}", address, program.read(address))).collect();
+    println!(
+      "{{\"outputs\":[{}],\"ending\":\"{}\",\"memory\":{{{}}}}}",
+      join(&outputs), ending.replace('\\', "\\\\").replace('"', "\\\""), memory.join(","),
+    );
+  } else {
+    if format == Format::List {
+      println!("{}", join(&outputs));
+    }
+    for &address in reads.iter() {
+      println!("memory[{}]: {}", address, program.read(address));
+    }
+    if exit_code != 0 {
+      eprintln!("({})", ending);
+    }
+  }
+  process::exit(exit_code);
+}
+
+fn read_file(filename: &str) -> String {
+  fs::read_to_string(filename)
+    .unwrap_or_else(|err| fail(&format!("Problem reading file {:?}: {}", filename, err)))
+}
+
+// Comma or whitespace separated. `None` if any of them isn't a number.
+fn parse_values(text: &str) -> Option<Vec<isize>> {
+  text
+    .split(|c: char| c == ',' || c.is_whitespace())
+    .filter(|value| !value.is_empty())
+    .map(|value| value.parse().ok())
+    .collect()
+}
+
+// "1=12" -> (1, 12)
+fn parse_patch(text: &str) -> Option<(usize, isize)> {
+  let (address, value) = text.split_once('=')?;
+  Some((address.trim().parse().ok()?, value.trim().parse().ok()?))
+}
+
+fn usage() -> ! {
+  fail(USAGE)
+}
+
+fn fail(message: &str) -> ! {
+  eprintln!("{}", message);
+  process::exit(2);
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_values() {
+    assert_eq!(parse_values("1,2, -3\n4 5\n"), Some(vec![1, 2, -3, 4, 5]));
+    assert_eq!(parse_values(" ,\n"), Some(vec![]));
+    assert_eq!(parse_values("1,two,3"), None);
+    assert_eq!(parse_values("1.5"), None);
+  }
+
+  #[test]
+  fn parses_patches() {
+    assert_eq!(parse_patch("1=12"), Some((1, 12)));
+    assert_eq!(parse_patch(" 2 = -7 "), Some((2, -7)));
+    assert_eq!(parse_patch("-1=12"), None);
+    assert_eq!(parse_patch("1:12"), None);
+    assert_eq!(parse_patch("1=12=3"), None);
+    assert_eq!(parse_patch("=4"), None);
+  }
+}
@@ -0,0 +1,56 @@
+// Runs an Intcode program with the profiler on, then prints the profile. Any
+//   extra arguments are queued up as inputs. With `--folded <file>`, also
+//   writes folded call stacks for flamegraph.pl / inferno.
+//
+// Usage: cargo run -p intcode --bin profile -- day-09/puzzle-input.txt 2 --folded boost.folded
+
+use std::env;
+use std::process;
+
+use intcode::{parse_program_from_file, Program};
+
+const USAGE: &str = "Usage: profile <program-file> [inputs...] [--folded <file>]";
+
+fn main() {
+  let mut args = env::args().skip(1);
+  let mut filename = None;
+  let mut folded_filename = None;
+  let mut inputs = Vec::new();
+
+  while let Some(arg) = args.next() {
+    if arg == "--folded" {
+      folded_filename = Some(args.next().unwrap_or_else(|| usage()));
+    } else if filename.is_none() {
+      filename = Some(arg);
+    } else {
+      inputs.push(arg.parse().unwrap_or_else(|_| panic!("Invalid input: {:?}", arg)));
+    }
+  }
+  let filename = filename.unwrap_or_else(|| usage());
+
+  let mut program = Program::new(&parse_program_from_file(&filename));
+  program.enable_profiler();
+  let result = program.run(&inputs);
+
+  match &result {
+    Ok(outputs) => println!("outputs: {:?}", outputs),
+    Err(err) => println!("error: {}", err),
+  }
+  let profile = program.take_profile().unwrap();
+  println!();
+  print!("{}", profile.report());
+
+  if let Some(folded_filename) = folded_filename {
+    profile
+      .save_folded_stacks(&folded_filename)
+      .unwrap_or_else(|_| panic!("Problem writing file: {:?}", folded_filename));
+  }
+  if result.is_err() {
+    process::exit(1);
+  }
+}
+
+fn usage() -> ! {
+  eprintln!("{}", USAGE);
+  process::exit(2);
+}
@@ -0,0 +1,346 @@
+// A compiled tier for the interpreter. Turned on with
+//   `Program::enable_compiler`, after which `resume` (and so `run`) executes
+//   basic blocks of pre-decoded instructions instead of decoding every
+//   instruction every time it runs.
+//
+// A block starts wherever execution lands and runs up to the next jump or
+//   halt (or 64 instructions, whichever comes first). Its instructions are
+//   stored with their parameter modes already resolved into `Operand`s, so
+//   running one is just a match on the opcode.
+//
+// Intcode is allowed to modify itself, so every write is checked against the
+//   addresses covered by compiled blocks. A write that lands in compiled code
+//   throws the whole cache away, and the written address is marked as
+//   modified. Blocks are never compiled over modified addresses again, so
+//   from then on that code always runs in the interpreter.
+//
+// Anything unusual (errors, code outside the dense part of memory, tracing)
+//   also falls back to the interpreter, which produces the exact same
+//   results, just slower. So do values that don't fit in an isize, which
+//   only come up with a wider word (see word.rs).
+
+use crate::instruction::{Instruction, Opcode, Parameter, ParameterMode};
+use crate::memory::Memory;
+use crate::word::Word;
+
+// Code at or above this address is always interpreted. Same as the dense
+//   memory limit, see memory.rs.
+const MAX_COMPILED_ADDRESS: usize = 1 << 20;
+
+const MAX_BLOCK_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Operand {
+  Immediate(isize),
+  Position(usize),
+  // Offset from the relative base
+  Relative(isize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Op {
+  Add(Operand, Operand, Operand),
+  Multiply(Operand, Operand, Operand),
+  Input(Operand),
+  Output(Operand),
+  JumpIfTrue(Operand, Operand),
+  JumpIfFalse(Operand, Operand),
+  LessThan(Operand, Operand, Operand),
+  Equals(Operand, Operand, Operand),
+  RelativeBaseOffset(Operand),
+  Halt,
+}
+
+#[derive(Debug)]
+pub(crate) struct CompiledInstruction {
+  pub address: usize,
+  // Address of the following instruction
+  pub next: usize,
+  pub op: Op,
+}
+
+#[derive(Debug)]
+pub(crate) struct Block {
+  pub start: usize,
+  pub instructions: Vec<CompiledInstruction>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct CodeCache {
+  // Indexed by start address
+  blocks: Vec<Option<Block>>,
+  // Addresses that are part of at least one compiled block
+  covered: Vec<bool>,
+  // Addresses of code that has been written to
+  modified: Vec<bool>,
+  // Bumped every time the cache is thrown away
+  generation: usize,
+}
+
+impl CodeCache {
+  pub fn generation(&self) -> usize {
+    self.generation
+  }
+
+  // Hands out the block starting at `address`, compiling it first if needed.
+  //   Give it back with `return_block` when done. Returns `None` if the code
+  //   there has to be interpreted.
+  pub fn take_block<W: Word>(&mut self, address: usize, memory: &Memory<W>) -> Option<Block> {
+    if address >= MAX_COMPILED_ADDRESS {
+      return None;
+    }
+    if let Some(block) = self.blocks.get_mut(address).and_then(|block| block.take()) {
+      return Some(block);
+    }
+
+    let block = self.compile_block(address, memory)?;
+    let end = block.instructions.last().unwrap().next;
+    if self.covered.len() < end {
+      self.covered.resize(end, false);
+    }
+    for covered in self.covered[address..end].iter_mut() {
+      *covered = true;
+    }
+    Some(block)
+  }
+
+  // `generation` is what `generation()` returned when the block was taken. If
+  //   the cache was thrown away since then, the block may be stale.
+  pub fn return_block(&mut self, block: Block, generation: usize) {
+    if generation != self.generation {
+      return;
+    }
+    if self.blocks.len() <= block.start {
+      self.blocks.resize_with(block.start + 1, || None);
+    }
+    let start = block.start;
+    self.blocks[start] = Some(block);
+  }
+
+  // Has to be called after every write to memory
+  pub fn note_write(&mut self, address: usize) {
+    if !self.covered.get(address).copied().unwrap_or(false) {
+      return;
+    }
+
+    if self.modified.len() <= address {
+      self.modified.resize(address + 1, false);
+    }
+    self.modified[address] = true;
+    self.blocks.clear();
+    self.covered.clear();
+    self.generation += 1;
+  }
+
+  fn is_modified(&self, address: usize) -> bool {
+    self.modified.get(address).copied().unwrap_or(false)
+  }
+
+  fn compile_block<W: Word>(&self, start: usize, memory: &Memory<W>) -> Option<Block> {
+    let mut instructions = Vec::new();
+    let mut address = start;
+
+    while instructions.len() < MAX_BLOCK_LEN && memory.is_addressable(address) {
+      let instruction = match decode(memory, address) {
+        Some(instruction) => instruction,
+        None => break,
+      };
+      let next = address + instruction.size();
+      if next > MAX_COMPILED_ADDRESS || (address..next).any(|a| self.is_modified(a)) {
+        break;
+      }
+      let op = match compile_op(&instruction) {
+        Some(op) => op,
+        None => break,
+      };
+
+      instructions.push(CompiledInstruction { address, next, op });
+      address = next;
+      if let Op::JumpIfTrue(..) | Op::JumpIfFalse(..) | Op::Halt = op {
+        break;
+      }
+    }
+
+    if instructions.is_empty() {
+      None
+    } else {
+      Some(Block { start, instructions })
+    }
+  }
+}
+
+// `None` if the instruction doesn't decode, or if any of its values don't
+//   fit in an isize
+fn decode<W: Word>(memory: &Memory<W>, address: usize) -> Option<Instruction> {
+  let value = |address| memory.read(address).to_isize();
+  let instruction = Instruction::decode(value(address)?, |i| value(address + 1 + i).unwrap_or(0)).ok()?;
+  if (1..instruction.size()).all(|i| value(address + i).is_some()) {
+    Some(instruction)
+  } else {
+    None
+  }
+}
+
+// Returns `None` for instructions that are guaranteed to fail, so the
+//   interpreter can report the error.
+fn compile_op(instruction: &Instruction) -> Option<Op> {
+  let params = instruction.params();
+  let operand = |param: &Parameter| match param.mode {
+    ParameterMode::Immediate => Some(Operand::Immediate(param.value)),
+    ParameterMode::Position if param.value < 0 => None,
+    ParameterMode::Position => Some(Operand::Position(param.value as usize)),
+    ParameterMode::Relative => Some(Operand::Relative(param.value)),
+  };
+  let target = |param: &Parameter| match param.mode {
+    ParameterMode::Immediate => None,
+    _ => operand(param),
+  };
+
+  Some(match instruction.opcode {
+    Opcode::Add => Op::Add(operand(&params[0])?, operand(&params[1])?, target(&params[2])?),
+    Opcode::Multiply => Op::Multiply(operand(&params[0])?, operand(&params[1])?, target(&params[2])?),
+    Opcode::Input => Op::Input(target(&params[0])?),
+    Opcode::Output => Op::Output(operand(&params[0])?),
+    Opcode::JumpIfTrue => Op::JumpIfTrue(operand(&params[0])?, operand(&params[1])?),
+    Opcode::JumpIfFalse => Op::JumpIfFalse(operand(&params[0])?, operand(&params[1])?),
+    Opcode::LessThan => Op::LessThan(operand(&params[0])?, operand(&params[1])?, target(&params[2])?),
+    Opcode::Equals => Op::Equals(operand(&params[0])?, operand(&params[1])?, target(&params[2])?),
+    Opcode::RelativeBaseOffset => Op::RelativeBaseOffset(operand(&params[0])?),
+    Opcode::Halt => Op::Halt,
+  })
+}
+
+
+#[cfg(test)]
+mod tests {
+  use crate::{parse_program_from_file, IntcodeError, Program, Yield};
+
+  // Runs the program both ways with the same inputs, and checks that
+  //   everything observable comes out the same
+  fn assert_same_as_interpreter(values: &[isize], inputs: &[isize]) {
+    let mut interpreted = Program::new(values);
+    let mut compiled = Program::new(values);
+    compiled.enable_compiler();
+
+    let expected = interpreted.run(inputs);
+    assert_eq!(compiled.run(inputs), expected, "program: {:?}", values);
+    assert_eq!(compiled.is_halted(), interpreted.is_halted());
+    assert_eq!(compiled.instruction_pointer(), interpreted.instruction_pointer());
+    assert_eq!(compiled.relative_base(), interpreted.relative_base());
+    assert_eq!(compiled.instruction_count(), interpreted.instruction_count());
+    assert_eq!(compiled.pending_inputs(), interpreted.pending_inputs());
+    assert_eq!(compiled.snapshot(), interpreted.snapshot());
+  }
+
+  #[test]
+  fn matches_the_interpreter_on_examples() {
+    let day_5_compare = vec![
+      3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+      1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+      999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99,
+    ];
+    let cases: Vec<(Vec<isize>, Vec<isize>)> = vec![
+      // Day 2
+      (vec![1,9,10,3,2,3,11,0,99,30,40,50], vec![]),
+      (vec![1,1,1,4,99,5,6,0,99], vec![]),
+      // Day 5
+      (vec![3,0,4,0,99], vec![42]),
+      (vec![1002,4,3,4,33], vec![]),
+      (vec![3,9,8,9,10,9,4,9,99,-1,8], vec![8]),
+      (vec![3,3,1107,-1,8,3,4,3,99], vec![3]),
+      (vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], vec![0]),
+      (vec![3,3,1105,-1,9,1101,0,0,12,4,12,99,1], vec![5]),
+      (day_5_compare.clone(), vec![7]),
+      (day_5_compare.clone(), vec![8]),
+      (day_5_compare, vec![9]),
+      // Day 9
+      (vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99], vec![]),
+      (vec![1102,34915192,34915192,7,4,7,99,0], vec![]),
+      (vec![104,1125899906842624,99], vec![]),
+      // Errors
+      (vec![1,0,0,0,42], vec![]),
+      (vec![1101,1,1,-1,99], vec![]),
+      (vec![109,-10,204,0,99], vec![]),
+      (vec![1105,1,-3], vec![]),
+      (vec![1102,4611686018427387904,2,0,99], vec![]),
+      (vec![109,9223372036854775807,209,1,99], vec![]),
+      // Starved for input
+      (vec![3,0,3,1,99], vec![1]),
+    ];
+
+    for (values, inputs) in cases.iter() {
+      assert_same_as_interpreter(values, inputs);
+    }
+  }
+
+  #[test]
+  fn matches_the_interpreter_on_puzzle_inputs() {
+    let puzzle_input = |filename: &str| parse_program_from_file(
+      &format!("{}/../{}", env!("CARGO_MANIFEST_DIR"), filename),
+    );
+
+    let mut day_2 = puzzle_input("day-02/input.txt");
+    day_2[1] = 12;
+    day_2[2] = 2;
+    assert_same_as_interpreter(&day_2, &[]);
+
+    let day_5 = puzzle_input("day-05/puzzle-input.txt");
+    assert_same_as_interpreter(&day_5, &[1]);
+    assert_same_as_interpreter(&day_5, &[5]);
+
+    let day_7 = puzzle_input("day-07/puzzle-input.txt");
+    assert_same_as_interpreter(&day_7, &[3, 0]);
+    assert_same_as_interpreter(&day_7, &[7, 0]);
+
+    let day_9 = puzzle_input("day-09/puzzle-input.txt");
+    assert_same_as_interpreter(&day_9, &[1]);
+    assert_same_as_interpreter(&day_9, &[2]);
+  }
+
+  #[test]
+  fn handles_self_modifying_code() {
+    // Counts down from 3 by rewriting the immediate operand of its own
+    //   output instruction (address 1)
+    let source = [
+      // 0: out #3, patched each time around
+      104,3,
+      // 2: add [1], #-1, [1]
+      1001,1,-1,1,
+      // 6: jt [1], #0
+      1005,1,0,
+      // 9: out [1], then halt
+      4,1,99,
+    ];
+    assert_same_as_interpreter(&source, &[]);
+
+    let mut program = Program::new(&source);
+    program.enable_compiler();
+    assert_eq!(program.run(&[]), Ok(vec![3, 2, 1, 0]));
+
+    // Patching code from outside also has to invalidate it
+    let mut program = Program::new(&[104,1,1105,1,0]);
+    program.enable_compiler();
+    assert_eq!(program.resume(), Ok(Yield::Output(1)));
+    assert_eq!(program.resume(), Ok(Yield::Output(1)));
+    program.write(1, 7);
+    assert_eq!(program.resume(), Ok(Yield::Output(7)));
+  }
+
+  #[test]
+  fn writes_into_the_running_block_take_effect() {
+    // The add at 0 rewrites the output at 4 before it runs, in the same block
+    let program = [1101,5,0,5, 104,0, 99];
+    assert_same_as_interpreter(&program, &[]);
+
+    let mut compiled = Program::new(&program);
+    compiled.enable_compiler();
+    assert_eq!(compiled.run(&[]), Ok(vec![5]));
+  }
+
+  #[test]
+  fn loop_limit_still_applies() {
+    let mut program = Program::new(&[1105,1,0]);
+    program.enable_compiler();
+    assert!(matches!(program.resume(), Err(IntcodeError::LoopLimitExceeded { .. })));
+  }
+}
@@ -0,0 +1,1276 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::compiler::{Block, CodeCache, Op, Operand};
+use crate::device::{InputDevice, OutputDevice};
+use crate::error::IntcodeError;
+use crate::instruction::{DecodeError, Instruction, Opcode, Parameter, ParameterMode};
+use crate::limits::{Limit, Limits};
+use crate::memory::Memory;
+use crate::profile::Profile;
+use crate::snapshot::Snapshot;
+use crate::trace::{MemoryWrite, TraceEvent, TraceSink};
+use crate::word::Word;
+
+const DEBUG: bool = false;
+// const DEBUG: bool = true;
+
+// How many times around `resume`'s loop between checks of the deadline.
+//   Looking at the clock every instruction would slow everything down.
+const DEADLINE_CHECK_INTERVAL: usize = 256;
+
+// Generic over the word stored in each memory cell, see word.rs
+pub struct Program<W: Word = isize> {
+  values: Memory<W>,
+  status: ProgramStatus,
+  instruction_pointer: usize,
+  relative_base: isize,
+  inputs: VecDeque<W>,
+  instruction_count: usize,
+  trace_sink: Option<Box<dyn TraceSink<W> + Send>>,
+  profile: Option<Profile>,
+  input_device: Option<Box<dyn InputDevice<W> + Send>>,
+  output_device: Option<Box<dyn OutputDevice<W> + Send>>,
+  // Set whenever a device is used, so `resume` knows the program isn't stuck
+  did_device_io: bool,
+  // Only there when the compiled tier is turned on
+  code_cache: Option<CodeCache>,
+  limits: Limits,
+  // What's left of the budgets in `limits`. The instruction budget is
+  //   stored as the instruction count it runs out at.
+  instruction_budget_end: Option<usize>,
+  outputs_remaining: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgramStatus {
+  Running,
+  Halted,
+}
+
+// Clones get the full machine state, but not the trace sink, profile or
+//   devices. A forked copy writing into the same trace as the original would
+//   just be confusing, and devices generally can't be shared anyway.
+impl<W: Word> Clone for Program<W> {
+  fn clone(&self) -> Self {
+    Program {
+      values: self.values.clone(),
+      status: self.status,
+      instruction_pointer: self.instruction_pointer,
+      relative_base: self.relative_base,
+      inputs: self.inputs.clone(),
+      instruction_count: self.instruction_count,
+      trace_sink: None,
+      profile: None,
+      input_device: None,
+      output_device: None,
+      did_device_io: false,
+      code_cache: self.code_cache.as_ref().map(|_| CodeCache::default()),
+      limits: self.limits,
+      instruction_budget_end: self.instruction_budget_end,
+      outputs_remaining: self.outputs_remaining,
+    }
+  }
+}
+
+// What the program was doing when `resume` handed control back to the caller
+#[derive(Debug, PartialEq)]
+pub enum Yield<W = isize> {
+  // Hit an Input instruction with no queued input. Calling `resume` again
+  //   (after `push_input`) retries that same instruction.
+  NeedsInput,
+  Output(W),
+  Halted,
+  // Ran out of one of the budgets set with `set_limits`. Nothing was lost,
+  //   see limits.rs.
+  BudgetExhausted(Limit),
+}
+
+impl Program {
+  pub fn new(values: &[isize]) -> Program {
+    Program::from_words(values)
+  }
+}
+
+impl<W: Word> Program<W> {
+  // `new`, for any word type
+  pub fn from_words(values: &[W]) -> Self {
+    Program {
+      values: Memory::new(values),
+      status: ProgramStatus::Running,
+      instruction_pointer: 0,
+      relative_base: 0,
+      inputs: VecDeque::new(),
+      instruction_count: 0,
+      trace_sink: None,
+      profile: None,
+      input_device: None,
+      output_device: None,
+      did_device_io: false,
+      code_cache: None,
+      limits: Limits::default(),
+      instruction_budget_end: None,
+      outputs_remaining: None,
+    }
+  }
+
+  // Captures everything needed to pick up execution later, see snapshot.rs.
+  //   Outputs aren't buffered inside the program (`resume` hands each one
+  //   back right away), so there are none to save.
+  pub fn snapshot(&self) -> Snapshot<W> {
+    Snapshot {
+      halted: self.is_halted(),
+      instruction_pointer: self.instruction_pointer,
+      relative_base: self.relative_base,
+      instruction_count: self.instruction_count,
+      memory_limit: self.values.limit(),
+      inputs: self.inputs.iter().cloned().collect(),
+      memory: self.values.dense_values().to_vec(),
+      sparse_memory: self.values.sparse_values(),
+    }
+  }
+
+  // Limits aren't part of a snapshot (a deadline couldn't be saved anyway),
+  //   so a restored program has the default ones, like a new program does
+  //   (a clone keeps them). Set them again to carry on under a budget.
+  pub fn restore(snapshot: &Snapshot<W>) -> Self {
+    let mut values = Memory::new(&snapshot.memory);
+    for (address, value) in snapshot.sparse_memory.iter() {
+      values.write(*address, value.clone());
+    }
+    values.set_limit(snapshot.memory_limit);
+
+    Program {
+      values,
+      status: if snapshot.halted { ProgramStatus::Halted } else { ProgramStatus::Running },
+      instruction_pointer: snapshot.instruction_pointer,
+      relative_base: snapshot.relative_base,
+      inputs: snapshot.inputs.iter().cloned().collect(),
+      instruction_count: snapshot.instruction_count,
+      trace_sink: None,
+      profile: None,
+      input_device: None,
+      output_device: None,
+      did_device_io: false,
+      code_cache: None,
+      limits: Limits::default(),
+      instruction_budget_end: None,
+      outputs_remaining: None,
+    }
+  }
+
+  pub fn is_halted(&self) -> bool {
+    matches!(self.status, ProgramStatus::Halted)
+  }
+
+  // Total number of instructions executed so far
+  pub fn instruction_count(&self) -> usize {
+    self.instruction_count
+  }
+
+  pub fn instruction_pointer(&self) -> usize {
+    self.instruction_pointer
+  }
+
+  pub fn relative_base(&self) -> isize {
+    self.relative_base
+  }
+
+  // Inputs that have been pushed but not read by the program yet
+  pub fn pending_inputs(&self) -> &VecDeque<W> {
+    &self.inputs
+  }
+
+  pub fn read(&self, address: usize) -> W {
+    self.values.read(address)
+  }
+
+  pub fn write(&mut self, address: usize, value: W) {
+    self.store(address, value);
+  }
+
+  pub fn memory_limit(&self) -> Option<usize> {
+    self.values.limit()
+  }
+
+  // Caps the addressable memory. Touching an address at or above the limit
+  //   is an `AddressOutOfRange` error. `None` (the default) means unbounded.
+  pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+    self.values.set_limit(limit);
+  }
+
+  pub fn limits(&self) -> &Limits {
+    &self.limits
+  }
+
+  // Replaces the limits, and starts fresh budgets counting from here
+  pub fn set_limits(&mut self, limits: Limits) {
+    self.limits = limits;
+    self.instruction_budget_end = limits.instructions.map(|budget| self.instruction_count + budget);
+    self.outputs_remaining = limits.outputs;
+  }
+
+  pub fn push_input(&mut self, value: W) {
+    self.inputs.push_back(value);
+  }
+
+  // Runs until the program needs more input or halts, returning all outputs
+  //   produced along the way.
+  // Running out of budget is a `BudgetExhausted` error here, and the outputs
+  //   produced before that are lost. Use `resume` to keep them. So is going
+  //   over the loop limit, which counts every instruction in the run.
+  pub fn run(&mut self, inputs: &[W]) -> Result<Vec<W>, IntcodeError> {
+    if self.is_halted() {
+      return Err(IntcodeError::AlreadyHalted);
+    }
+
+    if DEBUG {
+      println!();
+      println!("-----------------------------");
+      println!("Inputs: {:?}", inputs);
+      println!("Initial program: {:?}", self.values);
+    }
+
+    self.inputs.extend(inputs.iter().cloned());
+    let mut output = Vec::new();
+    // One count for the whole run, so the loop limit still catches a program
+    //   that outputs forever instead of collecting outputs until memory runs
+    //   out
+    let mut iteration_count = 0;
+
+    loop {
+      match self.resume_counting(&mut iteration_count)? {
+        Yield::Output(value) => {
+          output.push(value);
+          self.count_iteration(&mut iteration_count)?;
+        },
+        Yield::BudgetExhausted(limit) => {
+          return Err(IntcodeError::BudgetExhausted {
+            instruction_pointer: self.instruction_pointer,
+            limit,
+          });
+        },
+        Yield::NeedsInput | Yield::Halted => return Ok(output),
+      }
+    }
+  }
+
+  // Runs until the next output, until an Input instruction finds no queued
+  //   input, or until the program halts -- whichever comes first. See
+  //   device.rs for how attached devices change that.
+  // On error, the instruction pointer is left on the failing instruction.
+  pub fn resume(&mut self) -> Result<Yield<W>, IntcodeError> {
+    self.resume_counting(&mut 0)
+  }
+
+  // `resume`, counting towards the loop limit from `iteration_count`
+  fn resume_counting(&mut self, iteration_count: &mut usize) -> Result<Yield<W>, IntcodeError> {
+    if self.needs_only_execute() {
+      return self.resume_executing(iteration_count);
+    }
+
+    for round in 0.. {
+      if round % DEADLINE_CHECK_INTERVAL == 0 && self.past_deadline() {
+        return Ok(Yield::BudgetExhausted(Limit::Deadline));
+      }
+
+      let event = match self.take_compiled_block() {
+        Some((block, generation)) => {
+          let event = self.execute_block(&block, generation, iteration_count);
+          self.code_cache.as_mut().unwrap().return_block(block, generation);
+          event?
+        },
+        None => self.interpret_step(iteration_count)?,
+      };
+      if let Some(event) = event {
+        return Ok(event);
+      }
+    }
+    unreachable!()
+  }
+
+  // True if nothing optional is switched on that would have to look at every
+  //   step: no tracing, profiling, compiled tier, memory budget or deadline
+  fn needs_only_execute(&self) -> bool {
+    self.trace_sink.is_none()
+      && self.profile.is_none()
+      && self.code_cache.is_none()
+      && self.limits.memory.is_none()
+      && self.limits.deadline.is_none()
+  }
+
+  // `resume` when `needs_only_execute`: `step` without the checks for things
+  //   that aren't switched on
+  fn resume_executing(&mut self, iteration_count: &mut usize) -> Result<Yield<W>, IntcodeError> {
+    loop {
+      if self.is_halted() {
+        return Ok(Yield::Halted);
+      }
+      if self.instruction_budget_spent() {
+        return Ok(Yield::BudgetExhausted(Limit::Instructions));
+      }
+      let instruction = self.decode_next_instruction()?;
+      match self.execute(instruction)? {
+        Some(event) => return Ok(event),
+        None => self.count_iteration(iteration_count)?,
+      }
+    }
+  }
+
+  fn past_deadline(&self) -> bool {
+    !self.is_halted() && self.limits.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+  }
+
+  fn instruction_budget_spent(&self) -> bool {
+    self.instruction_budget_end.is_some_and(|end| self.instruction_count >= end)
+  }
+
+  // True if `address` is beyond the memory budget, but not beyond the end of
+  //   memory
+  fn over_memory_budget(&self, address: usize) -> bool {
+    self.limits.memory.is_some_and(|budget| address >= budget) && self.values.is_addressable(address)
+  }
+
+  // `step`, plus the bookkeeping for `resume`'s loop limit
+  fn interpret_step(&mut self, iteration_count: &mut usize) -> Result<Option<Yield<W>>, IntcodeError> {
+    let event = self.step()?;
+    if event.is_none() {
+      self.count_iteration(iteration_count)?;
+    }
+    Ok(event)
+  }
+
+  fn count_iteration(&mut self, iteration_count: &mut usize) -> Result<(), IntcodeError> {
+    if self.did_device_io {
+      self.did_device_io = false;
+      *iteration_count = 0;
+    }
+    *iteration_count += 1;
+    if self.limits.loop_iterations.is_some_and(|limit| *iteration_count >= limit) {
+      return Err(IntcodeError::LoopLimitExceeded {
+        instruction_pointer: self.instruction_pointer,
+        iterations: *iteration_count,
+      });
+    }
+    Ok(())
+  }
+
+  // Executes a single instruction. Returns `None` if the program can just
+  //   keep going, or the reason it had to stop (same as `resume`).
+  // A `NeedsInput` step doesn't execute anything, and neither does stepping a
+  //   halted program.
+  pub fn step(&mut self) -> Result<Option<Yield<W>>, IntcodeError> {
+    if self.is_halted() {
+      return Ok(Some(Yield::Halted));
+    }
+
+    if self.instruction_budget_spent() {
+      return Ok(Some(Yield::BudgetExhausted(Limit::Instructions)));
+    }
+
+    let instruction_pointer = self.instruction_pointer;
+    let relative_base = self.relative_base;
+    let result = self.decode_next_instruction().and_then(|instruction| {
+      let result = if self.trace_sink.is_some() {
+        self.execute_traced(instruction)?
+      } else {
+        self.execute(instruction)?
+      };
+      Ok((instruction, result))
+    });
+    let (instruction, result) = match result {
+      Ok(result) => result,
+      Err(IntcodeError::AddressOutOfRange { address, .. }) if self.over_memory_budget(address) => {
+        return Ok(Some(Yield::BudgetExhausted(Limit::Memory)));
+      },
+      Err(IntcodeError::InstructionPointerOutOfRange { instruction_pointer })
+        if self.over_memory_budget(instruction_pointer) =>
+      {
+        return Ok(Some(Yield::BudgetExhausted(Limit::Memory)));
+      },
+      Err(err) => return Err(err),
+    };
+
+    if let Some(profile) = self.profile.as_mut() {
+      if did_execute(&result) {
+        profile.record(
+          instruction_pointer,
+          &instruction,
+          relative_base,
+          self.instruction_pointer,
+          self.relative_base,
+        );
+      }
+    }
+    Ok(result)
+  }
+
+  pub fn set_trace_sink(&mut self, sink: Box<dyn TraceSink<W> + Send>) {
+    self.trace_sink = Some(sink);
+  }
+
+  pub fn take_trace_sink(&mut self) -> Option<Box<dyn TraceSink<W> + Send>> {
+    self.trace_sink.take()
+  }
+
+  // Starts a fresh profile, see profile.rs. It keeps counting until it's
+  //   taken, errors included, so it can show what tripped the loop limit.
+  pub fn enable_profiler(&mut self) {
+    self.profile = Some(Profile::new());
+  }
+
+  pub fn profile(&self) -> Option<&Profile> {
+    self.profile.as_ref()
+  }
+
+  pub fn take_profile(&mut self) -> Option<Profile> {
+    self.profile.take()
+  }
+
+  // Turns on the compiled tier, see compiler.rs. Results are exactly the same
+  //   as with the interpreter, it's just faster on long runs.
+  pub fn enable_compiler(&mut self) {
+    if self.code_cache.is_none() {
+      self.code_cache = Some(CodeCache::default());
+    }
+  }
+
+  pub fn disable_compiler(&mut self) {
+    self.code_cache = None;
+  }
+
+  pub fn compiler_enabled(&self) -> bool {
+    self.code_cache.is_some()
+  }
+
+  pub fn set_input_device(&mut self, device: Box<dyn InputDevice<W> + Send>) {
+    self.input_device = Some(device);
+  }
+
+  pub fn take_input_device(&mut self) -> Option<Box<dyn InputDevice<W> + Send>> {
+    self.input_device.take()
+  }
+
+  pub fn set_output_device(&mut self, device: Box<dyn OutputDevice<W> + Send>) {
+    self.output_device = Some(device);
+  }
+
+  pub fn take_output_device(&mut self) -> Option<Box<dyn OutputDevice<W> + Send>> {
+    self.output_device.take()
+  }
+
+  // This and `execute` are the interpreter's inner loop. Inlining them both
+  //   into it makes BOOST run about 25% faster (see benches/boost.rs).
+  #[inline(always)]
+  fn decode_next_instruction(&self) -> Result<Instruction, IntcodeError> {
+    if !self.is_addressable(self.instruction_pointer) {
+      return Err(IntcodeError::InstructionPointerOutOfRange {
+        instruction_pointer: self.instruction_pointer,
+      });
+    }
+
+    let opcode_value = self.values.read(self.instruction_pointer).to_isize().ok_or_else(|| self.invalid_opcode())?;
+    // Params too big for an isize are clamped here, see `param_word`
+    Instruction::decode(
+      opcode_value,
+      |i| self.values.read(self.instruction_pointer + 1 + i).saturating_to_isize(),
+    )
+    .map_err(|err| match err {
+      DecodeError::InvalidOpcode => self.invalid_opcode(),
+      DecodeError::InvalidParameterMode(mode) => IntcodeError::InvalidParameterMode {
+        instruction_pointer: self.instruction_pointer,
+        opcode: opcode_value,
+        mode,
+      },
+    })
+  }
+
+  fn execute_traced(&mut self, instruction: Instruction) -> Result<Option<Yield<W>>, IntcodeError> {
+    let instruction_pointer = self.instruction_pointer;
+    let opcode = instruction.opcode;
+    let params = instruction.params();
+
+    // Resolve operands up front, since executing may overwrite them. Writes
+    //   are resolved to the address being written to.
+    let mut operands: Vec<W> = Vec::with_capacity(params.len());
+    let mut write_address = None;
+    for i in 0..params.len() {
+      // The target of a jump that isn't taken is never used, and may not
+      //   even be a valid address
+      let skip = match opcode {
+        Opcode::JumpIfTrue => i == 1 && operands[0].is_zero(),
+        Opcode::JumpIfFalse => i == 1 && !operands[0].is_zero(),
+        _ => false,
+      };
+      if skip {
+        break;
+      }
+      operands.push(
+        if opcode.write_param() == Some(i) {
+          // A bad write address is an error, but so might be computing the
+          //   value, or waiting for input, and `execute` decides which comes
+          //   first. Leave it to that.
+          let address = match self.get_index_for_param(params, i) {
+            Ok(address) => address,
+            Err(_) => return self.execute(instruction),
+          };
+          write_address = Some(address);
+          W::from_isize(address as isize)
+        } else {
+          self.get_param_val(params, i)?
+        }
+      );
+    }
+
+    let result = self.execute(instruction)?;
+    if !did_execute(&result) {
+      return Ok(result);
+    }
+
+    let output = match opcode {
+      Opcode::Output => Some(operands[0].clone()),
+      _ => None,
+    };
+    let write = write_address.map(|address| MemoryWrite { address, value: self.values.read(address) });
+    let event = TraceEvent {
+      step: self.instruction_count - 1,
+      instruction_pointer,
+      opcode,
+      operands,
+      relative_base: match opcode {
+        Opcode::RelativeBaseOffset => Some(self.relative_base),
+        _ => None,
+      },
+      input: match opcode {
+        Opcode::Input => write.as_ref().map(|write| write.value.clone()),
+        _ => None,
+      },
+      write,
+      output,
+    };
+
+    if let Some(sink) = self.trace_sink.as_mut() {
+      sink.record(&event).map_err(|err| IntcodeError::TraceFailed {
+        instruction_pointer,
+        message: err.to_string(),
+      })?;
+    }
+    Ok(result)
+  }
+
+  #[inline(always)]
+  fn execute(&mut self, instruction: Instruction) -> Result<Option<Yield<W>>, IntcodeError> {
+    let opcode = instruction.opcode;
+    let params = instruction.params();
+
+    let mut should_increment_pointer = true;
+    let mut event = None;
+
+    if DEBUG {
+      println!("Doing opcode {:?}", opcode);
+      println!("\tparams: {:?}", params);
+    }
+
+    match opcode {
+      Opcode::Add => {
+        let a = self.get_param_val(params, 0)?;
+        let b = self.get_param_val(params, 1)?;
+        let sum = a.checked_add(&b).ok_or_else(|| self.overflow())?;
+        self.write_value(params, 2, sum)?;
+      },
+      Opcode::Multiply => {
+        let a = self.get_param_val(params, 0)?;
+        let b = self.get_param_val(params, 1)?;
+        let product = a.checked_mul(&b).ok_or_else(|| self.overflow())?;
+        self.write_value(params, 2, product)?;
+      },
+      Opcode::Input => {
+        // Check the destination first so a bad write doesn't eat an input
+        self.get_index_for_param(params, 0)?;
+        match self.next_input()? {
+          Some(value) => self.write_value(params, 0, value)?,
+          None => return Ok(Some(Yield::NeedsInput)),
+        }
+      },
+      Opcode::Output => {
+        let value = self.get_param_val(params, 0)?;
+        if self.outputs_remaining == Some(0) {
+          return Ok(Some(Yield::BudgetExhausted(Limit::Outputs)));
+        }
+        event = self.send_output(value)?;
+      },
+      Opcode::JumpIfTrue => {
+        if !self.get_param_val(params, 0)?.is_zero() {
+          should_increment_pointer = false;
+          self.instruction_pointer = self.get_jump_target(params, 1)?;
+        }
+      },
+      Opcode::JumpIfFalse => {
+        if self.get_param_val(params, 0)?.is_zero() {
+          should_increment_pointer = false;
+          self.instruction_pointer = self.get_jump_target(params, 1)?;
+        }
+      },
+      Opcode::LessThan => {
+        let is_less_than =
+          self.get_param_val(params, 0)? <
+          self.get_param_val(params, 1)?;
+        self.write_value(params, 2, W::from_isize(if is_less_than { 1 } else { 0 }))?;
+      },
+      Opcode::Equals => {
+        let is_equal =
+          self.get_param_val(params, 0)? ==
+          self.get_param_val(params, 1)?;
+        self.write_value(params, 2, W::from_isize(if is_equal { 1 } else { 0 }))?;
+      },
+      Opcode::RelativeBaseOffset => {
+        let offset = self.get_param_val(params, 0)?;
+        self.relative_base = offset
+          .to_isize()
+          .and_then(|offset| self.relative_base.checked_add(offset))
+          .ok_or_else(|| self.overflow())?;
+      },
+      Opcode::Halt => {
+        self.status = ProgramStatus::Halted;
+        self.instruction_count += 1;
+        return Ok(Some(Yield::Halted));
+      }
+    }
+
+    if should_increment_pointer {
+      self.instruction_pointer += instruction.size();
+    }
+    self.instruction_count += 1;
+
+    if DEBUG {
+      println!("\tUpdated state: {:?}", self.values);
+      println!("\tinstruction_pointer: {:?}", self.instruction_pointer);
+    }
+
+    Ok(event)
+  }
+
+  // Tracing and profiling need the interpreter, since that's what sees each
+  //   instruction
+  fn take_compiled_block(&mut self) -> Option<(Block, usize)> {
+    if self.trace_sink.is_some() || self.profile.is_some() || self.is_halted() {
+      return None;
+    }
+    let cache = self.code_cache.as_mut()?;
+    let generation = cache.generation();
+    cache.take_block(self.instruction_pointer, &self.values).map(|block| (block, generation))
+  }
+
+  // Runs a compiled block from the top. Has the same effect as stepping
+  //   through it one instruction at a time, and stops at the same points.
+  fn execute_block(
+    &mut self,
+    block: &Block,
+    generation: usize,
+    iteration_count: &mut usize,
+  ) -> Result<Option<Yield<W>>, IntcodeError> {
+    for instruction in block.instructions.iter() {
+      self.instruction_pointer = instruction.address;
+      let mut next = instruction.next;
+      if self.instruction_budget_spent() {
+        return Ok(Some(Yield::BudgetExhausted(Limit::Instructions)));
+      }
+
+      let ok = match instruction.op {
+        Op::Add(a, b, target) => self.compiled_binary(a, b, target, |a, b| a.checked_add(b)),
+        Op::Multiply(a, b, target) => self.compiled_binary(a, b, target, |a, b| a.checked_mul(b)),
+        Op::LessThan(a, b, target) => {
+          self.compiled_binary(a, b, target, |a, b| Some(W::from_isize((a < b) as isize)))
+        },
+        Op::Equals(a, b, target) => {
+          self.compiled_binary(a, b, target, |a, b| Some(W::from_isize((a == b) as isize)))
+        },
+        Op::Input(target) => match self.compiled_address(target) {
+          Some(address) => match self.next_input()? {
+            Some(value) => {
+              self.store(address, value);
+              true
+            },
+            None => return Ok(Some(Yield::NeedsInput)),
+          },
+          None => false,
+        },
+        Op::Output(a) => match self.compiled_operand(a) {
+          Some(_) if self.outputs_remaining == Some(0) => {
+            return Ok(Some(Yield::BudgetExhausted(Limit::Outputs)));
+          },
+          Some(value) => {
+            let event = self.send_output(value)?;
+            if event.is_some() {
+              self.instruction_pointer = next;
+              self.instruction_count += 1;
+              return Ok(event);
+            }
+            true
+          },
+          None => false,
+        },
+        Op::JumpIfTrue(condition, target) | Op::JumpIfFalse(condition, target) => {
+          let jump_if = matches!(instruction.op, Op::JumpIfTrue(..));
+          match self.compiled_operand(condition) {
+            Some(condition) if condition.is_zero() != jump_if => {
+              match self.compiled_operand(target).and_then(|target| target.to_isize()) {
+                Some(target) if target >= 0 => {
+                  next = target as usize;
+                  true
+                },
+                _ => false,
+              }
+            },
+            Some(_) => true,
+            None => false,
+          }
+        },
+        Op::RelativeBaseOffset(a) => {
+          let relative_base = self
+            .compiled_operand(a)
+            .and_then(|offset| offset.to_isize())
+            .and_then(|offset| self.relative_base.checked_add(offset));
+          match relative_base {
+            Some(relative_base) => {
+              self.relative_base = relative_base;
+              true
+            },
+            None => false,
+          }
+        },
+        Op::Halt => {
+          self.status = ProgramStatus::Halted;
+          self.instruction_count += 1;
+          return Ok(Some(Yield::Halted));
+        },
+      };
+
+      if !ok {
+        // Something is off, like a bad address or an overflow. Let the
+        //   interpreter run this instruction, so it can report the error.
+        return self.interpret_step(iteration_count);
+      }
+
+      self.instruction_pointer = next;
+      self.instruction_count += 1;
+      self.count_iteration(iteration_count)?;
+
+      // The program wrote to compiled code, maybe even this block
+      if self.code_cache.as_ref().unwrap().generation() != generation {
+        return Ok(None);
+      }
+    }
+    Ok(None)
+  }
+
+  fn compiled_binary(
+    &mut self,
+    a: Operand,
+    b: Operand,
+    target: Operand,
+    f: impl Fn(&W, &W) -> Option<W>,
+  ) -> bool {
+    match (self.compiled_operand(a), self.compiled_operand(b), self.compiled_address(target)) {
+      (Some(a), Some(b), Some(address)) => match f(&a, &b) {
+        Some(value) => {
+          self.store(address, value);
+          true
+        },
+        None => false,
+      },
+      _ => false,
+    }
+  }
+
+  fn compiled_operand(&self, operand: Operand) -> Option<W> {
+    match operand {
+      Operand::Immediate(value) => Some(W::from_isize(value)),
+      _ => self.compiled_address(operand).map(|address| self.values.read(address)),
+    }
+  }
+
+  fn compiled_address(&self, operand: Operand) -> Option<usize> {
+    let address = match operand {
+      Operand::Immediate(_) => return None,
+      Operand::Position(address) => address,
+      Operand::Relative(offset) => match self.relative_base.checked_add(offset) {
+        Some(address) if address >= 0 => address as usize,
+        _ => return None,
+      },
+    };
+    if self.is_addressable(address) {
+      Some(address)
+    } else {
+      None
+    }
+  }
+
+  // Every memory write goes through here, so the compiled tier can notice
+  //   self-modifying code
+  fn store(&mut self, address: usize, value: W) {
+    self.values.write(address, value);
+    if let Some(cache) = self.code_cache.as_mut() {
+      cache.note_write(address);
+    }
+  }
+
+  // Hands the value to the output device, or returns the yield if there
+  //   isn't one
+  fn send_output(&mut self, value: W) -> Result<Option<Yield<W>>, IntcodeError> {
+    let instruction_pointer = self.instruction_pointer;
+    if let Some(remaining) = self.outputs_remaining.as_mut() {
+      *remaining -= 1;
+    }
+    match self.output_device.as_mut() {
+      Some(device) => {
+        device.write(value).map_err(|err| IntcodeError::DeviceFailed {
+          instruction_pointer,
+          message: err.to_string(),
+        })?;
+        self.did_device_io = true;
+        Ok(None)
+      },
+      None => Ok(Some(Yield::Output(value))),
+    }
+  }
+
+  fn next_input(&mut self) -> Result<Option<W>, IntcodeError> {
+    if let Some(value) = self.inputs.pop_front() {
+      return Ok(Some(value));
+    }
+    let instruction_pointer = self.instruction_pointer;
+    match self.input_device.as_mut() {
+      Some(device) => {
+        self.did_device_io = true;
+        device.read().map_err(|err| IntcodeError::DeviceFailed {
+          instruction_pointer,
+          message: err.to_string(),
+        })
+      },
+      None => Ok(None),
+    }
+  }
+
+  // Within both the memory limit and the memory budget
+  fn is_addressable(&self, address: usize) -> bool {
+    self.values.is_addressable(address) && self.limits.memory.is_none_or(|budget| address < budget)
+  }
+
+  // The raw value of the current instruction, for errors
+  fn opcode_value(&self) -> isize {
+    self.values.read(self.instruction_pointer).saturating_to_isize()
+  }
+
+  fn invalid_opcode(&self) -> IntcodeError {
+    IntcodeError::InvalidOpcode {
+      instruction_pointer: self.instruction_pointer,
+      opcode: self.opcode_value(),
+    }
+  }
+
+  fn overflow(&self) -> IntcodeError {
+    IntcodeError::Overflow {
+      instruction_pointer: self.instruction_pointer,
+      opcode: self.opcode_value(),
+    }
+  }
+
+  // The full value of the i-th param. `Instruction` only has room for an
+  //   isize, and decoding clamps anything bigger, so a clamped value gets
+  //   read back from memory. With isize words that's never needed, but it
+  //   doesn't hurt either.
+  fn param_word(&self, params: &[Parameter], i: usize) -> W {
+    match params[i].value {
+      isize::MIN | isize::MAX => self.values.read(self.instruction_pointer + 1 + i),
+      value => W::from_isize(value),
+    }
+  }
+
+  fn get_param_val(&self, params: &[Parameter], i: usize) -> Result<W, IntcodeError> {
+    match params[i].mode {
+      ParameterMode::Immediate => Ok(self.param_word(params, i)),
+      _ => Ok(self.values.read(self.get_index_for_param(params, i)?)),
+    }
+  }
+
+  fn get_jump_target(&self, params: &[Parameter], i: usize) -> Result<usize, IntcodeError> {
+    let target = self.get_param_val(params, i)?;
+    match target.to_isize() {
+      Some(target) if target >= 0 => Ok(target as usize),
+      _ => Err(self.bad_address(&target)),
+    }
+  }
+
+  fn write_value(&mut self, params: &[Parameter], i: usize, value: W) -> Result<(), IntcodeError> {
+    let index_to_write_to = self.get_index_for_param(params, i)?;
+    self.store(index_to_write_to, value);
+    Ok(())
+  }
+
+  fn get_index_for_param(&self, params: &[Parameter], i: usize) -> Result<usize, IntcodeError> {
+    let address = match params[i].mode {
+      ParameterMode::Position => self.param_word(params, i),
+      ParameterMode::Immediate => {
+        return Err(IntcodeError::ImmediateModeWrite {
+          instruction_pointer: self.instruction_pointer,
+          opcode: self.opcode_value(),
+        });
+      },
+      ParameterMode::Relative => W::from_isize(self.relative_base)
+        .checked_add(&self.param_word(params, i))
+        .ok_or_else(|| self.overflow())?,
+    };
+
+    match address.to_isize() {
+      Some(index) if index >= 0 && self.is_addressable(index as usize) => Ok(index as usize),
+      _ => Err(self.bad_address(&address)),
+    }
+  }
+
+  // The error for an address that's negative, or past the end of memory
+  fn bad_address(&self, address: &W) -> IntcodeError {
+    let instruction_pointer = self.instruction_pointer;
+    let opcode = self.opcode_value();
+    if *address < W::zero() {
+      IntcodeError::NegativeAddress { instruction_pointer, opcode, address: address.saturating_to_isize() }
+    } else {
+      let address = address.to_isize().map_or(usize::MAX, |address| address as usize);
+      IntcodeError::AddressOutOfRange { instruction_pointer, opcode, address }
+    }
+  }
+}
+
+// False for the yields that mean the instruction didn't actually run
+fn did_execute<W>(result: &Option<Yield<W>>) -> bool {
+  !matches!(result, Some(Yield::NeedsInput) | Some(Yield::BudgetExhausted(_)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::limits::DEFAULT_LOOP_ITERATIONS;
+
+  struct TestCase {
+    program: Vec<isize>,
+    inputs: Vec<isize>,
+    end_state: Vec<isize>,
+    output: Vec<isize>,
+  }
+
+  fn run_test_cases(cases: &[TestCase]) {
+    for case in cases {
+      let mut program = Program::new(&case.program);
+      let output = program.run(&case.inputs).unwrap();
+      assert!(!case.end_state.is_empty());
+      let end_state: Vec<isize> = (0..case.end_state.len())
+        .map(|address| program.read(address))
+        .collect();
+      assert_eq!(end_state, case.end_state);
+      assert_eq!(output, case.output);
+    }
+  }
+
+  #[test]
+  fn works_with_op_codes_1_2_99() {
+    // Example programs from Day 2
+    let cases = vec![
+      TestCase {
+        program:   vec![1,0,0,0,99],
+        end_state: vec![2,0,0,0,99],
+        inputs: vec![],
+        output: vec![],
+      },
+      TestCase {
+        program:   vec![2,3,0,3,99],
+        end_state: vec![2,3,0,6,99],
+        inputs: vec![],
+        output: vec![],
+      },
+      TestCase {
+        program:   vec![2,4,4,5,99,0],
+        end_state: vec![2,4,4,5,99,9801],
+        inputs: vec![],
+        output: vec![],
+      },
+      TestCase {
+        program:   vec![1,1,1,4,99,5,6,0,99],
+        end_state: vec![30,1,1,4,2,5,6,0,99],
+        inputs: vec![],
+        output: vec![],
+      },
+      TestCase {
+        program:   vec![1,9,10,3,2,3,11,0,99,30,40,50],
+        end_state: vec![3500,9,10,70,2,3,11,0,99,30,40,50],
+        inputs: vec![],
+        output: vec![],
+      },
+    ];
+
+    run_test_cases(&cases);
+  }
+
+  #[test]
+  fn op_codes_3_and_4() {
+    let cases = vec![
+      TestCase {
+        program: vec![3,0,4,0,99],
+        inputs: vec![1],
+        end_state: vec![1, 0, 4, 0, 99],
+        output: vec![1],
+      },
+    ];
+
+    run_test_cases(&cases);
+  }
+
+  #[test]
+  fn op_code_5() {
+    // Basic tests for jump-if-true. I wrote these.
+    let cases = vec![
+      TestCase {
+        // Address 8 is non-zero, so it does jump.
+        program: vec![1005, 8, 5, 4, 9, 4, 10, 99, 1, -1, -2],
+        inputs: vec![-1],
+        end_state: vec![1005, 8, 5, 4, 9, 4, 10, 99, 1, -1, -2],
+        output: vec![-2],
+      },
+      TestCase {
+        // Address 8 is 0, so it does NOT jump.
+        program: vec![1005, 8, 5, 4, 9, 4, 10, 99, 0, -1, -2],
+        inputs: vec![-1],
+        end_state: vec![1005, 8, 5, 4, 9, 4, 10, 99, 0, -1, -2],
+        output: vec![-1, -2],
+      },
+    ];
+
+    run_test_cases(&cases);
+  }
+
+  #[test]
+  fn op_code_6() {
+    // Basic tests for jump-if-false. I wrote these.
+    let cases = vec![
+      TestCase {
+        // Address 8 is non-zero, so it does NOT jump.
+        program: vec![1006, 8, 5, 4, 9, 4, 10, 99, 1, -1, -2],
+        inputs: vec![-1],
+        end_state: vec![1006, 8, 5, 4, 9, 4, 10, 99, 1, -1, -2],
+        output: vec![-1, -2],
+      },
+      TestCase {
+        // Address 8 is 0, so it does jump.
+        program: vec![1006, 8, 5, 4, 9, 4, 10, 99, 0, -1, -2],
+        inputs: vec![-1],
+        end_state: vec![1006, 8, 5, 4, 9, 4, 10, 99, 0, -1, -2],
+        output: vec![-2],
+      },
+    ];
+
+    run_test_cases(&cases);
+  }
+
+  #[test]
+  fn op_code_8() {
+    let program1 = vec![3,9,8,9,10,9,4,9,99,-1,8];
+    let program2 = vec![3,3,1108,-1,8,3,4,3,99];
+
+    let cases = vec![
+      // position mode (1st bulleted example in part 2)
+      TestCase {
+        program: program1.clone(),
+        inputs: vec![8], // equals 8
+        end_state: vec![3,9,8,9,10,9,4,9,99,1,8],
+        output: vec![1],
+      },
+      TestCase {
+        program: program1.clone(),
+        inputs: vec![5], // less than 8
+        end_state: vec![3,9,8,9,10,9,4,9,99,0,8],
+        output: vec![0],
+      },
+      TestCase {
+        program: program1.clone(),
+        inputs: vec![900], // greater than 8
+        end_state: vec![3,9,8,9,10,9,4,9,99,0,8],
+        output: vec![0],
+      },
+
+      // immediate mode (3rd bulleted example in part 2)
+      TestCase {
+        program: program2.clone(),
+        inputs: vec![8], // equals 8,
+        end_state: vec![3,3,1108,1,8,3,4,3,99],
+        output: vec![1],
+      },
+      TestCase {
+        program: program2.clone(),
+        inputs: vec![5], // less than 8,
+        end_state: vec![3,3,1108,0,8,3,4,3,99],
+        output: vec![0],
+      },
+      TestCase {
+        program: program2.clone(),
+        inputs: vec![900], // greater than 8,
+        end_state: vec![3,3,1108,0,8,3,4,3,99],
+        output: vec![0],
+      },
+    ];
+
+    run_test_cases(&cases);
+  }
+
+  #[test]
+  fn op_code_9() {
+    let quine_program = vec![
+      109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99,
+    ];
+
+    let cases = vec![
+      TestCase {
+        program: quine_program.clone(),
+        inputs: vec![],
+        end_state: quine_program.clone(),
+        output: quine_program.clone(),
+      },
+      TestCase {
+        // Should output a 16-digit number
+        program: vec![1102,34915192,34915192,7,4,7,99,0],
+        inputs: vec![],
+        end_state: vec![1102,34915192,34915192,7,4,7,99,1219070632396864],
+        output: vec![1219070632396864],
+      },
+      TestCase {
+        // Should output the large number in the middle
+        program: vec![104,1125899906842624,99],
+        inputs: vec![],
+        end_state: vec![104,1125899906842624,99],
+        output: vec![1125899906842624],
+      },
+    ];
+
+    run_test_cases(&cases);
+  }
+
+  #[test]
+  fn resume_yields_on_input_output_and_halt() {
+    let mut program = Program::new(&[3,0,4,0,99]);
+
+    assert_eq!(program.resume().unwrap(), Yield::NeedsInput);
+    // Still waiting, since nothing was pushed
+    assert_eq!(program.resume().unwrap(), Yield::NeedsInput);
+
+    program.push_input(42);
+    assert_eq!(program.resume().unwrap(), Yield::Output(42));
+    assert_eq!(program.resume().unwrap(), Yield::Halted);
+    assert!(program.is_halted());
+    assert_eq!(program.resume().unwrap(), Yield::Halted);
+  }
+
+  #[test]
+  fn run_keeps_unused_inputs_queued() {
+    // Reads two inputs and outputs their sum, twice over
+    let adder = vec![3,20,3,21,1,20,21,22,4,22,1105,1,0,99];
+    let mut program = Program::new(&adder);
+
+    assert_eq!(program.run(&[1, 2, 3]).unwrap(), vec![3]);
+    assert!(!program.is_halted());
+    assert_eq!(program.run(&[4]).unwrap(), vec![7]);
+  }
+
+  #[test]
+  fn errors_instead_of_panicking() {
+    fn run_error(values: &[isize]) -> IntcodeError {
+      let mut program = Program::new(values);
+      program.set_memory_limit(Some(100));
+      program.run(&[5]).unwrap_err()
+    }
+
+    assert_eq!(
+      run_error(&[1,0,0,0,42]),
+      IntcodeError::InvalidOpcode { instruction_pointer: 4, opcode: 42 },
+    );
+    assert_eq!(
+      run_error(&[-1]),
+      IntcodeError::InvalidOpcode { instruction_pointer: 0, opcode: -1 },
+    );
+    assert_eq!(
+      run_error(&[304,0,99]),
+      IntcodeError::InvalidParameterMode { instruction_pointer: 0, opcode: 304, mode: 3 },
+    );
+    assert_eq!(
+      run_error(&[11101,1,1,0,99]),
+      IntcodeError::ImmediateModeWrite { instruction_pointer: 0, opcode: 11101 },
+    );
+    assert_eq!(
+      run_error(&[4,-3,99]),
+      IntcodeError::NegativeAddress { instruction_pointer: 0, opcode: 4, address: -3 },
+    );
+    assert_eq!(
+      run_error(&[1105,1,-7]),
+      IntcodeError::NegativeAddress { instruction_pointer: 0, opcode: 1105, address: -7 },
+    );
+    assert_eq!(
+      run_error(&[3,1000,99]),
+      IntcodeError::AddressOutOfRange { instruction_pointer: 0, opcode: 3, address: 1000 },
+    );
+    assert_eq!(
+      run_error(&[1101,isize::MAX,1,0,99]),
+      IntcodeError::Overflow { instruction_pointer: 0, opcode: 1101 },
+    );
+    assert_eq!(
+      run_error(&[109,isize::MIN,209,-1,99]),
+      IntcodeError::Overflow { instruction_pointer: 2, opcode: 209 },
+    );
+    assert_eq!(
+      run_error(&[1105,1,1000]),
+      IntcodeError::InstructionPointerOutOfRange { instruction_pointer: 1000 },
+    );
+    assert_eq!(
+      run_error(&[1105,1,0]),
+      IntcodeError::LoopLimitExceeded {
+        instruction_pointer: 0,
+        iterations: DEFAULT_LOOP_ITERATIONS,
+      },
+    );
+  }
+
+  #[test]
+  fn errors_leave_the_program_recoverable() {
+    let mut program = Program::new(&[3,1000,99]);
+    program.set_memory_limit(Some(100));
+    program.push_input(7);
+    assert!(program.resume().is_err());
+
+    // The input was not consumed, and the program can be patched and resumed
+    program.write(1, 0);
+    assert_eq!(program.resume().unwrap(), Yield::Halted);
+    assert_eq!(program.read(0), 7);
+
+    assert_eq!(program.run(&[]).unwrap_err(), IntcodeError::AlreadyHalted);
+  }
+
+  #[test]
+  fn run_stops_a_program_that_outputs_forever() {
+    let mut program = Program::new(&[104,1,1105,1,0]);
+    assert_eq!(
+      program.run(&[]),
+      Err(IntcodeError::LoopLimitExceeded {
+        instruction_pointer: 0,
+        iterations: DEFAULT_LOOP_ITERATIONS,
+      }),
+    );
+    // Each `resume` still gets a fresh count, since it hands every output back
+    assert_eq!(program.resume(), Ok(Yield::Output(1)));
+  }
+
+  #[test]
+  fn memory_grows_on_demand() {
+    // Writes far past the end of the program, then reads it back
+    let far_address = 1_000_000_000;
+    let mut program = Program::new(&[1101,5,6,far_address,4,far_address,99]);
+    assert_eq!(program.run(&[]).unwrap(), vec![11]);
+    assert_eq!(program.read(far_address as usize), 11);
+    assert_eq!(program.read(far_address as usize + 1), 0);
+  }
+}
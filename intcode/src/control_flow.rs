@@ -0,0 +1,453 @@
+// Static control flow analysis. Starting at address 0, follows every path
+//   the program could take and splits the code it finds into basic blocks,
+//   which can then be rendered as a Graphviz graph:
+//
+//   cargo run -p intcode --bin cfg -- day-09/puzzle-input.txt | dot -Tsvg > boost.svg
+//
+// Jumps with an immediate mode target are resolved. Anything else (like the
+//   `jf #0, rb+0` that returns from a function) jumps somewhere that depends
+//   on runtime state, so it's flagged as an indirect jump and its targets are
+//   unknown. Code that's only reached through indirect jumps will show up as
+//   unreachable.
+//
+// It also flags:
+//   - writes that land on reachable code (self-modifying code), as far as
+//     they can be known statically (position mode writes)
+//   - ranges that no path reaches, split into likely data (read or written
+//     by the code, or not valid instructions) and unreachable code
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::instruction::{Instruction, Opcode, ParameterMode};
+
+#[derive(Debug, PartialEq)]
+pub struct BasicBlock {
+  pub start: usize,
+  // One past the last address of the block
+  pub end: usize,
+  pub instructions: Vec<(usize, Instruction)>,
+  // Start addresses of the blocks that can run next
+  pub successors: Vec<usize>,
+  // Ends in a jump whose target isn't known statically
+  pub indirect_jump: bool,
+  // Runs into something that isn't a valid instruction
+  pub invalid_end: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegionKind {
+  UnreachableCode,
+  Data,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Region {
+  pub start: usize,
+  pub end: usize,
+  pub kind: RegionKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SelfModifyingWrite {
+  // Address of the instruction doing the write
+  pub instruction: usize,
+  pub target: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ControlFlowGraph {
+  pub blocks: Vec<BasicBlock>,
+  pub regions: Vec<Region>,
+  pub self_modifying_writes: Vec<SelfModifyingWrite>,
+}
+
+// What can run after an instruction
+pub(crate) struct Flow {
+  pub(crate) targets: Vec<usize>,
+  pub(crate) falls_through: bool,
+  pub(crate) indirect: bool,
+}
+
+impl ControlFlowGraph {
+  pub fn build(values: &[isize]) -> ControlFlowGraph {
+    let read = |address: usize| values.get(address).copied().unwrap_or(0);
+    let decode = |address: usize| {
+      if address >= values.len() {
+        return None;
+      }
+      Instruction::decode(read(address), |i| read(address + 1 + i)).ok()
+    };
+
+    // Find every reachable instruction, and where blocks have to start
+    let mut instructions: BTreeMap<usize, Instruction> = BTreeMap::new();
+    let mut leaders: BTreeSet<usize> = BTreeSet::new();
+    // Reachable addresses that don't hold a valid instruction
+    let mut invalid: BTreeSet<usize> = BTreeSet::new();
+    let mut pending = vec![0];
+    leaders.insert(0);
+
+    while let Some(address) = pending.pop() {
+      if instructions.contains_key(&address) {
+        continue;
+      }
+      let instruction = match decode(address) {
+        Some(instruction) => instruction,
+        None => {
+          invalid.insert(address);
+          continue;
+        },
+      };
+      instructions.insert(address, instruction);
+
+      let flow = flow(&instruction);
+      let next = address + instruction.size();
+      if flow.falls_through {
+        pending.push(next);
+      }
+      if is_jump(instruction.opcode) {
+        leaders.insert(next);
+      }
+      for &target in flow.targets.iter() {
+        leaders.insert(target);
+        pending.push(target);
+      }
+    }
+
+    let blocks = build_blocks(&instructions, &leaders, &invalid);
+
+    // Which addresses hold reachable code, and which are read or written as
+    //   data by it
+    let mut code = vec![false; values.len()];
+    for (&address, instruction) in instructions.iter() {
+      let end = (address + instruction.size()).min(values.len());
+      code[address..end].iter_mut().for_each(|covered| *covered = true);
+    }
+    let mut data_references: BTreeSet<usize> = BTreeSet::new();
+    let mut self_modifying_writes = Vec::new();
+    for (&address, instruction) in instructions.iter() {
+      for (i, param) in instruction.params().iter().enumerate() {
+        if param.mode != ParameterMode::Position || param.value < 0 {
+          continue;
+        }
+        let target = param.value as usize;
+        data_references.insert(target);
+        let is_write = instruction.opcode.write_param() == Some(i);
+        if is_write && code.get(target).copied().unwrap_or(false) {
+          self_modifying_writes.push(SelfModifyingWrite { instruction: address, target });
+        }
+      }
+    }
+
+    let regions = find_regions(values, &code, &data_references);
+
+    ControlFlowGraph { blocks, regions, self_modifying_writes }
+  }
+
+  pub fn block_at(&self, address: usize) -> Option<&BasicBlock> {
+    self.blocks.iter().find(|block| block.start <= address && address < block.end)
+  }
+
+  pub fn to_dot(&self) -> String {
+    let mut dot = String::new();
+    dot.push_str("digraph intcode {\n");
+    dot.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+
+    for block in self.blocks.iter() {
+      let mut label = String::new();
+      for (address, instruction) in block.instructions.iter() {
+        write!(label, "{}: {}\\l", address, instruction).unwrap();
+      }
+      if block.invalid_end {
+        write!(label, "{}: (invalid)\\l", block.end).unwrap();
+      }
+      let style = if block.invalid_end { ", color=red" } else { "" };
+      writeln!(dot, "  b{} [label=\"{}\"{}];", block.start, label, style).unwrap();
+
+      for successor in block.successors.iter() {
+        writeln!(dot, "  b{} -> b{};", block.start, successor).unwrap();
+      }
+      if block.indirect_jump {
+        writeln!(dot, "  indirect{} [label=\"?\", shape=circle, color=orange];", block.start).unwrap();
+        writeln!(dot, "  b{} -> indirect{} [style=dashed, color=orange];", block.start, block.start).unwrap();
+      }
+    }
+
+    for region in self.regions.iter() {
+      let (description, shape) = match region.kind {
+        RegionKind::UnreachableCode => ("unreachable code", "box, style=dashed"),
+        RegionKind::Data => ("data", "note"),
+      };
+      writeln!(
+        dot,
+        "  r{} [label=\"{}-{}: {}\", shape={}, color=gray];",
+        region.start, region.start, region.end - 1, description, shape,
+      ).unwrap();
+    }
+
+    for write in self.self_modifying_writes.iter() {
+      if let (Some(from), Some(to)) = (self.block_at(write.instruction), self.block_at(write.target)) {
+        writeln!(
+          dot,
+          "  b{} -> b{} [style=dotted, color=red, label=\"writes {}\"];",
+          from.start, to.start, write.target,
+        ).unwrap();
+      }
+    }
+
+    dot.push_str("}\n");
+    dot
+  }
+}
+
+fn is_jump(opcode: Opcode) -> bool {
+  opcode == Opcode::JumpIfTrue || opcode == Opcode::JumpIfFalse
+}
+
+pub(crate) fn flow(instruction: &Instruction) -> Flow {
+  let params = instruction.params();
+  match instruction.opcode {
+    Opcode::Halt => Flow { targets: vec![], falls_through: false, indirect: false },
+    Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+      let jump_if = instruction.opcode == Opcode::JumpIfTrue;
+      // An immediate condition means the jump is always or never taken
+      let (can_jump, can_fall_through) = match params[0].mode {
+        ParameterMode::Immediate => {
+          let jumps = (params[0].value != 0) == jump_if;
+          (jumps, !jumps)
+        },
+        _ => (true, true),
+      };
+      let target = match params[1].mode {
+        ParameterMode::Immediate if params[1].value >= 0 => Some(params[1].value as usize),
+        _ => None,
+      };
+      Flow {
+        targets: if can_jump { target.into_iter().collect() } else { vec![] },
+        falls_through: can_fall_through,
+        indirect: can_jump && target.is_none(),
+      }
+    },
+    _ => Flow { targets: vec![], falls_through: true, indirect: false },
+  }
+}
+
+fn build_blocks(
+  instructions: &BTreeMap<usize, Instruction>,
+  leaders: &BTreeSet<usize>,
+  invalid: &BTreeSet<usize>,
+) -> Vec<BasicBlock> {
+  let mut blocks = Vec::new();
+
+  for &start in leaders.iter() {
+    if !instructions.contains_key(&start) {
+      // Either a jump target that isn't a valid instruction, or the address
+      //   after an unconditional jump, which nothing may reach
+      if invalid.contains(&start) {
+        blocks.push(BasicBlock {
+          start,
+          end: start,
+          instructions: vec![],
+          successors: vec![],
+          indirect_jump: false,
+          invalid_end: true,
+        });
+      }
+      continue;
+    }
+
+    let mut block = BasicBlock {
+      start,
+      end: start,
+      instructions: vec![],
+      successors: vec![],
+      indirect_jump: false,
+      invalid_end: false,
+    };
+    let mut address = start;
+    loop {
+      let instruction = match instructions.get(&address) {
+        Some(instruction) => *instruction,
+        None => {
+          block.invalid_end = true;
+          break;
+        },
+      };
+      block.instructions.push((address, instruction));
+      address += instruction.size();
+      block.end = address;
+
+      let flow = flow(&instruction);
+      if flow.targets.is_empty() && flow.falls_through && !is_jump(instruction.opcode) {
+        if leaders.contains(&address) {
+          block.successors.push(address);
+          break;
+        }
+        continue;
+      }
+
+      block.successors.extend(flow.targets.iter().copied());
+      if flow.falls_through {
+        block.successors.push(address);
+      }
+      block.indirect_jump = flow.indirect;
+      break;
+    }
+
+    block.successors.dedup();
+    blocks.push(block);
+  }
+
+  blocks
+}
+
+// Splits everything that isn't reachable code into ranges
+fn find_regions(values: &[isize], code: &[bool], data_references: &BTreeSet<usize>) -> Vec<Region> {
+  let mut regions = Vec::new();
+  let mut address = 0;
+
+  while address < values.len() {
+    if code[address] {
+      address += 1;
+      continue;
+    }
+    let start = address;
+    while address < values.len() && !code[address] {
+      address += 1;
+    }
+
+    let referenced = data_references.range(start..address).next().is_some();
+    let kind = if !referenced && decodes_cleanly(&values[start..address]) {
+      RegionKind::UnreachableCode
+    } else {
+      RegionKind::Data
+    };
+    regions.push(Region { start, end: address, kind });
+  }
+
+  regions
+}
+
+// True if the values are a sequence of complete instructions, with nothing
+//   odd in their unused mode digits
+fn decodes_cleanly(values: &[isize]) -> bool {
+  let mut address = 0;
+  while address < values.len() {
+    let read = |i: usize| values.get(address + 1 + i).copied().unwrap_or(0);
+    let instruction = match Instruction::decode(values[address], read) {
+      Ok(instruction) => instruction,
+      Err(_) => return false,
+    };
+    let end = address + instruction.size();
+    if end > values.len() || instruction.encode()[..] != values[address..end] {
+      return false;
+    }
+    address = end;
+  }
+  true
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{assemble, parse_program_from_file};
+
+  fn starts(graph: &ControlFlowGraph) -> Vec<usize> {
+    graph.blocks.iter().map(|block| block.start).collect()
+  }
+
+  #[test]
+  fn splits_blocks_at_jumps() {
+    let program = assemble("
+      in [counter]
+      loop: out [counter]
+      add [counter], #-1, [counter]
+      jt [counter], #loop
+      hlt
+      counter: .data 0
+    ").unwrap();
+    let graph = ControlFlowGraph::build(&program);
+
+    assert_eq!(starts(&graph), vec![0, 2, 11]);
+    assert_eq!(graph.blocks[0].successors, vec![2]);
+    assert_eq!(graph.blocks[1].successors, vec![2, 11]);
+    assert_eq!(graph.blocks[2].successors, vec![]);
+    assert_eq!(graph.regions, vec![Region { start: 12, end: 13, kind: RegionKind::Data }]);
+    assert!(graph.self_modifying_writes.is_empty());
+  }
+
+  #[test]
+  fn finds_indirect_jumps_and_unreachable_code() {
+    let program = assemble("
+      jt #1, #skip
+      ; Never runs, but looks like code
+      out #1
+      hlt
+      skip: arb #10
+      jf #0, rb+0
+    ").unwrap();
+    let graph = ControlFlowGraph::build(&program);
+
+    assert_eq!(starts(&graph), vec![0, 6]);
+    assert_eq!(graph.blocks[0].successors, vec![6]);
+    assert!(graph.blocks[1].indirect_jump);
+    assert_eq!(graph.blocks[1].successors, vec![]);
+    assert_eq!(graph.regions, vec![
+      Region { start: 3, end: 6, kind: RegionKind::UnreachableCode },
+    ]);
+  }
+
+  #[test]
+  fn finds_self_modifying_writes() {
+    // Patches the output's operand, like the compiler.rs test
+    let program = vec![104,3, 1001,1,-1,1, 1005,1,0, 4,1,99];
+    let graph = ControlFlowGraph::build(&program);
+
+    assert_eq!(graph.self_modifying_writes, vec![
+      SelfModifyingWrite { instruction: 2, target: 1 },
+    ]);
+    assert!(graph.regions.is_empty());
+    assert!(graph.to_dot().contains("b0 -> b0 [style=dotted, color=red, label=\"writes 1\"];"));
+  }
+
+  #[test]
+  fn jumping_into_garbage_is_flagged() {
+    let graph = ControlFlowGraph::build(&[1105,1,4,99,42]);
+    assert_eq!(starts(&graph), vec![0, 4]);
+    assert!(graph.blocks[1].invalid_end);
+    assert_eq!(graph.regions, vec![Region { start: 3, end: 5, kind: RegionKind::Data }]);
+  }
+
+  #[test]
+  fn renders_dot() {
+    let graph = ControlFlowGraph::build(&[1105,1,4,99,3,10,1005,10,4,99,0]);
+    let expected = [
+      "digraph intcode {",
+      "  node [shape=box, fontname=\"monospace\"];",
+      "  b0 [label=\"0: jt #1, #4\\l\"];",
+      "  b0 -> b4;",
+      "  b4 [label=\"4: in [10]\\l6: jt [10], #4\\l\"];",
+      "  b4 -> b4;",
+      "  b4 -> b9;",
+      "  b9 [label=\"9: hlt\\l\"];",
+      "  r3 [label=\"3-3: unreachable code\", shape=box, style=dashed, color=gray];",
+      "  r10 [label=\"10-10: data\", shape=note, color=gray];",
+      "}",
+    ];
+    assert_eq!(graph.to_dot(), expected.join("\n") + "\n");
+  }
+
+  #[test]
+  fn analyzes_boost() {
+    let boost = parse_program_from_file(
+      concat!(env!("CARGO_MANIFEST_DIR"), "/../day-09/puzzle-input.txt"),
+    );
+    let graph = ControlFlowGraph::build(&boost);
+
+    assert_eq!(graph.blocks[0].start, 0);
+    assert!(graph.blocks.iter().any(|block| block.indirect_jump));
+    assert!(graph.blocks.iter().all(|block| !block.invalid_end));
+    assert!(graph.to_dot().starts_with("digraph intcode {\n"));
+  }
+}
@@ -0,0 +1,318 @@
+// A small gdb-style REPL for poking at a running Intcode program. Supports
+//   breakpoints on addresses, watchpoints on memory cells, single stepping,
+//   editing memory and feeding inputs. Type `help` at the prompt for the list
+//   of commands.
+//
+// This replaced flipping the `DEBUG` flag in computer.rs, which dumps the
+//   entire memory on every instruction.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+
+use crate::computer::{Program, Yield};
+use crate::disassembler::{self, ListingLine};
+use crate::instruction::MAX_PARAMS;
+
+// So that `continue` on a program stuck in a loop eventually gives control back
+const MAX_CONTINUE_STEPS: usize = 10_000_000;
+
+// Most lines `mem` and `disas` will show at once
+const MAX_LISTING_LINES: usize = 1000;
+
+const HELP: &str = "\
+Commands:
+  step [n]            (s)  execute n instructions (default 1)
+  continue            (c)  run until a breakpoint, watchpoint, input wait or halt
+  break [addr]        (b)  set a breakpoint, or list them
+  delete <addr>       (d)  remove a breakpoint
+  watch [addr]        (w)  stop when the value at addr changes, or list watchpoints
+  unwatch <addr>           remove a watchpoint
+  regs                (r)  show instruction pointer, relative base, etc
+  mem <addr> [count]  (x)  show memory
+  set <addr> <value>       change memory
+  input <values...>   (in) queue up inputs for the program
+  disas [addr] [count] (l) disassemble, starting at the instruction pointer by default
+  help                (h)  show this message
+  quit                (q)  exit the debugger";
+
+pub struct Debugger {
+  program: Program,
+  breakpoints: BTreeSet<usize>,
+  // Watched address -> the value it had the last time we looked
+  watchpoints: BTreeMap<usize, isize>,
+}
+
+impl Debugger {
+  pub fn new(program: Program) -> Self {
+    Debugger {
+      program,
+      breakpoints: BTreeSet::new(),
+      watchpoints: BTreeMap::new(),
+    }
+  }
+
+  pub fn program(&self) -> &Program {
+    &self.program
+  }
+
+  pub fn run_repl(&mut self, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+    self.show_location(&mut out)?;
+    let mut lines = input.lines();
+    loop {
+      write!(out, "(intcode) ")?;
+      out.flush()?;
+      let line = match lines.next() {
+        Some(line) => line?,
+        None => break,
+      };
+      if !self.execute(&line, &mut out)? {
+        break;
+      }
+    }
+    Ok(())
+  }
+
+  // Runs a single debugger command. Returns false once the user asks to quit.
+  pub fn execute(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (command, args) = match words.split_first() {
+      Some((command, args)) => (*command, args),
+      None => return Ok(true),
+    };
+
+    let numbers: Result<Vec<isize>, _> = args.iter().map(|arg| arg.parse::<isize>()).collect();
+    let numbers = match numbers {
+      Ok(numbers) => numbers,
+      Err(_) => {
+        writeln!(out, "expected numbers, got {:?}", args.join(" "))?;
+        return Ok(true);
+      },
+    };
+    // Everything but inputs and the value for `set` is an address or a count
+    let is_value = |index: usize| matches!(command, "input" | "in") || (command == "set" && index == 1);
+    if let Some((_, n)) = numbers.iter().enumerate().find(|&(index, &n)| n < 0 && !is_value(index)) {
+      writeln!(out, "expected an address or count, got {}", n)?;
+      return Ok(true);
+    }
+    let address_arg = |index: usize| numbers.get(index).map(|&n| n as usize);
+
+    match (command, address_arg(0)) {
+      ("step", count) | ("s", count) => {
+        for _ in 0..count.unwrap_or(1) {
+          if self.step_and_report(out)? {
+            break;
+          }
+        }
+        self.show_location(out)?;
+      },
+      ("continue", _) | ("c", _) => {
+        self.continue_execution(out)?;
+        self.show_location(out)?;
+      },
+      ("break", Some(address)) | ("b", Some(address)) => {
+        self.breakpoints.insert(address);
+        writeln!(out, "breakpoint set at {}", address)?;
+      },
+      ("break", None) | ("b", None) => {
+        writeln!(out, "breakpoints: {:?}", self.breakpoints)?;
+      },
+      ("delete", Some(address)) | ("d", Some(address)) => {
+        if !self.breakpoints.remove(&address) {
+          writeln!(out, "no breakpoint at {}", address)?;
+        }
+      },
+      ("watch", Some(address)) | ("w", Some(address)) => {
+        self.watchpoints.insert(address, self.program.read(address));
+        writeln!(out, "watching [{}] (currently {})", address, self.program.read(address))?;
+      },
+      ("watch", None) | ("w", None) => {
+        let watched: Vec<&usize> = self.watchpoints.keys().collect();
+        writeln!(out, "watchpoints: {:?}", watched)?;
+      },
+      ("unwatch", Some(address)) => {
+        if self.watchpoints.remove(&address).is_none() {
+          writeln!(out, "no watchpoint at {}", address)?;
+        }
+      },
+      ("regs", _) | ("r", _) => self.show_registers(out)?,
+      ("mem", Some(address)) | ("x", Some(address)) => {
+        let count = address_arg(1).unwrap_or(1);
+        if count > MAX_LISTING_LINES {
+          writeln!(out, "can't show more than {} cells at once", MAX_LISTING_LINES)?;
+        } else if address.checked_add(count).is_none() {
+          writeln!(out, "addresses past {} don't exist", usize::MAX)?;
+        } else {
+          for address in address..address + count {
+            writeln!(out, "[{}] = {}", address, self.program.read(address))?;
+          }
+        }
+      },
+      ("set", Some(address)) if numbers.len() == 2 => {
+        self.program.write(address, numbers[1]);
+        if let Some(last_value) = self.watchpoints.get_mut(&address) {
+          *last_value = numbers[1];
+        }
+      },
+      ("input", _) | ("in", _) => {
+        for &value in numbers.iter() {
+          self.program.push_input(value);
+        }
+        writeln!(out, "pending inputs: {:?}", self.program.pending_inputs())?;
+      },
+      ("disas", address) | ("l", address) => {
+        let address = address.unwrap_or_else(|| self.program.instruction_pointer());
+        let count = address_arg(1).unwrap_or(10);
+        if count > MAX_LISTING_LINES {
+          writeln!(out, "can't show more than {} instructions at once", MAX_LISTING_LINES)?;
+        } else {
+          self.disassemble(address, count, out)?;
+        }
+      },
+      ("help", _) | ("h", _) => writeln!(out, "{}", HELP)?,
+      ("quit", _) | ("q", _) => return Ok(false),
+      _ => writeln!(out, "invalid command {:?} (try `help`)", line.trim())?,
+    }
+
+    Ok(true)
+  }
+
+  // Executes one instruction and reports anything interesting that happened.
+  //   Returns true if execution should stop here.
+  fn step_and_report(&mut self, out: &mut impl Write) -> io::Result<bool> {
+    match self.program.step() {
+      Ok(None) => {},
+      Ok(Some(Yield::Output(value))) => writeln!(out, "output: {}", value)?,
+      Ok(Some(Yield::NeedsInput)) => {
+        writeln!(out, "waiting for input (use `input <values>`)")?;
+        return Ok(true);
+      },
+      Ok(Some(Yield::Halted)) => {
+        writeln!(out, "program halted")?;
+        return Ok(true);
+      },
+      Ok(Some(Yield::BudgetExhausted(limit))) => {
+        writeln!(out, "ran out of {}", limit)?;
+        return Ok(true);
+      },
+      Err(err) => {
+        writeln!(out, "error: {}", err)?;
+        return Ok(true);
+      },
+    }
+
+    let mut watchpoint_hit = false;
+    for (&address, last_value) in self.watchpoints.iter_mut() {
+      let value = self.program.read(address);
+      if value != *last_value {
+        writeln!(out, "watchpoint: [{}] changed from {} to {}", address, last_value, value)?;
+        *last_value = value;
+        watchpoint_hit = true;
+      }
+    }
+    Ok(watchpoint_hit)
+  }
+
+  fn continue_execution(&mut self, out: &mut impl Write) -> io::Result<()> {
+    // Always take the first step, so we can continue from a breakpoint
+    for steps in 0..MAX_CONTINUE_STEPS {
+      let instruction_pointer = self.program.instruction_pointer();
+      if steps > 0 && self.breakpoints.contains(&instruction_pointer) {
+        writeln!(out, "breakpoint at {}", instruction_pointer)?;
+        return Ok(());
+      }
+      if self.step_and_report(out)? {
+        return Ok(());
+      }
+    }
+    writeln!(out, "still running after {} instructions, stopping here", MAX_CONTINUE_STEPS)
+  }
+
+  fn show_registers(&self, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "instruction pointer: {}", self.program.instruction_pointer())?;
+    writeln!(out, "relative base: {}", self.program.relative_base())?;
+    writeln!(out, "instructions executed: {}", self.program.instruction_count())?;
+    writeln!(out, "pending inputs: {:?}", self.program.pending_inputs())?;
+    writeln!(out, "halted: {}", self.program.is_halted())
+  }
+
+  fn show_location(&self, out: &mut impl Write) -> io::Result<()> {
+    self.disassemble(self.program.instruction_pointer(), 1, out)
+  }
+
+  fn disassemble(&self, start: usize, count: usize, out: &mut impl Write) -> io::Result<()> {
+    // Enough memory for `count` lines, even if every one of them is an
+    //   instruction with the most params
+    let end = start.saturating_add(count.saturating_mul(MAX_PARAMS + 1));
+    let window: Vec<isize> = (start..end).map(|address| self.program.read(address)).collect();
+    for line in disassembler::disassemble(&window).into_iter().take(count) {
+      let line = ListingLine { address: start + line.address, ..line };
+      let marker = if line.address == self.program.instruction_pointer() { "=>" } else { "  " };
+      writeln!(out, "{} {}", marker, line)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run_script(program: &[isize], script: &str) -> (Debugger, String) {
+    let mut debugger = Debugger::new(Program::new(program));
+    let mut out = Vec::new();
+    debugger.run_repl(script.as_bytes(), &mut out).unwrap();
+    (debugger, String::from_utf8(out).unwrap())
+  }
+
+  #[test]
+  fn breakpoints_and_stepping() {
+    // Day 9 quine
+    let quine = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+    let (debugger, out) = run_script(&quine, "break 4\ncontinue\nstep\nregs\nc\nc\n");
+
+    assert!(out.contains("output: 109\nbreakpoint at 4\n=> 4: add [100], #1, [100]"));
+    assert!(out.contains("=> 8: eq [100], #16, [101]"));
+    assert!(out.contains("relative base: 1"));
+    assert!(out.contains("output: 1\nbreakpoint at 4"));
+    assert_eq!(debugger.program().read(100), 2);
+  }
+
+  #[test]
+  fn watchpoints_input_and_memory_edits() {
+    let program = vec![3,0,4,0,99];
+    let script = "watch 0\nc\ninput 7\nc\nset 3 9\nx 3\nc\nc\n";
+    let (debugger, out) = run_script(&program, script);
+
+    assert!(out.contains("waiting for input"));
+    assert!(out.contains("pending inputs: [7]"));
+    assert!(out.contains("watchpoint: [0] changed from 3 to 7"));
+    assert!(out.contains("[3] = 9"));
+    // Output now reads from address 9, which was never written
+    assert!(out.contains("output: 0"));
+    assert!(out.contains("program halted"));
+    assert!(debugger.program().is_halted());
+  }
+
+  #[test]
+  fn bad_commands_dont_end_the_session() {
+    let (debugger, out) = run_script(&[99], "frobnicate\nbreak nope\nbreak -5\nwatch -1\nb\nset 1 -2\nx 1\nquit\nstep\n");
+    assert!(out.contains("invalid command \"frobnicate\""));
+    assert!(out.contains("expected numbers"));
+    assert!(out.contains("expected an address or count, got -5"));
+    assert!(out.contains("expected an address or count, got -1"));
+    assert!(out.contains("breakpoints: {}"));
+    // Values can still be negative
+    assert!(out.contains("[1] = -2"));
+    assert!(debugger.watchpoints.is_empty());
+    assert!(!out.contains("program halted"));
+  }
+
+  #[test]
+  fn listings_are_capped() {
+    let script = "l 0 100000000000000\nx 0 9223372036854775807\nx 9223372036854775807 2\n";
+    let (_, out) = run_script(&[99], script);
+    assert!(out.contains("can't show more than 1000 instructions at once"));
+    assert!(out.contains("can't show more than 1000 cells at once"));
+    assert!(out.contains("[9223372036854775807] = 0\n[9223372036854775808] = 0\n"));
+  }
+}
@@ -0,0 +1,915 @@
+// Turns an Intcode image into structured pseudocode, with functions, loops
+//   and if/else instead of jumps. For example, this is the recursive function
+//   at the end of the Day 9 BOOST program:
+//
+//   fn f922() {
+//     mem[63] = v1 < 3
+//     if (v1 >= 3) {
+//       out1 = v1 - 1
+//       call f922
+//       v2 = out1
+//       out1 = v1 - 3
+//       call f922
+//       v1 = out1 + v2
+//     }
+//     return
+//   }
+//
+// It knows the idioms the puzzle programs are built from:
+//   - calls store the return address relative to rb, then jump to the
+//     function (`mul #1, #915, rb+0` / `jf #0, #922`)
+//   - functions start with `arb #N` to get a frame, and end with `arb #-N`
+//     and a jump to the return address (`jf #0, rb+0`). Slots in the frame
+//     are named v1 to v(N-1) (arguments first, then locals), and the slots
+//     past the end of it are out0, out1, ..., the next call's return address
+//     and arguments.
+//   - comparisons (`lt`, `eq`) that feed straight into a jump become the
+//     jump's condition. The comparison's result is still assigned unless it
+//     lands in a memory cell that's provably overwritten (or the program
+//     halts) before anything reads it, which assumes relative mode accesses
+//     never alias the cell.
+//   - jumps back to an earlier address become loops, and jumps forward over
+//     a stretch of code become ifs. Anything that doesn't fit falls back to
+//     a `goto`.
+//
+// Functions are only found through calls, so code that's only reached some
+//   other way (like the indirect jump at the end of BOOST's self-test) is
+//   left out.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::control_flow::flow;
+use crate::instruction::{Instruction, Opcode, Parameter, ParameterMode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+  // A single line: an assignment, a call, `break`, `return`, etc.
+  Simple(String),
+  Label(usize),
+  Goto(usize),
+  If { condition: String, then_body: Vec<Statement>, else_body: Vec<Statement> },
+  While { condition: String, body: Vec<Statement> },
+  DoWhile { body: Vec<Statement>, condition: String },
+  Loop(Vec<Statement>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+  pub entry: usize,
+  // Set when the function has a recognized prologue and epilogue
+  pub frame_size: Option<usize>,
+  pub body: Vec<Statement>,
+}
+
+impl Function {
+  pub fn name(&self) -> String {
+    function_name(self.entry)
+  }
+}
+
+impl fmt::Display for Function {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "fn {}() {{", self.name())?;
+    write_statements(f, &self.body, 1)?;
+    write!(f, "}}")
+  }
+}
+
+fn write_statements(f: &mut fmt::Formatter, statements: &[Statement], depth: usize) -> fmt::Result {
+  let indent = "  ".repeat(depth);
+  for statement in statements {
+    match statement {
+      Statement::Simple(text) => writeln!(f, "{}{}", indent, text)?,
+      // Labels stick out to the left, like in C
+      Statement::Label(address) => writeln!(f, "{}L{}:", "  ".repeat(depth - 1), address)?,
+      Statement::Goto(address) => writeln!(f, "{}goto L{}", indent, address)?,
+      Statement::If { condition, then_body, else_body } => {
+        writeln!(f, "{}if ({}) {{", indent, condition)?;
+        write_statements(f, then_body, depth + 1)?;
+        if !else_body.is_empty() {
+          writeln!(f, "{}}} else {{", indent)?;
+          write_statements(f, else_body, depth + 1)?;
+        }
+        writeln!(f, "{}}}", indent)?;
+      },
+      Statement::While { condition, body } => {
+        writeln!(f, "{}while ({}) {{", indent, condition)?;
+        write_statements(f, body, depth + 1)?;
+        writeln!(f, "{}}}", indent)?;
+      },
+      Statement::DoWhile { body, condition } => {
+        writeln!(f, "{}do {{", indent)?;
+        write_statements(f, body, depth + 1)?;
+        writeln!(f, "{}}} while ({})", indent, condition)?;
+      },
+      Statement::Loop(body) => {
+        writeln!(f, "{}loop {{", indent)?;
+        write_statements(f, body, depth + 1)?;
+        writeln!(f, "{}}}", indent)?;
+      },
+    }
+  }
+  Ok(())
+}
+
+pub fn decompile(values: &[isize]) -> Vec<Function> {
+  let mut entries: BTreeSet<usize> = BTreeSet::new();
+  let mut pending = vec![0];
+  let mut functions = Vec::new();
+  entries.insert(0);
+
+  while let Some(entry) = pending.pop() {
+    let code = FunctionCode::find(values, entry);
+    for &target in code.calls.values() {
+      if entries.insert(target) {
+        pending.push(target);
+      }
+    }
+    functions.push(code.decompile(values));
+  }
+
+  functions.sort_by_key(|function| function.entry);
+  functions
+}
+
+pub fn format_decompiled(functions: &[Function]) -> String {
+  let functions: Vec<String> = functions.iter().map(|function| format!("{}\n", function)).collect();
+  functions.join("\n")
+}
+
+fn function_name(entry: usize) -> String {
+  if entry == 0 { "main".to_string() } else { format!("f{}", entry) }
+}
+
+// When a jump is taken: `left op right`
+#[derive(Debug, Clone)]
+struct Condition {
+  left: String,
+  op: &'static str,
+  right: String,
+}
+
+impl Condition {
+  fn negate(&self) -> Condition {
+    let op = match self.op {
+      "<" => ">=",
+      ">=" => "<",
+      "==" => "!=",
+      _ => "==",
+    };
+    Condition { left: self.left.clone(), op, right: self.right.clone() }
+  }
+}
+
+impl fmt::Display for Condition {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} {} {}", self.left, self.op, self.right)
+  }
+}
+
+// What's left of an instruction once the idioms have been recognized
+#[derive(Debug)]
+enum Item {
+  // Produces no code, like a prologue or a comparison folded into a jump
+  Nop,
+  Simple(String),
+  Branch { condition: Condition, target: usize },
+  Goto(usize),
+}
+
+#[derive(Debug)]
+struct Entry {
+  address: usize,
+  next: usize,
+  item: Item,
+}
+
+struct FunctionCode {
+  entry: usize,
+  // Everything reachable from the entry without following calls, or None
+  //   where that isn't a valid instruction
+  instructions: BTreeMap<usize, Option<Instruction>>,
+  // Jumps that make calls, and the functions they call
+  calls: BTreeMap<usize, usize>,
+}
+
+impl FunctionCode {
+  fn find(values: &[isize], entry: usize) -> FunctionCode {
+    let mut code = FunctionCode { entry, instructions: BTreeMap::new(), calls: BTreeMap::new() };
+    let mut pending = vec![entry];
+
+    while let Some(address) = pending.pop() {
+      if code.instructions.contains_key(&address) {
+        continue;
+      }
+      let instruction = decode_at(values, address);
+      code.instructions.insert(address, instruction);
+      let instruction = match instruction {
+        Some(instruction) => instruction,
+        None => continue,
+      };
+      let next = address + instruction.size();
+
+      if let Some(target) = call_target(values, address) {
+        // Carry on after the call, rather than following it into the function
+        let jump = decode_at(values, next).unwrap();
+        code.instructions.insert(next, Some(jump));
+        code.calls.insert(next, target);
+        pending.push(next + jump.size());
+        continue;
+      }
+
+      let flow = flow(&instruction);
+      if flow.falls_through {
+        pending.push(next);
+      }
+      pending.extend(flow.targets);
+    }
+
+    code
+  }
+
+  fn instruction(&self, address: usize) -> Option<Instruction> {
+    self.instructions.get(&address).copied().flatten()
+  }
+
+  // The instruction that ends right where `address` starts
+  fn previous(&self, address: usize) -> Option<Instruction> {
+    let (&previous_address, instruction) = self.instructions.range(..address).next_back()?;
+    let instruction = (*instruction)?;
+    if previous_address + instruction.size() == address { Some(instruction) } else { None }
+  }
+
+  // `arb #N` at the entry, and every other `arb` an `arb #-N` right before
+  //   a return
+  fn frame_size(&self) -> Option<usize> {
+    let prologue = self.instruction(self.entry)?;
+    let size = match prologue.params() {
+      [Parameter { value, mode: ParameterMode::Immediate }]
+        if prologue.opcode == Opcode::RelativeBaseOffset && *value > 0 => *value as usize,
+      _ => return None,
+    };
+
+    for (&address, instruction) in self.instructions.iter() {
+      let instruction = match instruction {
+        Some(instruction) if instruction.opcode == Opcode::RelativeBaseOffset => instruction,
+        _ => continue,
+      };
+      let is_epilogue = instruction.params()[0] == immediate(-(size as isize))
+        && self.instruction(address + 2).is_some_and(|jump| is_return_jump(&jump));
+      if address != self.entry && !is_epilogue {
+        return None;
+      }
+    }
+    Some(size)
+  }
+
+  fn decompile(&self, values: &[isize]) -> Function {
+    let translator = Translator {
+      values,
+      code: self,
+      frame_size: self.frame_size(),
+      jump_targets: self.instructions
+        .values()
+        .flatten()
+        .flat_map(|instruction| flow(instruction).targets)
+        .collect(),
+    };
+
+    let entries: Vec<Entry> = self.instructions
+      .iter()
+      .map(|(&address, instruction)| match instruction {
+        Some(instruction) => Entry {
+          address,
+          next: address + instruction.size(),
+          item: translator.translate(address, instruction),
+        },
+        None => Entry {
+          address,
+          next: address + 1,
+          item: Item::Simple(format!("invalid {}", values.get(address).copied().unwrap_or(0))),
+        },
+      })
+      .collect();
+
+    // The entry isn't always the lowest address
+    let mut gotos = BTreeSet::new();
+    if entries[0].address != self.entry {
+      gotos.insert(self.entry);
+    }
+
+    // Once to find which gotos are left, and again to put in their labels
+    let mut structurer = Structurer::new(&entries, gotos.clone());
+    let mut body = structurer.structure(0, entries.len(), None);
+    if !structurer.gotos.is_empty() {
+      let labels = structurer.gotos.union(&gotos).copied().collect();
+      body = Structurer::new(&entries, labels).structure(0, entries.len(), None);
+    }
+    if entries[0].address != self.entry {
+      body.insert(0, Statement::Goto(self.entry));
+    }
+
+    Function { entry: self.entry, frame_size: translator.frame_size, body }
+  }
+}
+
+struct Translator<'a> {
+  values: &'a [isize],
+  code: &'a FunctionCode,
+  frame_size: Option<usize>,
+  jump_targets: BTreeSet<usize>,
+}
+
+impl<'a> Translator<'a> {
+  fn translate(&self, address: usize, instruction: &Instruction) -> Item {
+    let params = instruction.params();
+    let next = address + instruction.size();
+
+    if let Some(&target) = self.code.calls.get(&address) {
+      return Item::Simple(format!("call {}", function_name(target)));
+    }
+
+    match instruction.opcode {
+      // Storing the return address for a call
+      Opcode::Add | Opcode::Multiply if self.code.calls.contains_key(&next) => Item::Nop,
+      Opcode::Add | Opcode::Multiply => {
+        let (target, value) = (self.operand(&params[2]), self.arithmetic(instruction));
+        // `add rb+1, #0, rb+1` and friends don't do anything
+        if target == value { Item::Nop } else { Item::Simple(format!("{} = {}", target, value)) }
+      },
+      Opcode::LessThan | Opcode::Equals => {
+        if self.is_dead_comparison(address, instruction) {
+          Item::Nop
+        } else {
+          Item::Simple(format!("{} = {}", self.operand(&params[2]), self.comparison(instruction)))
+        }
+      },
+      Opcode::Input => Item::Simple(format!("{} = input()", self.operand(&params[0]))),
+      Opcode::Output => Item::Simple(format!("output({})", self.operand(&params[0]))),
+      Opcode::RelativeBaseOffset if self.frame_size.is_some() => Item::Nop,
+      Opcode::RelativeBaseOffset => match constant(&params[0]) {
+        Some(offset) if offset < 0 => Item::Simple(format!("rb -= {}", -offset)),
+        _ => Item::Simple(format!("rb += {}", self.operand(&params[0]))),
+      },
+      Opcode::Halt => Item::Simple("halt".to_string()),
+      Opcode::JumpIfTrue | Opcode::JumpIfFalse => self.jump(address, instruction),
+    }
+  }
+
+  fn jump(&self, address: usize, instruction: &Instruction) -> Item {
+    let flow = flow(instruction);
+    let target = flow.targets.first().copied();
+    let indirect_target = || format!("goto *{}", self.operand(&instruction.params()[1]));
+
+    if self.frame_size.is_some() && is_return_jump(instruction) {
+      return Item::Simple("return".to_string());
+    }
+    if !flow.falls_through {
+      return match target {
+        Some(target) => Item::Goto(target),
+        None => Item::Simple(indirect_target()),
+      };
+    }
+    if target.is_none() && !flow.indirect {
+      // Never taken
+      return Item::Nop;
+    }
+
+    let condition = self.jump_condition(address, instruction);
+    match target {
+      Some(target) => Item::Branch { condition, target },
+      None => Item::Simple(format!("if ({}) {}", condition, indirect_target())),
+    }
+  }
+
+  fn jump_condition(&self, address: usize, jump: &Instruction) -> Condition {
+    let condition = match self.folded_comparison(address, jump) {
+      Some(comparison) => self.comparison(&comparison),
+      None => Condition {
+        left: self.operand(&jump.params()[0]),
+        op: "!=",
+        right: "0".to_string(),
+      },
+    };
+    if jump.opcode == Opcode::JumpIfTrue { condition } else { condition.negate() }
+  }
+
+  // The comparison right before a jump that tests its result, as long as
+  //   nothing else jumps in between them
+  fn comparison_before(&self, address: usize, jump: &Instruction) -> Option<Instruction> {
+    if self.jump_targets.contains(&address) {
+      return None;
+    }
+    let comparison = self.code.previous(address)?;
+    let is_comparison = comparison.opcode == Opcode::LessThan || comparison.opcode == Opcode::Equals;
+    if is_comparison && comparison.params()[2] == jump.params()[0] {
+      Some(comparison)
+    } else {
+      None
+    }
+  }
+
+  // Like `comparison_before`, but only if the comparison can stand in for
+  //   the jump's condition. It can't when it overwrites one of its own inputs
+  //   (`eq [63], #39, [63]`) and the assignment stays in.
+  fn folded_comparison(&self, address: usize, jump: &Instruction) -> Option<Instruction> {
+    let comparison = self.comparison_before(address, jump)?;
+    let params = comparison.params();
+    let overwrites_input = params[..2].contains(&params[2]);
+    if overwrites_input && !self.is_dead_comparison(address - comparison.size(), &comparison) {
+      return None;
+    }
+    Some(comparison)
+  }
+
+  fn is_dead_comparison(&self, address: usize, comparison: &Instruction) -> bool {
+    let jump_address = address + comparison.size();
+    let jump = match self.code.instruction(jump_address) {
+      Some(jump) if is_jump(jump.opcode) => jump,
+      _ => return false,
+    };
+    if self.comparison_before(jump_address, &jump).is_none() {
+      return false;
+    }
+    match comparison.params()[2] {
+      Parameter { value, mode: ParameterMode::Position } if value >= 0 => {
+        is_dead_after(self.values, value as usize, jump_address, &jump)
+      },
+      _ => false,
+    }
+  }
+
+  fn operand(&self, param: &Parameter) -> String {
+    match param.mode {
+      ParameterMode::Immediate => param.value.to_string(),
+      ParameterMode::Position => format!("mem[{}]", param.value),
+      ParameterMode::Relative => {
+        let size = match self.frame_size {
+          Some(size) => size as isize,
+          None => return format!("rb[{}]", param.value),
+        };
+        let slot = param.value + size;
+        if slot >= size {
+          format!("out{}", slot - size)
+        } else if slot >= 1 {
+          format!("v{}", slot)
+        } else {
+          format!("rb[{}]", param.value)
+        }
+      },
+    }
+  }
+
+  fn arithmetic(&self, instruction: &Instruction) -> String {
+    let params = instruction.params();
+    let (left, right) = (self.operand(&params[0]), self.operand(&params[1]));
+
+    if let Some(value) = folded_constant(instruction) {
+      return value.to_string();
+    }
+    match (instruction.opcode, constant(&params[0]), constant(&params[1])) {
+      (Opcode::Add, Some(0), _) => right,
+      (Opcode::Add, _, Some(0)) => left,
+      (Opcode::Add, _, Some(value)) if value < 0 && value != isize::MIN => {
+        format!("{} - {}", left, -value)
+      },
+      (Opcode::Add, Some(value), _) if value < 0 && value != isize::MIN => {
+        format!("{} - {}", right, -value)
+      },
+      (Opcode::Add, _, _) => format!("{} + {}", left, right),
+      (_, Some(0), _) | (_, _, Some(0)) => "0".to_string(),
+      (_, Some(1), _) => right,
+      (_, _, Some(1)) => left,
+      (_, Some(-1), _) => format!("-{}", right),
+      (_, _, Some(-1)) => format!("-{}", left),
+      _ => format!("{} * {}", left, right),
+    }
+  }
+
+  fn comparison(&self, instruction: &Instruction) -> Condition {
+    let params = instruction.params();
+    Condition {
+      left: self.operand(&params[0]),
+      op: if instruction.opcode == Opcode::LessThan { "<" } else { "==" },
+      right: self.operand(&params[1]),
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+struct LoopContext {
+  head: usize,
+  // Where a `break` goes
+  exit: usize,
+  // False for do/while, where `continue` would skip to the condition
+  can_continue: bool,
+}
+
+struct Structurer<'a> {
+  entries: &'a [Entry],
+  labels: BTreeSet<usize>,
+  placed_labels: BTreeSet<usize>,
+  // Targets of the gotos that couldn't be turned into anything nicer
+  gotos: BTreeSet<usize>,
+}
+
+impl<'a> Structurer<'a> {
+  fn new(entries: &'a [Entry], labels: BTreeSet<usize>) -> Self {
+    Structurer { entries, labels, placed_labels: BTreeSet::new(), gotos: BTreeSet::new() }
+  }
+
+  // Structures the entries in `start..end`
+  fn structure(&mut self, start: usize, end: usize, context: Option<LoopContext>) -> Vec<Statement> {
+    let entries = self.entries;
+    let mut statements = Vec::new();
+    let mut i = start;
+
+    while i < end {
+      let address = entries[i].address;
+      if self.labels.contains(&address) && self.placed_labels.insert(address) {
+        statements.push(Statement::Label(address));
+      }
+
+      // A loop runs from here to the furthest jump back here
+      let already_in_loop = context.is_some_and(|context| context.head == address);
+      let back_jump = (i..end).rev().find(|&j| match entries[j].item {
+        Item::Branch { target, .. } | Item::Goto(target) => target == address,
+        _ => false,
+      });
+      if let (false, Some(last)) = (already_in_loop, back_jump) {
+        statements.push(self.structure_loop(i, last));
+        i = last + 1;
+        continue;
+      }
+
+      match &entries[i].item {
+        Item::Nop => {},
+        Item::Simple(text) => statements.push(Statement::Simple(text.clone())),
+        Item::Goto(target) => statements.extend(self.goto(*target, entries[i].next, context)),
+        Item::Branch { condition, target } => {
+          if let Some((statement, next)) = self.structure_if(i, end, condition, *target, context) {
+            statements.push(statement);
+            i = next;
+            continue;
+          }
+          if let Some(statement) = self.goto(*target, entries[i].next, context) {
+            statements.push(Statement::If {
+              condition: condition.to_string(),
+              then_body: vec![statement],
+              else_body: vec![],
+            });
+          }
+        },
+      }
+      i += 1;
+    }
+
+    statements
+  }
+
+  fn structure_loop(&mut self, first: usize, last: usize) -> Statement {
+    let entries = self.entries;
+    let head = entries[first].address;
+    let exit = entries[last].next;
+    let context = LoopContext { head, exit, can_continue: true };
+
+    match &entries[last].item {
+      Item::Branch { condition, .. } => {
+        let context = LoopContext { can_continue: false, ..context };
+        Statement::DoWhile {
+          body: self.structure(first, last, Some(context)),
+          condition: condition.to_string(),
+        }
+      },
+      _ => {
+        // A `while` if the loop starts by testing whether to leave it
+        let test = (first..last).find(|&i| !matches!(entries[i].item, Item::Nop));
+        match test.map(|i| (i, &entries[i].item)) {
+          Some((i, Item::Branch { condition, target })) if *target == exit => Statement::While {
+            condition: condition.negate().to_string(),
+            body: self.structure(i + 1, last, Some(context)),
+          },
+          _ => Statement::Loop(self.structure(first, last, Some(context))),
+        }
+      },
+    }
+  }
+
+  // A jump forward over some code is an if, and if that code ends by
+  //   jumping over some more, it's an if/else. Returns the statement, and
+  //   the index of the entry after it.
+  fn structure_if(
+    &mut self,
+    i: usize,
+    end: usize,
+    condition: &Condition,
+    target: usize,
+    context: Option<LoopContext>,
+  ) -> Option<(Statement, usize)> {
+    let entries = self.entries;
+    if target <= entries[i].next {
+      return None;
+    }
+    let then_end = self.position(target, i + 1, end)?;
+
+    if let Item::Goto(else_target) = entries[then_end - 1].item {
+      if else_target > target {
+        if let Some(else_end) = self.position(else_target, then_end, end) {
+          let then_body = self.structure(i + 1, then_end - 1, context);
+          let else_body = self.structure(then_end, else_end, context);
+          let statement = if then_body.is_empty() {
+            // Nothing but the jump over the else, so flip it around
+            Statement::If { condition: condition.to_string(), then_body: else_body, else_body: vec![] }
+          } else {
+            Statement::If { condition: condition.negate().to_string(), then_body, else_body }
+          };
+          return Some((statement, else_end));
+        }
+      }
+    }
+
+    let statement = Statement::If {
+      condition: condition.negate().to_string(),
+      then_body: self.structure(i + 1, then_end, context),
+      else_body: vec![],
+    };
+    Some((statement, then_end))
+  }
+
+  // Index of the entry at `address` in `start..=end`, where `end` stands for
+  //   the address right after the range
+  fn position(&self, address: usize, start: usize, end: usize) -> Option<usize> {
+    (start..end)
+      .find(|&i| self.entries[i].address == address)
+      .or_else(|| if self.entries[end - 1].next == address { Some(end) } else { None })
+  }
+
+  fn goto(&mut self, target: usize, next: usize, context: Option<LoopContext>) -> Option<Statement> {
+    if let Some(context) = context {
+      if target == context.exit {
+        return Some(Statement::Simple("break".to_string()));
+      }
+      if context.can_continue && target == context.head {
+        return Some(Statement::Simple("continue".to_string()));
+      }
+    }
+    if target == next {
+      // Goes where it was going anyway
+      return None;
+    }
+    self.gotos.insert(target);
+    Some(Statement::Goto(target))
+  }
+}
+
+fn decode_at(values: &[isize], address: usize) -> Option<Instruction> {
+  if address >= values.len() {
+    return None;
+  }
+  let read = |address: usize| values.get(address).copied().unwrap_or(0);
+  Instruction::decode(values[address], |i| read(address + 1 + i)).ok()
+}
+
+fn is_jump(opcode: Opcode) -> bool {
+  opcode == Opcode::JumpIfTrue || opcode == Opcode::JumpIfFalse
+}
+
+fn immediate(value: isize) -> Parameter {
+  Parameter { value, mode: ParameterMode::Immediate }
+}
+
+fn constant(param: &Parameter) -> Option<isize> {
+  if param.mode == ParameterMode::Immediate { Some(param.value) } else { None }
+}
+
+// The value an add or multiply computes, if both of its inputs are constants
+fn folded_constant(instruction: &Instruction) -> Option<isize> {
+  let params = instruction.params();
+  let (left, right) = (constant(&params[0])?, constant(&params[1])?);
+  match instruction.opcode {
+    Opcode::Add => left.checked_add(right),
+    Opcode::Multiply => left.checked_mul(right),
+    _ => None,
+  }
+}
+
+// The target of a jump that's always taken, if it's known
+fn always_jumps_to(instruction: &Instruction) -> Option<usize> {
+  let flow = flow(instruction);
+  if !is_jump(instruction.opcode) || flow.falls_through {
+    return None;
+  }
+  flow.targets.first().copied()
+}
+
+// An unconditional jump to the address stored at rb+0
+fn is_return_jump(instruction: &Instruction) -> bool {
+  let flow = flow(instruction);
+  is_jump(instruction.opcode)
+    && !flow.falls_through
+    && flow.indirect
+    && instruction.params()[1] == Parameter { value: 0, mode: ParameterMode::Relative }
+}
+
+// A call stores the address right after the jump relative to rb, then
+//   jumps to the function. Returns the function's address.
+fn call_target(values: &[isize], address: usize) -> Option<usize> {
+  let write = decode_at(values, address)?;
+  if write.opcode != Opcode::Add && write.opcode != Opcode::Multiply {
+    return None;
+  }
+  if write.params()[2].mode != ParameterMode::Relative {
+    return None;
+  }
+  let return_address = folded_constant(&write)?;
+  let jump_address = address + write.size();
+  let jump = decode_at(values, jump_address)?;
+  let target = always_jumps_to(&jump)?;
+  if return_address == (jump_address + jump.size()) as isize { Some(target) } else { None }
+}
+
+// True if every path from the jump overwrites `cell` (or halts) before
+//   reading it
+fn is_dead_after(values: &[isize], cell: usize, jump_address: usize, jump: &Instruction) -> bool {
+  let is_cell = |param: &Parameter| {
+    param.mode == ParameterMode::Position && param.value == cell as isize
+  };
+  let mut visited = BTreeSet::new();
+  let mut pending = vec![jump_address];
+
+  while let Some(address) = pending.pop() {
+    if !visited.insert(address) {
+      continue;
+    }
+    let instruction = match decode_at(values, address) {
+      Some(instruction) => instruction,
+      // The program crashes here, so nothing reads it
+      None => continue,
+    };
+    let write_param = instruction.opcode.write_param();
+    if address != jump_address {
+      let params = instruction.params();
+      if params.iter().enumerate().any(|(i, param)| Some(i) != write_param && is_cell(param)) {
+        return false;
+      }
+      if write_param.is_some_and(|i| is_cell(&params[i])) {
+        continue;
+      }
+    }
+
+    let flow = flow(if address == jump_address { jump } else { &instruction });
+    if flow.indirect {
+      return false;
+    }
+    if flow.falls_through && instruction.opcode != Opcode::Halt {
+      pending.push(address + instruction.size());
+    }
+    pending.extend(flow.targets);
+  }
+  true
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{assemble, parse_program_from_file};
+
+  fn decompile_source(source: &str) -> String {
+    format_decompiled(&decompile(&assemble(source).unwrap()))
+  }
+
+  #[test]
+  fn loop_counters_become_do_while_loops() {
+    let source = "
+      loop: out [counter]
+      add [counter], #1, [counter]
+      lt [counter], #10, [63]
+      jt [63], #loop
+      hlt
+      counter: .data 0
+    ";
+    let expected = [
+      "fn main() {",
+      "  do {",
+      "    output(mem[14])",
+      "    mem[14] = mem[14] + 1",
+      "  } while (mem[14] < 10)",
+      "  halt",
+      "}",
+    ];
+    assert_eq!(decompile_source(source), expected.join("\n") + "\n");
+  }
+
+  #[test]
+  fn tests_at_the_top_become_while_loops() {
+    let source = "
+      in [n]
+      loop: eq [n], #0, [63]
+      jt [63], #done
+      out [n]
+      add [n], #-1, [n]
+      jt #1, #loop
+      done: hlt
+      n: .data 0
+    ";
+    let expected = [
+      "fn main() {",
+      "  mem[19] = input()",
+      "  while (mem[19] != 0) {",
+      "    output(mem[19])",
+      "    mem[19] = mem[19] - 1",
+      "  }",
+      "  halt",
+      "}",
+    ];
+    assert_eq!(decompile_source(source), expected.join("\n") + "\n");
+  }
+
+  #[test]
+  fn equality_tests_become_if_else() {
+    // Day 5's "is the input equal to 8" example, but with a live result
+    let source = "
+      in [9]
+      eq [9], #8, [10]
+      jf [10], #else
+      out #1
+      jt #1, #end
+      else: out [10]
+      end: hlt
+    ";
+    let expected = [
+      "fn main() {",
+      "  mem[9] = input()",
+      "  mem[10] = mem[9] == 8",
+      "  if (mem[9] == 8) {",
+      "    output(1)",
+      "  } else {",
+      "    output(mem[10])",
+      "  }",
+      "  halt",
+      "}",
+    ];
+    assert_eq!(decompile_source(source), expected.join("\n") + "\n");
+  }
+
+  #[test]
+  fn falls_back_to_goto() {
+    // Jumps into the middle of a loop
+    let source = "
+      in [0]
+      jt [0], #inside
+      loop: out #1
+      inside: out #2
+      jf [0], #loop
+      hlt
+    ";
+    let expected = [
+      "fn main() {",
+      "  mem[0] = input()",
+      "  if (mem[0] == 0) {",
+      "  L5:",
+      "    output(1)",
+      "  }",
+      "  output(2)",
+      "  if (mem[0] == 0) {",
+      "    goto L5",
+      "  }",
+      "  halt",
+      "}",
+    ];
+    assert_eq!(decompile_source(source), expected.join("\n") + "\n");
+  }
+
+  #[test]
+  fn decompiles_boost() {
+    let boost = parse_program_from_file(
+      concat!(env!("CARGO_MANIFEST_DIR"), "/../day-09/puzzle-input.txt"),
+    );
+    let functions = decompile(&boost);
+    let entries: Vec<usize> = functions.iter().map(|function| function.entry).collect();
+    assert_eq!(entries, vec![0, 922]);
+    assert_eq!(functions[1].frame_size, Some(3));
+
+    let expected = [
+      "fn f922() {",
+      "  mem[63] = v1 < 3",
+      "  if (v1 >= 3) {",
+      "    out1 = v1 - 1",
+      "    call f922",
+      "    v2 = out1",
+      "    out1 = v1 - 3",
+      "    call f922",
+      "    v1 = out1 + v2",
+      "  }",
+      "  return",
+      "}",
+    ];
+    assert_eq!(functions[1].to_string(), expected.join("\n"));
+
+    let main = functions[0].to_string();
+    assert!(main.starts_with("fn main() {\n  mem[63] = 1187721666102244\n  if (mem[63] >= 34463338) {\n"));
+    assert!(main.contains("\nL904:\n  rb[1] = 27\n  call f922\n"));
+  }
+}
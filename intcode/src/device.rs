@@ -0,0 +1,272 @@
+// I/O devices that can be plugged into a program, so it reads and writes
+//   values without the caller shuttling every one of them through
+//   `push_input` / `resume`.
+//
+// With an input device attached, an Input instruction that finds the input
+//   queue empty asks the device instead. Only when the device has nothing
+//   either does the program yield `NeedsInput`. With an output device
+//   attached, outputs go to the device and `resume` doesn't yield them.
+//
+// Stock devices:
+//   - mpsc channels: `Receiver<isize>` and `Sender<isize>`
+//   - closures: `FnMut() -> Option<isize>` and `FnMut(isize)`
+//   - `VecInput` / `RecordedOutput`: a fixed list of inputs, and a list of
+//     outputs that can be read back afterwards
+//   - `AsciiInput` / `AsciiOutput`: text in and out, one character per value
+//     (terminal.rs builds a whole interactive session out of these)
+//
+// Channels and closures work for any word type (see word.rs), the rest are
+//   isize only.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Stdin, Stdout, Write};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use crate::word::Word;
+
+pub trait InputDevice<W = isize> {
+  // `Ok(None)` means there's no input available (right now, or ever)
+  fn read(&mut self) -> io::Result<Option<W>>;
+}
+
+pub trait OutputDevice<W = isize> {
+  fn write(&mut self, value: W) -> io::Result<()>;
+}
+
+impl<W: Word, F: FnMut() -> Option<W>> InputDevice<W> for F {
+  fn read(&mut self) -> io::Result<Option<W>> {
+    Ok(self())
+  }
+}
+
+impl<W: Word, F: FnMut(W)> OutputDevice<W> for F {
+  fn write(&mut self, value: W) -> io::Result<()> {
+    self(value);
+    Ok(())
+  }
+}
+
+// Blocks until a value arrives. A disconnected channel has no more input.
+impl<W: Word> InputDevice<W> for Receiver<W> {
+  fn read(&mut self) -> io::Result<Option<W>> {
+    Ok(self.recv().ok())
+  }
+}
+
+impl<W: Word> OutputDevice<W> for Sender<W> {
+  fn write(&mut self, value: W) -> io::Result<()> {
+    self.send(value).map_err(|_| {
+      io::Error::new(io::ErrorKind::BrokenPipe, "output channel is disconnected")
+    })
+  }
+}
+
+pub struct VecInput {
+  values: VecDeque<isize>,
+}
+
+impl VecInput {
+  pub fn new(values: &[isize]) -> Self {
+    VecInput { values: values.iter().copied().collect() }
+  }
+}
+
+impl InputDevice for VecInput {
+  fn read(&mut self) -> io::Result<Option<isize>> {
+    Ok(self.values.pop_front())
+  }
+}
+
+// Cloning gives another handle to the same list, like `MemoryTrace`
+#[derive(Clone, Default)]
+pub struct RecordedOutput {
+  values: Arc<Mutex<Vec<isize>>>,
+}
+
+impl RecordedOutput {
+  pub fn new() -> Self {
+    RecordedOutput::default()
+  }
+
+  pub fn values(&self) -> Vec<isize> {
+    self.values.lock().unwrap().clone()
+  }
+}
+
+impl OutputDevice for RecordedOutput {
+  fn write(&mut self, value: isize) -> io::Result<()> {
+    self.values.lock().unwrap().push(value);
+    Ok(())
+  }
+}
+
+// Feeds text to the program one character code at a time, reading a line
+//   whenever it runs out. Lines end in a newline (10), like the ASCII
+//   programs from Days 17, 21 and 25 expect.
+pub struct AsciiInput<R: BufRead> {
+  reader: R,
+  pending: VecDeque<isize>,
+}
+
+impl AsciiInput<BufReader<Stdin>> {
+  pub fn stdin() -> Self {
+    AsciiInput::new(BufReader::new(io::stdin()))
+  }
+}
+
+impl<R: BufRead> AsciiInput<R> {
+  pub fn new(reader: R) -> Self {
+    AsciiInput { reader, pending: VecDeque::new() }
+  }
+
+  // The next line without its line ending, or `None` at the end of the input
+  pub(crate) fn read_line(&mut self) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if self.reader.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    line.truncate(line.trim_end_matches(['\r', '\n']).len());
+    Ok(Some(line))
+  }
+}
+
+// What the program gets for a line of text
+pub(crate) fn ascii_codes(line: &str) -> impl Iterator<Item = isize> + '_ {
+  line.bytes().map(isize::from).chain(Some(10))
+}
+
+impl<R: BufRead> InputDevice for AsciiInput<R> {
+  fn read(&mut self) -> io::Result<Option<isize>> {
+    if self.pending.is_empty() {
+      match self.read_line()? {
+        Some(line) => self.pending.extend(ascii_codes(&line)),
+        None => return Ok(None),
+      }
+    }
+    Ok(self.pending.pop_front())
+  }
+}
+
+// Prints ASCII values as text. Anything outside of the ASCII range is
+//   printed as a number on its own line, since that's how these programs
+//   report their actual answer.
+pub struct AsciiOutput<W: Write> {
+  writer: W,
+}
+
+impl AsciiOutput<Stdout> {
+  pub fn stdout() -> Self {
+    AsciiOutput::new(io::stdout())
+  }
+}
+
+impl<W: Write> AsciiOutput<W> {
+  pub fn new(writer: W) -> Self {
+    AsciiOutput { writer }
+  }
+}
+
+impl<W: Write> OutputDevice for AsciiOutput<W> {
+  fn write(&mut self, value: isize) -> io::Result<()> {
+    if (0..128).contains(&value) {
+      self.writer.write_all(&[value as u8])?;
+      if value == 10 {
+        self.writer.flush()?;
+      }
+    } else {
+      writeln!(self.writer, "{}", value)?;
+      self.writer.flush()?;
+    }
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Program, Yield};
+  use std::sync::mpsc::channel;
+  use std::thread;
+
+  // Adds 1 to every input, forever
+  const ADD_ONE: [isize; 12] = [3,11,1001,11,1,11,4,11,1105,1,0,0];
+
+  #[test]
+  fn vectors_and_closures() {
+    let output = RecordedOutput::new();
+    let mut program = Program::new(&ADD_ONE);
+    program.set_input_device(Box::new(VecInput::new(&[1, 2, 3])));
+    program.set_output_device(Box::new(output.clone()));
+
+    assert_eq!(program.resume(), Ok(Yield::NeedsInput));
+    assert_eq!(output.values(), vec![2, 3, 4]);
+
+    // Queued inputs still come first
+    let mut next: isize = 10;
+    program.push_input(100);
+    program.set_input_device(Box::new(move || {
+      next += 10;
+      if next <= 30 { Some(next) } else { None }
+    }));
+    let (sender, receiver) = channel();
+    program.set_output_device(Box::new(move |value: isize| sender.send(value).unwrap()));
+    assert_eq!(program.resume(), Ok(Yield::NeedsInput));
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![101, 21, 31]);
+  }
+
+  #[test]
+  fn channels_connect_programs() {
+    // Two programs passing a value back and forth, each adding 1, until it
+    //   gets to 10
+    let (to_first, first_inputs) = channel();
+    let (to_second, second_inputs) = channel();
+    let (to_main, results) = channel();
+
+    let mut first = Program::new(&ADD_ONE);
+    first.push_input(0);
+    first.set_input_device(Box::new(first_inputs));
+    first.set_output_device(Box::new(to_second));
+
+    let mut second = Program::new(&ADD_ONE);
+    second.set_input_device(Box::new(second_inputs));
+    let mut to_first = Some(to_first);
+    second.set_output_device(Box::new(move |value: isize| {
+      if value < 10 {
+        to_first.as_ref().unwrap().send(value).unwrap();
+      } else {
+        to_main.send(value).unwrap();
+        // Hang up, so the first program runs out of input
+        to_first = None;
+      }
+    }));
+
+    let first = thread::spawn(move || first.resume());
+    let second = thread::spawn(move || second.resume());
+
+    assert_eq!(results.recv().unwrap(), 10);
+    // Once the first program stops, its end of the second's input channel
+    //   goes away too
+    assert_eq!(first.join().unwrap(), Ok(Yield::NeedsInput));
+    assert_eq!(second.join().unwrap(), Ok(Yield::NeedsInput));
+  }
+
+  #[test]
+  fn ascii_in_and_out() {
+    // Echoes its input back until it reads a 0
+    let echo = vec![3,11,1006,11,10,4,11,1105,1,0,99,0];
+    let mut program = Program::new(&echo);
+    program.set_input_device(Box::new(AsciiInput::new("hi\n".as_bytes())));
+    assert_eq!(program.run(&[]).unwrap(), vec![104, 105, 10]);
+
+    let mut out = Vec::new();
+    {
+      let mut device = AsciiOutput::new(&mut out);
+      for &value in [104, 105, 10, 19690720].iter() {
+        device.write(value).unwrap();
+      }
+    }
+    assert_eq!(String::from_utf8(out).unwrap(), "hi\n19690720\n");
+  }
+}
@@ -0,0 +1,172 @@
+// Turns a raw Intcode image into a readable listing, one instruction per line.
+//   For example, the start of the Day 9 BOOST program:
+//
+//    0: mul #34463338, #34463338, [63]
+//    4: lt [63], #34463338, [63]
+//    8: jt [63], #53
+//   ...
+//   25: in rb+0
+//   ...
+//   63: .data 0, 0
+//
+// This is a straight linear sweep starting at address 0, so data that happens
+//   to decode as a valid instruction will show up as one. Anything that doesn't
+//   decode (or runs off the end of the image) is emitted as a `.data` directive.
+//   So are instructions with junk in their unused mode digits, so that feeding
+//   the listing back into `assemble` gives the exact same image.
+
+use std::fmt;
+
+use crate::instruction::{Instruction, MAX_PARAMS};
+
+// Max number of values on a single `.data` line
+const DATA_VALUES_PER_LINE: usize = 8;
+
+#[derive(Debug, PartialEq)]
+pub enum LineContents {
+  Instruction(Instruction),
+  Data(Vec<isize>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ListingLine {
+  pub address: usize,
+  pub contents: LineContents,
+}
+
+impl ListingLine {
+  // Number of memory cells covered by this line
+  pub fn size(&self) -> usize {
+    match &self.contents {
+      LineContents::Instruction(instruction) => instruction.size(),
+      LineContents::Data(values) => values.len(),
+    }
+  }
+}
+
+impl fmt::Display for ListingLine {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    // Right-align addresses so the mnemonics line up
+    let width = f.width().unwrap_or(0);
+    write!(f, "{:>width$}: ", self.address, width = width)?;
+    match &self.contents {
+      LineContents::Instruction(instruction) => write!(f, "{}", instruction),
+      LineContents::Data(values) => {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        write!(f, ".data {}", values.join(", "))
+      },
+    }
+  }
+}
+
+pub fn disassemble(values: &[isize]) -> Vec<ListingLine> {
+  let mut lines = Vec::new();
+  let mut pending_data: Vec<isize> = Vec::new();
+  let mut address = 0;
+
+  while address < values.len() {
+    match decode_at(values, address) {
+      Some(instruction) => {
+        flush_data(&mut lines, &mut pending_data, address);
+        lines.push(ListingLine {
+          address,
+          contents: LineContents::Instruction(instruction),
+        });
+        address += instruction.size();
+      },
+      None => {
+        pending_data.push(values[address]);
+        address += 1;
+        if pending_data.len() == DATA_VALUES_PER_LINE {
+          flush_data(&mut lines, &mut pending_data, address);
+        }
+      },
+    }
+  }
+  flush_data(&mut lines, &mut pending_data, address);
+
+  lines
+}
+
+pub fn format_listing(lines: &[ListingLine]) -> String {
+  let last_address = lines.last().map_or(0, |line| line.address);
+  let width = last_address.to_string().len();
+
+  lines
+    .iter()
+    .map(|line| format!("{:>width$}\n", line, width = width))
+    .collect()
+}
+
+// Only accepts instructions that fit in the image and re-encode to exactly the
+//   same values, so that the listing round-trips through the assembler.
+fn decode_at(values: &[isize], address: usize) -> Option<Instruction> {
+  let end = values.len().min(address + 1 + MAX_PARAMS);
+  let words = &values[address..end];
+
+  let instruction = Instruction::decode(
+    words[0],
+    |i| words.get(i + 1).copied().unwrap_or(0),
+  ).ok()?;
+
+  if instruction.size() > words.len() || instruction.encode()[..] != words[..instruction.size()] {
+    return None;
+  }
+  Some(instruction)
+}
+
+// `address` is the address just past the pending data
+fn flush_data(lines: &mut Vec<ListingLine>, pending_data: &mut Vec<isize>, address: usize) {
+  if pending_data.is_empty() {
+    return;
+  }
+  lines.push(ListingLine {
+    address: address - pending_data.len(),
+    contents: LineContents::Data(std::mem::take(pending_data)),
+  });
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn disassembles_instructions() {
+    // Day 9 quine
+    let program = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+    let expected = [
+      " 0: arb #1",
+      " 2: out rb-1",
+      " 4: add [100], #1, [100]",
+      " 8: eq [100], #16, [101]",
+      "12: jf [101], #0",
+      "15: hlt",
+    ];
+    assert_eq!(format_listing(&disassemble(&program)), expected.join("\n") + "\n");
+  }
+
+  #[test]
+  fn falls_back_to_data() {
+    // The trailing values after the halt don't decode as instructions.
+    // 30104 does decode, but has junk in an unused mode digit.
+    let program = vec![1005,8,5,4,9,30104,10,99,-1,-1,-2,0,0,0,0,0,0,0,0,0];
+    let expected = [
+      " 0: jt [8], #5",
+      " 3: out [9]",
+      " 5: .data 30104, 10",
+      " 7: hlt",
+      " 8: .data -1, -1, -2, 0, 0, 0, 0, 0",
+      "16: .data 0, 0, 0, 0",
+    ];
+    assert_eq!(format_listing(&disassemble(&program)), expected.join("\n") + "\n");
+  }
+
+  #[test]
+  fn truncated_instruction_is_data() {
+    let lines = disassemble(&[1101, 1]);
+    assert_eq!(lines, vec![
+      ListingLine { address: 0, contents: LineContents::Data(vec![1101, 1]) },
+    ]);
+  }
+}
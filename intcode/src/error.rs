@@ -0,0 +1,118 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::limits::Limit;
+
+// Everything that can go wrong while running an Intcode program.
+// `instruction_pointer` is the address of the failing instruction, and
+//   `opcode` is the raw value stored there (modes included, e.g. 1102).
+// With a word wider than isize (see word.rs), values that don't fit are
+//   clamped: to isize::MIN / isize::MAX, or usize::MAX for addresses.
+#[derive(Debug, PartialEq)]
+pub enum IntcodeError {
+  InvalidOpcode {
+    instruction_pointer: usize,
+    opcode: isize,
+  },
+  InvalidParameterMode {
+    instruction_pointer: usize,
+    opcode: isize,
+    mode: isize,
+  },
+  // Per Day 5 instructions, params that an instruction writes to will never
+  //   be in immediate mode.
+  ImmediateModeWrite {
+    instruction_pointer: usize,
+    opcode: isize,
+  },
+  NegativeAddress {
+    instruction_pointer: usize,
+    opcode: isize,
+    address: isize,
+  },
+  AddressOutOfRange {
+    instruction_pointer: usize,
+    opcode: isize,
+    address: usize,
+  },
+  // An Add or Multiply result too big for the word, or a relative base or
+  //   relative address too big for an isize
+  Overflow {
+    instruction_pointer: usize,
+    opcode: isize,
+  },
+  InstructionPointerOutOfRange {
+    instruction_pointer: usize,
+  },
+  LoopLimitExceeded {
+    instruction_pointer: usize,
+    iterations: usize,
+  },
+  // Only from `run`, which has no other way of saying so. `resume` yields
+  //   `BudgetExhausted` instead, see limits.rs.
+  BudgetExhausted {
+    instruction_pointer: usize,
+    limit: Limit,
+  },
+  // The trace sink couldn't record an instruction. The instruction itself
+  //   still ran.
+  TraceFailed {
+    instruction_pointer: usize,
+    message: String,
+  },
+  // An attached input or output device failed, see device.rs
+  DeviceFailed {
+    instruction_pointer: usize,
+    message: String,
+  },
+  // `run` was called on a program that has already halted
+  AlreadyHalted,
+}
+
+impl fmt::Display for IntcodeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      IntcodeError::InvalidOpcode { instruction_pointer, opcode } => write!(
+        f, "invalid opcode {} at address {}", opcode, instruction_pointer,
+      ),
+      IntcodeError::InvalidParameterMode { instruction_pointer, opcode, mode } => write!(
+        f, "invalid parameter mode {} in opcode {} at address {}",
+        mode, opcode, instruction_pointer,
+      ),
+      IntcodeError::ImmediateModeWrite { instruction_pointer, opcode } => write!(
+        f, "opcode {} at address {} writes to an immediate mode param",
+        opcode, instruction_pointer,
+      ),
+      IntcodeError::NegativeAddress { instruction_pointer, opcode, address } => write!(
+        f, "opcode {} at address {} uses negative address {}",
+        opcode, instruction_pointer, address,
+      ),
+      IntcodeError::AddressOutOfRange { instruction_pointer, opcode, address } => write!(
+        f, "opcode {} at address {} uses out of range address {}",
+        opcode, instruction_pointer, address,
+      ),
+      IntcodeError::Overflow { instruction_pointer, opcode } => write!(
+        f, "opcode {} at address {} overflowed", opcode, instruction_pointer,
+      ),
+      IntcodeError::InstructionPointerOutOfRange { instruction_pointer } => write!(
+        f, "instruction pointer {} is outside of program memory", instruction_pointer,
+      ),
+      IntcodeError::LoopLimitExceeded { instruction_pointer, iterations } => write!(
+        f, "exceeded {} iterations without yielding (stopped at address {})",
+        iterations, instruction_pointer,
+      ),
+      IntcodeError::BudgetExhausted { instruction_pointer, limit } => write!(
+        f, "ran out of {} (stopped at address {})", limit, instruction_pointer,
+      ),
+      IntcodeError::TraceFailed { instruction_pointer, message } => write!(
+        f, "failed to trace instruction at address {}: {}", instruction_pointer, message,
+      ),
+      IntcodeError::DeviceFailed { instruction_pointer, message } => write!(
+        f, "I/O device failed at address {}: {}", instruction_pointer, message,
+      ),
+      IntcodeError::AlreadyHalted => write!(f, "cant run a halted program"),
+    }
+  }
+}
+
+impl Error for IntcodeError {}
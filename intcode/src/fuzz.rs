@@ -0,0 +1,574 @@
+// Differential fuzzing. Days 2, 5, 7 and 9 used to have an Intcode
+//   interpreter each, and they now all share this crate, but there are still
+//   several ways to run a program that have to agree with each other: the
+//   interpreter, the compiled tier, the traced path (which also goes through
+//   `step` instead of the interpreter's inner loop), a run that's
+//   snapshotted and restored halfway through, and every word type.
+//
+// `fuzz` generates random programs, restricted to the opcodes and parameter
+//   modes of one day's puzzle (`Subset`), runs each on every `Engine`, and
+//   compares the outputs, how the run ended, and the final memory. When two
+//   engines disagree, the program is shrunk to a minimal counterexample
+//   before it's reported:
+//
+//   engines disagree: interpreter vs compiled
+//   program: 1101,2,3,5,99
+//   ...
+//
+// Random jumps loop forever often enough, so every run has an instruction
+//   budget, and running out of it is just another way to end.
+//
+// Engines with a narrower word are allowed to stop with an overflow where a
+//   wider one keeps going (maybe until it overflows too, or fails some other
+//   way on that same instruction), as long as they agree up to that point.
+
+use std::fmt;
+
+use crate::bigint::BigInt;
+use crate::computer::{Program, Yield};
+use crate::error::IntcodeError;
+use crate::instruction::{Instruction, Opcode, Parameter, ParameterMode};
+use crate::limits::Limits;
+use crate::snapshot::Snapshot;
+use crate::trace::MemoryTrace;
+use crate::word::Word;
+
+// Instructions per generated program, not counting the final halt
+const MAX_INSTRUCTIONS: usize = 12;
+// Cells of data after the code, for position mode params to point at
+const DATA_LEN: usize = 8;
+// Memory past the end of the program that's compared too, since relative
+//   mode can reach a bit beyond it
+const SCRATCH_LEN: usize = 16;
+// Most candidates the shrinker tries before settling for what it has
+const MAX_SHRINK_ATTEMPTS: usize = 10_000;
+
+// The instructions and parameter modes a day's puzzle used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subset {
+  // Add, Multiply and Halt, position mode only
+  Day2,
+  // Adds I/O, jumps and comparisons, and immediate mode
+  Day5,
+  // Adds RelativeBaseOffset and relative mode. Day 7 didn't add anything.
+  Day9,
+}
+
+impl Subset {
+  pub fn opcodes(self) -> &'static [Opcode] {
+    match self {
+      Subset::Day2 => &[Opcode::Add, Opcode::Multiply, Opcode::Halt],
+      Subset::Day5 => &[
+        Opcode::Add, Opcode::Multiply, Opcode::Input, Opcode::Output, Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse, Opcode::LessThan, Opcode::Equals, Opcode::Halt,
+      ],
+      Subset::Day9 => &crate::instruction::ALL_OPCODES,
+    }
+  }
+
+  pub fn modes(self) -> &'static [ParameterMode] {
+    match self {
+      Subset::Day2 => &[ParameterMode::Position],
+      Subset::Day5 => &[ParameterMode::Position, ParameterMode::Immediate],
+      Subset::Day9 => &[ParameterMode::Position, ParameterMode::Immediate, ParameterMode::Relative],
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Subset> {
+    match name {
+      "day2" => Some(Subset::Day2),
+      "day5" => Some(Subset::Day5),
+      "day9" => Some(Subset::Day9),
+      _ => None,
+    }
+  }
+}
+
+// A program and the inputs it's run with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+  pub program: Vec<isize>,
+  pub inputs: Vec<isize>,
+}
+
+impl Case {
+  // Always the same case for the same subset and seed
+  pub fn generate(subset: Subset, seed: u64) -> Case {
+    let mut rng = Rng::new(seed);
+    let opcodes = subset.opcodes();
+    let modes = subset.modes();
+
+    // Opcodes and modes first, since the addresses depend on the length
+    let mut shapes = Vec::new();
+    for _ in 0..rng.range(1, MAX_INSTRUCTIONS + 1) {
+      let opcode = opcodes[rng.range(0, opcodes.len())];
+      let param_modes: Vec<ParameterMode> = (0..opcode.num_params())
+        .map(|i| loop {
+          let mode = modes[rng.range(0, modes.len())];
+          // Valid programs never write to an immediate mode param
+          if opcode.write_param() != Some(i) || mode != ParameterMode::Immediate {
+            break mode;
+          }
+        })
+        .collect();
+      shapes.push((opcode, param_modes));
+    }
+    shapes.push((Opcode::Halt, Vec::new()));
+
+    let mut starts = Vec::new();
+    let mut code_len = 0;
+    for (opcode, _) in shapes.iter() {
+      starts.push(code_len);
+      code_len += opcode.num_params() + 1;
+    }
+    let len = code_len + DATA_LEN;
+
+    let mut program = Vec::with_capacity(len);
+    for (opcode, param_modes) in shapes {
+      let is_jump = matches!(opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse);
+      let params: Vec<Parameter> = param_modes
+        .iter()
+        .enumerate()
+        .map(|(i, &mode)| Parameter {
+          mode,
+          value: match mode {
+            ParameterMode::Position => rng.range(0, len) as isize,
+            ParameterMode::Relative => rng.range(0, len + 4) as isize - 4,
+            // Mostly jump to the start of an instruction, or it's hardly a
+            //   program
+            ParameterMode::Immediate if is_jump && i == 1 => starts[rng.range(0, starts.len())] as isize,
+            ParameterMode::Immediate => rng.value(),
+          },
+        })
+        .collect();
+      program.extend(Instruction::new(opcode, &params).encode());
+    }
+    for _ in 0..DATA_LEN {
+      program.push(rng.value());
+    }
+
+    let inputs = (0..rng.range(0, 4)).map(|_| rng.value()).collect();
+    Case { program, inputs }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Ending {
+  Halted,
+  NeedsInput,
+  OutOfBudget,
+  Error(IntcodeError),
+}
+
+impl fmt::Display for Ending {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Ending::Halted => write!(f, "halted"),
+      Ending::NeedsInput => write!(f, "needs input"),
+      Ending::OutOfBudget => write!(f, "out of budget"),
+      Ending::Error(err) => write!(f, "error: {}", err),
+    }
+  }
+}
+
+// Everything about a run that engines have to agree on. Values are BigInts,
+//   so engines with different words can be compared.
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+  pub outputs: Vec<BigInt>,
+  pub ending: Ending,
+  pub instruction_count: usize,
+  pub instruction_pointer: usize,
+  // The program's memory, plus some scratch space past the end of it
+  pub memory: Vec<BigInt>,
+}
+
+impl Outcome {
+  fn overflowed(&self) -> bool {
+    matches!(self.ending, Ending::Error(IntcodeError::Overflow { .. }))
+  }
+}
+
+// One way of running a program. `word_bits` is the width of the word, or
+//   `None` if it can't overflow.
+#[derive(Clone, Copy)]
+pub struct Engine {
+  pub name: &'static str,
+  pub word_bits: Option<u32>,
+  // Runs the program with the inputs queued up and the given instruction
+  //   budget
+  pub run: fn(&[isize], &[isize], usize) -> Outcome,
+}
+
+impl Engine {
+  // Every way this crate has of running a program. The first one, the plain
+  //   interpreter, is what everything else is compared against.
+  pub fn all() -> Vec<Engine> {
+    let isize_bits = Some(isize::BITS);
+    vec![
+      Engine { name: "interpreter", word_bits: isize_bits, run: run_words::<isize> },
+      Engine { name: "compiled", word_bits: isize_bits, run: run_compiled::<isize> },
+      Engine { name: "traced", word_bits: isize_bits, run: run_traced },
+      Engine { name: "snapshotted", word_bits: isize_bits, run: run_snapshotted },
+      Engine { name: "i64", word_bits: Some(64), run: run_words::<i64> },
+      Engine { name: "i128", word_bits: Some(128), run: run_words::<i128> },
+      Engine { name: "bigint", word_bits: None, run: run_words::<BigInt> },
+      Engine { name: "bigint compiled", word_bits: None, run: run_compiled::<BigInt> },
+    ]
+  }
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+  pub case: Case,
+  pub engines: (&'static str, &'static str),
+  pub outcomes: (Outcome, Outcome),
+}
+
+impl fmt::Display for Divergence {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let join = |values: &[isize]| values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(",");
+    writeln!(f, "engines disagree: {} vs {}", self.engines.0, self.engines.1)?;
+    writeln!(f, "program: {}", join(&self.case.program))?;
+    writeln!(f, "inputs: {}", join(&self.case.inputs))?;
+
+    let outcomes = [(self.engines.0, &self.outcomes.0), (self.engines.1, &self.outcomes.1)];
+    for (name, outcome) in outcomes.iter() {
+      writeln!(
+        f,
+        "{}: {} at address {} after {} instructions, outputs {:?}",
+        name, outcome.ending, outcome.instruction_pointer, outcome.instruction_count, outcome.outputs,
+      )?;
+    }
+    let (a, b) = (&self.outcomes.0.memory, &self.outcomes.1.memory);
+    for (address, (a, b)) in a.iter().zip(b.iter()).enumerate().filter(|(_, (a, b))| a != b) {
+      writeln!(f, "memory[{}]: {} vs {}", address, a, b)?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FuzzOptions {
+  pub subset: Subset,
+  // Case `i` is generated from `seed + i`, so a failing case can be rerun
+  //   on its own
+  pub seed: u64,
+  pub cases: usize,
+  // Instructions each run may execute
+  pub budget: usize,
+}
+
+impl Default for FuzzOptions {
+  fn default() -> Self {
+    FuzzOptions { subset: Subset::Day9, seed: 0, cases: 1000, budget: 1000 }
+  }
+}
+
+// Runs `options.cases` random programs on all the engines. Stops at the
+//   first disagreement, and returns it shrunk (boxed, since it holds two
+//   whole outcomes).
+pub fn fuzz(engines: &[Engine], options: FuzzOptions) -> Result<(), Box<Divergence>> {
+  for i in 0..options.cases {
+    let case = Case::generate(options.subset, options.seed.wrapping_add(i as u64));
+    if let Some(divergence) = case.check(engines, options.budget) {
+      return Err(Box::new(divergence.shrink(engines, options.budget)));
+    }
+  }
+  Ok(())
+}
+
+impl Case {
+  // Runs the case on every engine, and compares each against the first
+  pub fn check(&self, engines: &[Engine], budget: usize) -> Option<Divergence> {
+    let (reference, others) = engines.split_first()?;
+    let expected = (reference.run)(&self.program, &self.inputs, budget);
+    for engine in others {
+      // A wider word only has to agree up to where the reference overflowed,
+      //   and it's best not to go much further. Squaring in a loop without
+      //   ever overflowing gets out of hand quickly.
+      let budget = if expected.overflowed() && width(engine) > width(reference) {
+        expected.instruction_count + 1
+      } else {
+        budget
+      };
+      let outcome = (engine.run)(&self.program, &self.inputs, budget);
+      if !agree((reference, &expected), (engine, &outcome)) {
+        return Some(Divergence {
+          case: self.clone(),
+          engines: (reference.name, engine.name),
+          outcomes: (expected, outcome),
+        });
+      }
+    }
+    None
+  }
+}
+
+impl Divergence {
+  // Makes the case as small as it'll go while the engines still disagree:
+  //   drops inputs, deletes runs of words from the program, and moves values
+  //   towards zero.
+  pub fn shrink(self, engines: &[Engine], budget: usize) -> Divergence {
+    let mut best = self;
+    let mut attempts = 0;
+    let mut try_case = |best: &mut Divergence, case: Case| {
+      attempts += 1;
+      if attempts > MAX_SHRINK_ATTEMPTS {
+        return false;
+      }
+      match case.check(engines, budget) {
+        Some(smaller) => {
+          *best = smaller;
+          true
+        },
+        None => false,
+      }
+    };
+
+    let mut shrunk = true;
+    while shrunk {
+      shrunk = false;
+
+      let mut i = 0;
+      while i < best.case.inputs.len() {
+        let mut case = best.case.clone();
+        case.inputs.remove(i);
+        if try_case(&mut best, case) {
+          shrunk = true;
+        } else {
+          i += 1;
+        }
+      }
+
+      let mut chunk = best.case.program.len() / 2;
+      while chunk > 0 {
+        let mut start = 0;
+        while start + chunk <= best.case.program.len() {
+          let mut case = best.case.clone();
+          case.program.drain(start..start + chunk);
+          if try_case(&mut best, case) {
+            shrunk = true;
+          } else {
+            start += chunk;
+          }
+        }
+        chunk /= 2;
+      }
+
+      for i in 0..best.case.program.len() {
+        let value = best.case.program[i];
+        for &smaller in [0, value / 2].iter() {
+          if smaller == value {
+            continue;
+          }
+          let mut case = best.case.clone();
+          case.program[i] = smaller;
+          if try_case(&mut best, case) {
+            shrunk = true;
+            break;
+          }
+        }
+      }
+    }
+    best
+  }
+}
+
+fn agree(a: (&Engine, &Outcome), b: (&Engine, &Outcome)) -> bool {
+  if a.1 == b.1 {
+    return true;
+  }
+  let (narrow, wide) = if width(a.0) < width(b.0) { (a.1, b.1) } else { (b.1, a.1) };
+  width(a.0) != width(b.0)
+    && narrow.overflowed()
+    && wide.instruction_count >= narrow.instruction_count
+    && wide.outputs.starts_with(&narrow.outputs)
+}
+
+// `None` (can't overflow) sorts after every width
+fn width(engine: &Engine) -> (bool, Option<u32>) {
+  (engine.word_bits.is_none(), engine.word_bits)
+}
+
+fn run_words<W: Word>(values: &[isize], inputs: &[isize], budget: usize) -> Outcome {
+  run_with(values, inputs, budget, |_: &mut Program<W>| ())
+}
+
+fn run_compiled<W: Word>(values: &[isize], inputs: &[isize], budget: usize) -> Outcome {
+  run_with(values, inputs, budget, |program: &mut Program<W>| program.enable_compiler())
+}
+
+fn run_traced(values: &[isize], inputs: &[isize], budget: usize) -> Outcome {
+  run_with(values, inputs, budget, |program: &mut Program| {
+    program.set_trace_sink(Box::new(MemoryTrace::new()))
+  })
+}
+
+fn run_with<W: Word>(
+  values: &[isize],
+  inputs: &[isize],
+  budget: usize,
+  setup: impl FnOnce(&mut Program<W>),
+) -> Outcome {
+  let words: Vec<W> = values.iter().map(|&value| W::from_isize(value)).collect();
+  let mut program = Program::from_words(&words);
+  setup(&mut program);
+  for &input in inputs {
+    program.push_input(W::from_isize(input));
+  }
+  let mut outputs = Vec::new();
+  let ending = drive(&mut program, budget, &mut outputs);
+  outcome(&program, values.len(), outputs, ending)
+}
+
+// Runs half the budget, then saves a snapshot as text and carries on from a
+//   program restored from it
+fn run_snapshotted(values: &[isize], inputs: &[isize], budget: usize) -> Outcome {
+  let mut program = Program::new(values);
+  for &input in inputs {
+    program.push_input(input);
+  }
+  let mut outputs = Vec::new();
+  let mut ending = drive(&mut program, budget / 2, &mut outputs);
+  if ending == Ending::OutOfBudget && program.instruction_count() < budget {
+    let snapshot = Snapshot::parse(&program.snapshot().to_text()).unwrap();
+    program = Program::restore(&snapshot);
+    ending = drive(&mut program, budget, &mut outputs);
+  }
+  outcome(&program, values.len(), outputs, ending)
+}
+
+// Runs until the program stops for any reason, or until it's executed
+//   `budget` instructions in total
+fn drive<W: Word>(program: &mut Program<W>, budget: usize, outputs: &mut Vec<BigInt>) -> Ending {
+  let remaining = budget.saturating_sub(program.instruction_count());
+  program.set_limits(Limits { instructions: Some(remaining), ..Limits::default() });
+  loop {
+    match program.resume() {
+      Ok(Yield::Output(value)) => outputs.push(to_bigint(&value)),
+      Ok(Yield::Halted) => return Ending::Halted,
+      Ok(Yield::NeedsInput) => return Ending::NeedsInput,
+      Ok(Yield::BudgetExhausted(_)) => return Ending::OutOfBudget,
+      Err(err) => return Ending::Error(err),
+    }
+  }
+}
+
+fn outcome<W: Word>(program: &Program<W>, len: usize, outputs: Vec<BigInt>, ending: Ending) -> Outcome {
+  Outcome {
+    outputs,
+    ending,
+    instruction_count: program.instruction_count(),
+    instruction_pointer: program.instruction_pointer(),
+    memory: (0..len + SCRATCH_LEN).map(|address| to_bigint(&program.read(address))).collect(),
+  }
+}
+
+fn to_bigint<W: Word>(value: &W) -> BigInt {
+  value.to_string().parse().unwrap()
+}
+
+// xorshift64*, seeded through splitmix64 so nearby seeds give unrelated
+//   cases. Plenty for picking opcodes.
+struct Rng(u64);
+
+impl Rng {
+  fn new(seed: u64) -> Self {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    Rng((z ^ (z >> 31)) | 1)
+  }
+
+  fn next(&mut self) -> u64 {
+    self.0 ^= self.0 >> 12;
+    self.0 ^= self.0 << 25;
+    self.0 ^= self.0 >> 27;
+    self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+  }
+
+  // In `low..high`
+  fn range(&mut self, low: usize, high: usize) -> usize {
+    low + (self.next() % (high - low) as u64) as usize
+  }
+
+  // Mostly small, so comparisons and jumps do something interesting, but
+  //   now and then big enough to overflow after a few multiplications
+  fn value(&mut self) -> isize {
+    if self.range(0, 16) == 0 {
+      let magnitude = 1isize << self.range(20, 62);
+      if self.range(0, 2) == 0 { magnitude } else { -magnitude }
+    } else {
+      self.range(0, 24) as isize - 3
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn all_engines_agree() {
+    for &subset in [Subset::Day2, Subset::Day5, Subset::Day9].iter() {
+      let options = FuzzOptions { subset, seed: 2019, cases: 300, ..FuzzOptions::default() };
+      if let Err(divergence) = fuzz(&Engine::all(), options) {
+        panic!("{}", divergence);
+      }
+    }
+  }
+
+  #[test]
+  fn cases_stay_in_their_subset() {
+    for &subset in [Subset::Day2, Subset::Day5, Subset::Day9].iter() {
+      for seed in 0..100 {
+        let case = Case::generate(subset, seed);
+        assert_eq!(case, Case::generate(subset, seed));
+
+        // The code is one straight run of instructions, ending in a halt
+        let mut address = 0;
+        loop {
+          let instruction =
+            Instruction::decode(case.program[address], |i| case.program[address + 1 + i]).unwrap();
+          assert!(subset.opcodes().contains(&instruction.opcode));
+          for param in instruction.params() {
+            assert!(subset.modes().contains(&param.mode));
+          }
+          if instruction.opcode == Opcode::Halt {
+            break;
+          }
+          address += instruction.size();
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn shrinks_to_a_minimal_counterexample() {
+    // Gets any output of 10 or more wrong
+    fn buggy(values: &[isize], inputs: &[isize], budget: usize) -> Outcome {
+      let mut outcome = run_words::<isize>(values, inputs, budget);
+      for output in outcome.outputs.iter_mut() {
+        if *output >= BigInt::from(10isize) {
+          *output = BigInt::zero();
+        }
+      }
+      outcome
+    }
+    let engines = [
+      Engine::all()[0],
+      Engine { name: "buggy", word_bits: Some(64), run: buggy },
+    ];
+
+    let options = FuzzOptions { subset: Subset::Day5, seed: 7, ..FuzzOptions::default() };
+    let divergence = fuzz(&engines, options).unwrap_err();
+    assert_eq!(divergence.engines, ("interpreter", "buggy"));
+    // Nothing more than an output of a big enough immediate
+    assert_eq!(divergence.case.program.len(), 2, "{}", divergence);
+    assert_eq!(divergence.case.program[0], 104);
+    assert!(divergence.case.inputs.is_empty());
+    assert!(divergence.to_string().starts_with("engines disagree: interpreter vs buggy\nprogram: 104,"));
+  }
+}
@@ -0,0 +1,316 @@
+// Decoding of a single Intcode instruction.
+//
+// The opcode lives in the last two decimal digits of the instruction value,
+//   and the parameter modes are the digits above that, read right-to-left:
+//
+//   ABCDE
+//    1002
+//
+//   DE - two-digit opcode,      02 == opcode 2
+//    C - mode of 1st parameter,  0 == position mode
+//    B - mode of 2nd parameter,  1 == immediate mode
+//    A - mode of 3rd parameter,  0 == position mode (omitted leading zero)
+//
+// This used to be done by formatting the value as a string and slicing it,
+//   which dominated the runtime of longer programs. Plain div/mod decodes
+//   about 4x faster (benches/boost.rs times both).
+
+use std::fmt;
+
+pub const MAX_PARAMS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+  Add,
+  Multiply,
+  Input,
+  Output,
+  JumpIfTrue,
+  JumpIfFalse,
+  LessThan,
+  Equals,
+  RelativeBaseOffset,
+  Halt,
+}
+
+impl Opcode {
+  pub fn from_value(value: isize) -> Option<Opcode> {
+    match value {
+      1 => Some(Opcode::Add),
+      2 => Some(Opcode::Multiply),
+      3 => Some(Opcode::Input),
+      4 => Some(Opcode::Output),
+      5 => Some(Opcode::JumpIfTrue),
+      6 => Some(Opcode::JumpIfFalse),
+      7 => Some(Opcode::LessThan),
+      8 => Some(Opcode::Equals),
+      9 => Some(Opcode::RelativeBaseOffset),
+      99 => Some(Opcode::Halt),
+      _ => None,
+    }
+  }
+
+  pub fn value(self) -> isize {
+    match self {
+      Opcode::Add => 1,
+      Opcode::Multiply => 2,
+      Opcode::Input => 3,
+      Opcode::Output => 4,
+      Opcode::JumpIfTrue => 5,
+      Opcode::JumpIfFalse => 6,
+      Opcode::LessThan => 7,
+      Opcode::Equals => 8,
+      Opcode::RelativeBaseOffset => 9,
+      Opcode::Halt => 99,
+    }
+  }
+
+  // Index of the param that the instruction writes its result to, if any
+  pub fn write_param(self) -> Option<usize> {
+    match self {
+      Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => Some(2),
+      Opcode::Input => Some(0),
+      _ => None,
+    }
+  }
+
+  // Short names used by the disassembler and assembler
+  pub fn mnemonic(self) -> &'static str {
+    match self {
+      Opcode::Add => "add",
+      Opcode::Multiply => "mul",
+      Opcode::Input => "in",
+      Opcode::Output => "out",
+      Opcode::JumpIfTrue => "jt",
+      Opcode::JumpIfFalse => "jf",
+      Opcode::LessThan => "lt",
+      Opcode::Equals => "eq",
+      Opcode::RelativeBaseOffset => "arb",
+      Opcode::Halt => "hlt",
+    }
+  }
+
+  pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+    ALL_OPCODES.iter().copied().find(|opcode| opcode.mnemonic() == mnemonic)
+  }
+
+  pub fn num_params(self) -> usize {
+    match self {
+      Opcode::Add => 3,
+      Opcode::Multiply => 3,
+      Opcode::Input => 1,
+      Opcode::Output => 1,
+      Opcode::JumpIfTrue => 2,
+      Opcode::JumpIfFalse => 2,
+      Opcode::LessThan => 3,
+      Opcode::Equals => 3,
+      Opcode::RelativeBaseOffset => 1,
+      Opcode::Halt => 0,
+    }
+  }
+}
+
+pub const ALL_OPCODES: [Opcode; 10] = [
+  Opcode::Add,
+  Opcode::Multiply,
+  Opcode::Input,
+  Opcode::Output,
+  Opcode::JumpIfTrue,
+  Opcode::JumpIfFalse,
+  Opcode::LessThan,
+  Opcode::Equals,
+  Opcode::RelativeBaseOffset,
+  Opcode::Halt,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParameterMode {
+  Position,
+  Immediate,
+  Relative,
+}
+
+impl ParameterMode {
+  pub fn from_digit(digit: isize) -> Option<ParameterMode> {
+    match digit {
+      0 => Some(ParameterMode::Position),
+      1 => Some(ParameterMode::Immediate),
+      2 => Some(ParameterMode::Relative),
+      _ => None,
+    }
+  }
+
+  pub fn digit(self) -> isize {
+    match self {
+      ParameterMode::Position => 0,
+      ParameterMode::Immediate => 1,
+      ParameterMode::Relative => 2,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parameter {
+  pub value: isize,
+  pub mode: ParameterMode,
+}
+
+// Position mode is `[12]`, immediate mode is `#5`, relative mode is `rb+3`
+impl fmt::Display for Parameter {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.mode {
+      ParameterMode::Position => write!(f, "[{}]", self.value),
+      ParameterMode::Immediate => write!(f, "#{}", self.value),
+      ParameterMode::Relative if self.value < 0 => write!(f, "rb{}", self.value),
+      ParameterMode::Relative => write!(f, "rb+{}", self.value),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+  InvalidOpcode,
+  InvalidParameterMode(isize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+  pub opcode: Opcode,
+  // Fixed size so decoding doesn't allocate. Only the first
+  //   `opcode.num_params()` entries are meaningful.
+  params: [Parameter; MAX_PARAMS],
+}
+
+impl Instruction {
+  // Panics if `params` doesn't have exactly as many entries as the opcode needs
+  pub fn new(opcode: Opcode, params: &[Parameter]) -> Instruction {
+    assert_eq!(params.len(), opcode.num_params(), "Wrong number of params for {:?}", opcode);
+    let unused = Parameter { value: 0, mode: ParameterMode::Position };
+    let mut all_params = [unused; MAX_PARAMS];
+    all_params[..params.len()].copy_from_slice(params);
+    Instruction { opcode, params: all_params }
+  }
+
+  // `read_param(i)` should return the raw value of the i-th parameter, i.e.
+  //   the memory cell at `instruction_pointer + 1 + i`.
+  pub fn decode(
+    value: isize,
+    read_param: impl Fn(usize) -> isize,
+  ) -> Result<Instruction, DecodeError> {
+    if value < 0 {
+      return Err(DecodeError::InvalidOpcode);
+    }
+    let opcode = Opcode::from_value(value % 100).ok_or(DecodeError::InvalidOpcode)?;
+
+    let unused = Parameter { value: 0, mode: ParameterMode::Position };
+    let mut params = [unused; MAX_PARAMS];
+    let mut modes = value / 100;
+
+    for (i, param) in params.iter_mut().enumerate().take(opcode.num_params()) {
+      let mode_digit = modes % 10;
+      modes /= 10;
+      *param = Parameter {
+        value: read_param(i),
+        mode: ParameterMode::from_digit(mode_digit)
+          .ok_or(DecodeError::InvalidParameterMode(mode_digit))?,
+      };
+    }
+
+    Ok(Instruction { opcode, params })
+  }
+
+  pub fn params(&self) -> &[Parameter] {
+    &self.params[..self.opcode.num_params()]
+  }
+
+  // Number of memory cells taken up by the instruction, opcode included
+  pub fn size(&self) -> usize {
+    self.opcode.num_params() + 1
+  }
+
+  // The inverse of `decode`. Mode digits are only written for actual params,
+  //   so e.g. 30104 decodes fine but encodes back as 104.
+  pub fn encode(&self) -> Vec<isize> {
+    let mut opcode_value = self.opcode.value();
+    let mut place = 100;
+    for param in self.params() {
+      opcode_value += param.mode.digit() * place;
+      place *= 10;
+    }
+
+    let mut words = vec![opcode_value];
+    words.extend(self.params().iter().map(|param| param.value));
+    words
+  }
+}
+
+impl fmt::Display for Instruction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.opcode.mnemonic())?;
+    for (i, param) in self.params().iter().enumerate() {
+      write!(f, "{}{}", if i == 0 { " " } else { ", " }, param)?;
+    }
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn decode(words: &[isize]) -> Result<Instruction, DecodeError> {
+    Instruction::decode(words[0], |i| words[i + 1])
+  }
+
+  #[test]
+  fn decodes_opcode_and_modes() {
+    let instruction = decode(&[1002, 4, 3, 4]).unwrap();
+    assert_eq!(instruction.opcode, Opcode::Multiply);
+    assert_eq!(instruction.size(), 4);
+    assert_eq!(instruction.params(), &[
+      Parameter { value: 4, mode: ParameterMode::Position },
+      Parameter { value: 3, mode: ParameterMode::Immediate },
+      Parameter { value: 4, mode: ParameterMode::Position },
+    ]);
+
+    let instruction = decode(&[204, -1]).unwrap();
+    assert_eq!(instruction.opcode, Opcode::Output);
+    assert_eq!(instruction.params(), &[
+      Parameter { value: -1, mode: ParameterMode::Relative },
+    ]);
+
+    let instruction = decode(&[99]).unwrap();
+    assert_eq!(instruction.opcode, Opcode::Halt);
+    assert!(instruction.params().is_empty());
+  }
+
+  #[test]
+  fn ignores_mode_digits_past_the_last_param() {
+    // Output only has one param, so the 3 is never looked at
+    assert_eq!(decode(&[30104, 7]).unwrap().opcode, Opcode::Output);
+  }
+
+  #[test]
+  fn rejects_bad_values() {
+    assert_eq!(decode(&[42]), Err(DecodeError::InvalidOpcode));
+    assert_eq!(decode(&[-1]), Err(DecodeError::InvalidOpcode));
+    assert_eq!(decode(&[304, 0]), Err(DecodeError::InvalidParameterMode(3)));
+  }
+
+  #[test]
+  fn encode_and_display() {
+    let instruction = decode(&[21107, -4, 8, 3]).unwrap();
+    assert_eq!(instruction.encode(), vec![21107, -4, 8, 3]);
+    assert_eq!(instruction.to_string(), "lt #-4, #8, rb+3");
+
+    let instruction = Instruction::new(Opcode::Output, &[
+      Parameter { value: -1, mode: ParameterMode::Relative },
+    ]);
+    assert_eq!(instruction.encode(), vec![204, -1]);
+    assert_eq!(instruction.to_string(), "out rb-1");
+
+    assert_eq!(decode(&[30104, 7]).unwrap().encode(), vec![104, 7]);
+    assert_eq!(decode(&[99]).unwrap().to_string(), "hlt");
+  }
+}
@@ -0,0 +1,76 @@
+// ---------------------------------------------------
+// Shared Intcode computer, used by Days 2, 5, 7 and 9.
+// Originally written for Day 5, extended on Day 7 and Day 9.
+// ---------------------------------------------------
+
+use std::fs;
+
+mod assembler;
+mod bigint;
+mod compiler;
+mod computer;
+mod control_flow;
+mod debugger;
+mod decompiler;
+mod device;
+mod disassembler;
+mod error;
+mod fuzz;
+mod instruction;
+mod limits;
+mod memory;
+mod network;
+mod profile;
+mod search;
+mod snapshot;
+mod solver;
+mod symbolic;
+mod terminal;
+mod trace;
+mod word;
+
+pub use assembler::{assemble, format_program, AssembleError, AssembleErrorKind};
+pub use bigint::{BigInt, ParseBigIntError};
+pub use computer::{Program, ProgramStatus, Yield};
+pub use control_flow::{BasicBlock, ControlFlowGraph, Region, RegionKind, SelfModifyingWrite};
+pub use debugger::Debugger;
+pub use decompiler::{decompile, format_decompiled, Function, Statement};
+pub use device::{AsciiInput, AsciiOutput, InputDevice, OutputDevice, RecordedOutput, VecInput};
+pub use disassembler::{disassemble, format_listing, LineContents, ListingLine};
+pub use error::IntcodeError;
+pub use fuzz::{fuzz, Case, Divergence, Ending, Engine, FuzzOptions, Outcome, Subset};
+pub use instruction::{
+  DecodeError, Instruction, Opcode, Parameter, ParameterMode, ALL_OPCODES, MAX_PARAMS,
+};
+pub use limits::{Limit, Limits, DEFAULT_LOOP_ITERATIONS};
+pub use network::{Network, NetworkError, NetworkOutcome};
+pub use profile::Profile;
+pub use search::{PatchSearch, Target};
+pub use snapshot::{Snapshot, SnapshotError};
+pub use solver::Linear;
+pub use symbolic::{
+  Constraint, Expr, Relation, Solution, Symbol, SymbolicExecution, SymbolicReport,
+};
+pub use terminal::{SessionEnd, Terminal};
+pub use trace::{JsonLinesTrace, MemoryTrace, MemoryWrite, TraceEvent, TraceSink};
+pub use word::Word;
+
+pub fn parse_program(text: &str) -> Vec<isize> {
+  parse_words(text)
+}
+
+// `parse_program` for other word types, see word.rs
+pub fn parse_words<W: Word>(text: &str) -> Vec<W> {
+  text
+    .trim()
+    .split(',')
+    .map(|s| s.trim().parse().unwrap_or_else(|_| panic!("Invalid program value: {:?}", s)))
+    .collect()
+}
+
+pub fn parse_program_from_file(filename: &str) -> Vec<isize> {
+  parse_program(
+    &fs::read_to_string(filename)
+      .unwrap_or_else(|_| panic!("Problem reading file: {:?}", filename))
+  )
+}
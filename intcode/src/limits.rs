@@ -0,0 +1,194 @@
+// Limits on how far a program may run before `resume` hands control back:
+//
+//   program.set_limits(Limits { instructions: Some(10_000), ..Limits::default() });
+//   match program.resume()? {
+//     Yield::BudgetExhausted(Limit::Instructions) => {
+//       // Nothing is lost. Set the limits again for a fresh budget, and
+//       //   `resume` picks up where it left off.
+//     },
+//     ...
+//   }
+//
+// Running out of any budget stops the program right before the instruction
+//   that would go over it, and leaves everything as it was, so the same
+//   instruction runs once there's budget again.
+//
+// The loop limit is different. It's how `resume` notices a program that's
+//   stuck in a loop that can never yield, so going over it is an error
+//   (`IntcodeError::LoopLimitExceeded`), although even then the program can
+//   still be resumed.
+
+use std::fmt;
+use std::time::Instant;
+
+pub const DEFAULT_LOOP_ITERATIONS: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+  // Instructions the program may run, counted from when the limits are set
+  pub instructions: Option<usize>,
+  // Memory the program may use: every address it touches has to be below
+  //   this. Unlike `Program::set_memory_limit`, which is how much memory the
+  //   machine has, going over this isn't an error.
+  pub memory: Option<usize>,
+  // Outputs the program may produce, counted from when the limits are set
+  pub outputs: Option<usize>,
+  // Only checked every so often, so the program may run a little past it
+  pub deadline: Option<Instant>,
+  // Instructions a single `resume` may run without producing output or
+  //   using a device. `run` keeps counting across the outputs it collects.
+  pub loop_iterations: Option<usize>,
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Limits {
+      instructions: None,
+      memory: None,
+      outputs: None,
+      deadline: None,
+      loop_iterations: Some(DEFAULT_LOOP_ITERATIONS),
+    }
+  }
+}
+
+impl Limits {
+  pub fn unlimited() -> Self {
+    Limits { loop_iterations: None, ..Limits::default() }
+  }
+}
+
+// Which budget ran out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+  Instructions,
+  Memory,
+  Outputs,
+  Deadline,
+}
+
+impl fmt::Display for Limit {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match self {
+      Limit::Instructions => "instruction",
+      Limit::Memory => "memory",
+      Limit::Outputs => "output",
+      Limit::Deadline => "time",
+    };
+    write!(f, "{} budget", name)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{IntcodeError, Program, Yield};
+  use std::time::Duration;
+
+  // Counts down from 3, outputting each number
+  const COUNTDOWN: [isize; 12] = [4,11, 1001,11,-1,11, 1005,11,0, 99, 0, 3];
+
+  // Resumes with a fresh budget every time one runs out. Returns the
+  //   outputs, and how many times it ran out.
+  fn run_with_budget(program: &mut Program, limits: Limits) -> (Vec<isize>, Vec<Limit>) {
+    let mut outputs = Vec::new();
+    let mut exhausted = Vec::new();
+    program.set_limits(limits);
+    loop {
+      match program.resume().unwrap() {
+        Yield::Output(value) => outputs.push(value),
+        Yield::BudgetExhausted(limit) => {
+          exhausted.push(limit);
+          program.set_limits(limits);
+        },
+        _ => return (outputs, exhausted),
+      }
+    }
+  }
+
+  #[test]
+  fn instruction_budget_can_be_refilled() {
+    for &compiled in [false, true].iter() {
+      let mut program = Program::new(&COUNTDOWN);
+      if compiled {
+        program.enable_compiler();
+      }
+      // One trip around the loop
+      let limits = Limits { instructions: Some(3), ..Limits::default() };
+
+      program.set_limits(limits);
+      assert_eq!(program.resume(), Ok(Yield::Output(3)));
+      assert_eq!(program.resume(), Ok(Yield::BudgetExhausted(Limit::Instructions)));
+      assert_eq!(program.instruction_count(), 3);
+      assert_eq!(program.instruction_pointer(), 0);
+      // Still out of budget until the limits are set again
+      assert_eq!(program.resume(), Ok(Yield::BudgetExhausted(Limit::Instructions)));
+
+      let (outputs, exhausted) = run_with_budget(&mut program, limits);
+      assert_eq!(outputs, vec![2, 1]);
+      assert_eq!(exhausted, vec![Limit::Instructions, Limit::Instructions]);
+      assert_eq!(program.instruction_count(), 10);
+    }
+  }
+
+  #[test]
+  fn output_budget_stops_before_the_output() {
+    let mut program = Program::new(&COUNTDOWN);
+    program.set_limits(Limits { outputs: Some(2), ..Limits::default() });
+    assert_eq!(program.resume(), Ok(Yield::Output(3)));
+    assert_eq!(program.resume(), Ok(Yield::Output(2)));
+    assert_eq!(program.resume(), Ok(Yield::BudgetExhausted(Limit::Outputs)));
+    assert_eq!(program.instruction_pointer(), 0);
+
+    let limits = Limits { outputs: Some(1), ..Limits::default() };
+    assert_eq!(run_with_budget(&mut program, limits), (vec![1], vec![]));
+  }
+
+  #[test]
+  fn memory_budget_is_not_the_memory_limit() {
+    let far_write = [1101,5,6,1000, 4,1000, 99];
+    for &compiled in [false, true].iter() {
+      let mut program = Program::new(&far_write);
+      if compiled {
+        program.enable_compiler();
+      }
+      program.set_limits(Limits { memory: Some(100), ..Limits::default() });
+      assert_eq!(program.resume(), Ok(Yield::BudgetExhausted(Limit::Memory)));
+      assert_eq!(program.instruction_count(), 0);
+
+      program.set_limits(Limits { memory: Some(1001), ..Limits::default() });
+      assert_eq!(program.run(&[]), Ok(vec![11]));
+    }
+
+    // The memory limit is still an error, whatever the budget
+    let mut program = Program::new(&far_write);
+    program.set_memory_limit(Some(100));
+    program.set_limits(Limits { memory: Some(50), ..Limits::default() });
+    assert!(matches!(program.resume(), Err(IntcodeError::AddressOutOfRange { address: 1000, .. })));
+  }
+
+  #[test]
+  fn deadline_stops_endless_loops() {
+    let mut program = Program::new(&[1105,1,0]);
+    let deadline = Instant::now() + Duration::from_millis(20);
+    program.set_limits(Limits { deadline: Some(deadline), ..Limits::unlimited() });
+    assert_eq!(program.resume(), Ok(Yield::BudgetExhausted(Limit::Deadline)));
+    assert!(Instant::now() >= deadline);
+    assert_eq!(
+      program.run(&[]),
+      Err(IntcodeError::BudgetExhausted { instruction_pointer: 0, limit: Limit::Deadline }),
+    );
+  }
+
+  #[test]
+  fn loop_limit_is_configurable() {
+    let mut program = Program::new(&[1105,1,0]);
+    program.set_limits(Limits { loop_iterations: Some(10), ..Limits::default() });
+    assert_eq!(
+      program.resume(),
+      Err(IntcodeError::LoopLimitExceeded { instruction_pointer: 0, iterations: 10 }),
+    );
+    assert_eq!(program.instruction_count(), 10);
+  }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use crate::word::Word;
+
+// Addresses below this live in a flat Vec that grows on demand. Anything
+//   higher goes into a HashMap, so a single write to e.g. address 10^9
+//   doesn't allocate gigabytes.
+const DENSE_MEMORY_LIMIT: usize = 1 << 20;
+
+// Day 9: "The computer's available memory should be much larger than the
+//   initial program. Memory beyond the initial program starts with the
+//   value 0 and can be read or written like any other memory."
+#[derive(Debug, Clone)]
+pub struct Memory<W: Word = isize> {
+  dense: Vec<W>,
+  sparse: HashMap<usize, W>,
+  // Addresses at or above this are off limits. `None` means unbounded.
+  limit: Option<usize>,
+}
+
+impl<W: Word> Memory<W> {
+  pub fn new(values: &[W]) -> Self {
+    let dense_len = values.len().min(DENSE_MEMORY_LIMIT);
+    let mut memory = Memory {
+      dense: values[..dense_len].to_vec(),
+      sparse: HashMap::new(),
+      limit: None,
+    };
+    for (address, value) in values.iter().enumerate().skip(dense_len) {
+      memory.write(address, value.clone());
+    }
+    memory
+  }
+
+  pub fn limit(&self) -> Option<usize> {
+    self.limit
+  }
+
+  pub fn set_limit(&mut self, limit: Option<usize>) {
+    self.limit = limit;
+  }
+
+  // Everything below the sparse region, up to the last value written there.
+  //   Trailing zeros may or may not be included.
+  pub fn dense_values(&self) -> &[W] {
+    &self.dense
+  }
+
+  // The nonzero values at high addresses, sorted by address
+  pub fn sparse_values(&self) -> Vec<(usize, W)> {
+    let mut values: Vec<(usize, W)> = self.sparse.iter().map(|(&a, v)| (a, v.clone())).collect();
+    values.sort_unstable_by_key(|&(address, _)| address);
+    values
+  }
+
+  pub fn is_addressable(&self, address: usize) -> bool {
+    self.limit.is_none_or(|limit| address < limit)
+  }
+
+  pub fn read(&self, address: usize) -> W {
+    let value = if address < DENSE_MEMORY_LIMIT {
+      self.dense.get(address)
+    } else {
+      self.sparse.get(&address)
+    };
+    value.cloned().unwrap_or_else(W::zero)
+  }
+
+  pub fn write(&mut self, address: usize, value: W) {
+    if address < DENSE_MEMORY_LIMIT {
+      if address >= self.dense.len() {
+        if value.is_zero() {
+          return;
+        }
+        self.dense.resize(address + 1, W::zero());
+      }
+      self.dense[address] = value;
+    } else if value.is_zero() {
+      self.sparse.remove(&address);
+    } else {
+      self.sparse.insert(address, value);
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_zero_by_default() {
+    let memory: Memory = Memory::new(&[1, 2, 3]);
+    assert_eq!(memory.read(2), 3);
+    assert_eq!(memory.read(3), 0);
+    assert_eq!(memory.read(DENSE_MEMORY_LIMIT * 10), 0);
+  }
+
+  #[test]
+  fn far_writes_stay_sparse() {
+    let mut memory: Memory = Memory::new(&[1, 2, 3]);
+    memory.write(1_000_000_000, 42);
+    assert_eq!(memory.read(1_000_000_000), 42);
+    assert_eq!(memory.dense.len(), 3);
+    assert_eq!(memory.sparse.len(), 1);
+
+    memory.write(1_000_000_000, 0);
+    assert!(memory.sparse.is_empty());
+  }
+
+  #[test]
+  fn limit_bounds_addresses() {
+    let mut memory: Memory = Memory::new(&[]);
+    assert!(memory.is_addressable(usize::MAX));
+    memory.set_limit(Some(100));
+    assert!(memory.is_addressable(99));
+    assert!(!memory.is_addressable(100));
+  }
+}
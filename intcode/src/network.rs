@@ -0,0 +1,377 @@
+// Runs a group of Intcode machines wired together by named channels. Each
+//   machine reads from one channel and writes every output to any number of
+//   channels, so pipelines, rings (Day 7's feedback loop) and broadcasts are
+//   all just different wiring:
+//
+//   let mut network = Network::new();
+//   network.add_machine("A", Program::new(&code), "a", &["b"])?;
+//   network.add_machine("B", Program::new(&code), "b", &["a", "log"])?;
+//   network.send("a", 0);
+//   network.run()?;
+//
+// Channels spring into existence the first time they're mentioned. One that
+//   no machine reads from collects values for the caller to `take`.
+//
+// `run` uses a cooperative round-robin scheduler on the current thread, and
+//   `run_threaded` gives each machine its own thread. Either way the network
+//   runs until every machine has halted, or until every machine that's still
+//   running is stuck waiting on an empty channel (a deadlock).
+
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use crate::computer::{Program, Yield};
+use crate::error::IntcodeError;
+use crate::limits::Limit;
+
+#[derive(Debug, PartialEq)]
+pub enum NetworkOutcome {
+  Halted,
+  // Names of the machines left waiting for input
+  Deadlocked(Vec<String>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum NetworkError {
+  DuplicateMachine(String),
+  // Two machines reading from the same channel would race for its values
+  SharedInput(String),
+  MachineFailed { machine: String, error: IntcodeError },
+}
+
+impl fmt::Display for NetworkError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      NetworkError::DuplicateMachine(name) => write!(f, "machine {:?} already exists", name),
+      NetworkError::SharedInput(channel) => write!(
+        f, "channel {:?} already has a machine reading from it", channel,
+      ),
+      NetworkError::MachineFailed { machine, error } => write!(f, "machine {:?}: {}", machine, error),
+    }
+  }
+}
+
+impl Error for NetworkError {}
+
+struct Machine {
+  name: String,
+  program: Program,
+  input: String,
+  outputs: Vec<String>,
+}
+
+impl Machine {
+  fn failed(&self, error: IntcodeError) -> NetworkError {
+    NetworkError::MachineFailed { machine: self.name.clone(), error }
+  }
+
+  // Budgets belong to whoever set them, so one running out stops the network
+  fn exhausted(&self, limit: Limit) -> NetworkError {
+    self.failed(IntcodeError::BudgetExhausted {
+      instruction_pointer: self.program.instruction_pointer(),
+      limit,
+    })
+  }
+}
+
+#[derive(Default)]
+pub struct Network {
+  machines: Vec<Machine>,
+  channels: BTreeMap<String, VecDeque<isize>>,
+}
+
+impl Network {
+  pub fn new() -> Self {
+    Network::default()
+  }
+
+  pub fn add_machine(
+    &mut self,
+    name: &str,
+    program: Program,
+    input: &str,
+    outputs: &[&str],
+  ) -> Result<(), NetworkError> {
+    if self.machines.iter().any(|machine| machine.name == name) {
+      return Err(NetworkError::DuplicateMachine(name.to_string()));
+    }
+    if self.machines.iter().any(|machine| machine.input == input) {
+      return Err(NetworkError::SharedInput(input.to_string()));
+    }
+
+    for channel in outputs.iter().chain(Some(&input)) {
+      self.channels.entry(channel.to_string()).or_default();
+    }
+    self.machines.push(Machine {
+      name: name.to_string(),
+      program,
+      input: input.to_string(),
+      outputs: outputs.iter().map(|output| output.to_string()).collect(),
+    });
+    Ok(())
+  }
+
+  pub fn program(&self, name: &str) -> Option<&Program> {
+    self.machines.iter().find(|machine| machine.name == name).map(|machine| &machine.program)
+  }
+
+  pub fn send(&mut self, channel: &str, value: isize) {
+    self.channels.entry(channel.to_string()).or_default().push_back(value);
+  }
+
+  // Removes and returns everything waiting in a channel
+  pub fn take(&mut self, channel: &str) -> Vec<isize> {
+    self.channels.get_mut(channel).map_or_else(Vec::new, |queue| queue.drain(..).collect())
+  }
+
+  pub fn run(&mut self) -> Result<NetworkOutcome, NetworkError> {
+    loop {
+      // Progress means a value moved somewhere, or a machine halted
+      let mut made_progress = false;
+
+      for machine in self.machines.iter_mut() {
+        if machine.program.is_halted() {
+          continue;
+        }
+
+        let queue = self.channels.get_mut(&machine.input).unwrap();
+        made_progress |= !queue.is_empty();
+        for value in queue.drain(..) {
+          machine.program.push_input(value);
+        }
+
+        loop {
+          match machine.program.resume().map_err(|error| machine.failed(error))? {
+            Yield::Output(value) => {
+              made_progress = true;
+              for output in machine.outputs.iter() {
+                self.channels.get_mut(output).unwrap().push_back(value);
+              }
+            },
+            Yield::NeedsInput => break,
+            Yield::Halted => {
+              made_progress = true;
+              break;
+            },
+            Yield::BudgetExhausted(limit) => return Err(machine.exhausted(limit)),
+          }
+        }
+      }
+
+      if !made_progress {
+        return Ok(self.outcome());
+      }
+    }
+  }
+
+  pub fn run_threaded(&mut self) -> Result<NetworkOutcome, NetworkError> {
+    let shared = Mutex::new(SharedState {
+      channels: std::mem::take(&mut self.channels),
+      inputs: self.machines.iter().map(|machine| machine.input.clone()).collect(),
+      waiting: vec![false; self.machines.len()],
+      halted: self.machines.iter().map(|machine| machine.program.is_halted()).collect(),
+      stopped: false,
+    });
+    let wakeup = Condvar::new();
+
+    let results: Vec<Result<(), NetworkError>> = thread::scope(|scope| {
+      let handles: Vec<_> = self.machines
+        .iter_mut()
+        .enumerate()
+        .map(|(index, machine)| {
+          let shared = &shared;
+          let wakeup = &wakeup;
+          scope.spawn(move || run_on_thread(index, machine, shared, wakeup))
+        })
+        .collect();
+      handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+
+    self.channels = shared.into_inner().unwrap().channels;
+    for result in results {
+      result?;
+    }
+    Ok(self.outcome())
+  }
+
+  fn outcome(&self) -> NetworkOutcome {
+    let waiting: Vec<String> = self.machines
+      .iter()
+      .filter(|machine| !machine.program.is_halted())
+      .map(|machine| machine.name.clone())
+      .collect();
+    if waiting.is_empty() {
+      NetworkOutcome::Halted
+    } else {
+      NetworkOutcome::Deadlocked(waiting)
+    }
+  }
+}
+
+// Everything the machine threads share, behind one lock
+struct SharedState {
+  channels: BTreeMap<String, VecDeque<isize>>,
+  // Input channel of each machine, by index
+  inputs: Vec<String>,
+  waiting: Vec<bool>,
+  halted: Vec<bool>,
+  // Set on deadlock or error, so that every thread gives up
+  stopped: bool,
+}
+
+impl SharedState {
+  fn is_deadlocked(&self) -> bool {
+    (0..self.inputs.len()).all(|index| {
+      self.halted[index] || (self.waiting[index] && self.channels[&self.inputs[index]].is_empty())
+    })
+  }
+}
+
+fn run_on_thread(
+  index: usize,
+  machine: &mut Machine,
+  shared: &Mutex<SharedState>,
+  wakeup: &Condvar,
+) -> Result<(), NetworkError> {
+  loop {
+    let result = machine.program.resume();
+    let mut state = shared.lock().unwrap();
+
+    match result {
+      Err(error) => {
+        state.stopped = true;
+        wakeup.notify_all();
+        return Err(machine.failed(error));
+      },
+      Ok(Yield::BudgetExhausted(limit)) => {
+        state.stopped = true;
+        wakeup.notify_all();
+        return Err(machine.exhausted(limit));
+      },
+      Ok(Yield::Output(value)) => {
+        for output in machine.outputs.iter() {
+          state.channels.get_mut(output).unwrap().push_back(value);
+        }
+        wakeup.notify_all();
+      },
+      Ok(Yield::Halted) => {
+        state.halted[index] = true;
+        wakeup.notify_all();
+        return Ok(());
+      },
+      Ok(Yield::NeedsInput) => {
+        state.waiting[index] = true;
+        while state.channels[&machine.input].is_empty() {
+          if !state.stopped && state.is_deadlocked() {
+            state.stopped = true;
+            wakeup.notify_all();
+          }
+          if state.stopped {
+            return Ok(());
+          }
+          state = wakeup.wait(state).unwrap();
+        }
+        state.waiting[index] = false;
+
+        let queue = state.channels.get_mut(&machine.input).unwrap();
+        for value in queue.drain(..) {
+          machine.program.push_input(value);
+        }
+      },
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Day 7 feedback loop example, max thruster signal 139629729
+  const FEEDBACK_EXAMPLE: [isize; 29] = [
+    3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,
+    27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5,
+  ];
+  // Adds 1 to every input, forever
+  const ADD_ONE: [isize; 12] = [3,11,1001,11,1,11,4,11,1105,1,0,0];
+
+  fn amplifier_ring() -> Network {
+    let names = ["A", "B", "C", "D", "E"];
+    let phase_settings = [9, 8, 7, 6, 5];
+    let mut network = Network::new();
+    for index in 0..names.len() {
+      let next = names[(index + 1) % names.len()];
+      network.add_machine(names[index], Program::new(&FEEDBACK_EXAMPLE), names[index], &[next])
+        .unwrap();
+      network.send(names[index], phase_settings[index]);
+    }
+    network.send("A", 0);
+    network
+  }
+
+  #[test]
+  fn runs_a_ring_cooperatively() {
+    let mut network = amplifier_ring();
+    assert_eq!(network.run(), Ok(NetworkOutcome::Halted));
+    assert_eq!(network.take("A"), vec![139629729]);
+  }
+
+  #[test]
+  fn runs_a_ring_on_threads() {
+    let mut network = amplifier_ring();
+    assert_eq!(network.run_threaded(), Ok(NetworkOutcome::Halted));
+    assert_eq!(network.take("A"), vec![139629729]);
+  }
+
+  #[test]
+  fn pipelines_and_broadcasts() {
+    for &threaded in [false, true].iter() {
+      let mut network = Network::new();
+      network.add_machine("first", Program::new(&ADD_ONE), "in", &["left", "right"]).unwrap();
+      network.add_machine("left", Program::new(&ADD_ONE), "left", &["out"]).unwrap();
+      network.add_machine("right", Program::new(&ADD_ONE), "right", &["out", "tap"]).unwrap();
+      network.send("in", 1);
+      network.send("in", 10);
+
+      let outcome = if threaded { network.run_threaded() } else { network.run() };
+      let names = vec!["first".to_string(), "left".to_string(), "right".to_string()];
+      assert_eq!(outcome, Ok(NetworkOutcome::Deadlocked(names)));
+
+      let mut out = network.take("out");
+      out.sort();
+      assert_eq!(out, vec![3, 3, 12, 12]);
+      assert_eq!(network.take("tap"), vec![3, 12]);
+      assert_eq!(network.take("nonexistent"), vec![]);
+    }
+  }
+
+  #[test]
+  fn reports_wiring_and_machine_errors() {
+    let mut network = Network::new();
+    network.add_machine("A", Program::new(&[99]), "a", &[]).unwrap();
+    assert_eq!(
+      network.add_machine("A", Program::new(&[99]), "b", &[]),
+      Err(NetworkError::DuplicateMachine("A".to_string())),
+    );
+    assert_eq!(
+      network.add_machine("B", Program::new(&[99]), "a", &[]),
+      Err(NetworkError::SharedInput("a".to_string())),
+    );
+
+    for &threaded in [false, true].iter() {
+      let mut network = Network::new();
+      network.add_machine("bad", Program::new(&[3,0,42]), "a", &[]).unwrap();
+      network.add_machine("waiting", Program::new(&ADD_ONE), "b", &[]).unwrap();
+      network.send("a", 1);
+
+      let outcome = if threaded { network.run_threaded() } else { network.run() };
+      assert_eq!(outcome, Err(NetworkError::MachineFailed {
+        machine: "bad".to_string(),
+        error: IntcodeError::InvalidOpcode { instruction_pointer: 2, opcode: 42 },
+      }));
+    }
+  }
+}
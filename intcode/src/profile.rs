@@ -0,0 +1,316 @@
+// Execution profile of a program, for finding out where the time goes (or
+//   why a program trips the loop limit). Turn it on with
+//   `Program::enable_profiler`, run, then look at `Program::profile`:
+//
+//   instructions: 371206
+//   memory high-water mark: 1077
+//
+//   opcodes:
+//     add       92797  25.0%
+//     ...
+//
+// It counts executions per address and per opcode, how often each backward
+//   jump target is jumped to (the loops), and the highest address the program
+//   touched.
+//
+// It also keeps track of call frames, so the profile can be written out as
+//   folded stacks for flamegraph.pl / inferno:
+//
+//   main;f922;f922;f922 1460
+//
+// A frame starts when an `arb` that grows the relative base comes right after
+//   a jump (a function prologue, named after its address like in the
+//   decompiler), and ends when the relative base drops back below where it
+//   was.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+
+use crate::instruction::{Instruction, Opcode, ParameterMode, ALL_OPCODES};
+
+// Number of entries in the report's top lists
+const REPORT_LENGTH: usize = 10;
+
+#[derive(Debug, Clone)]
+struct Frame {
+  parent: usize,
+  // Relative base when the frame started. Dropping back to it (or below)
+  //   ends the frame.
+  base: isize,
+  children: BTreeMap<usize, usize>,
+  instructions: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+  instructions: u64,
+  // A map, since code can run at any address (see memory.rs)
+  address_counts: BTreeMap<usize, u64>,
+  opcode_counts: [u64; ALL_OPCODES.len()],
+  // Backward jump target -> number of times it was jumped to
+  loop_counts: BTreeMap<usize, u64>,
+  // One past the highest address touched
+  memory_high_water: usize,
+  // Every call stack seen, as a tree. The root is `main`.
+  frames: Vec<Frame>,
+  current_frame: usize,
+  just_jumped: bool,
+}
+
+impl Default for Profile {
+  fn default() -> Self {
+    Profile {
+      instructions: 0,
+      address_counts: BTreeMap::new(),
+      opcode_counts: [0; ALL_OPCODES.len()],
+      loop_counts: BTreeMap::new(),
+      memory_high_water: 0,
+      frames: vec![Frame {
+        parent: 0,
+        base: isize::MIN,
+        children: BTreeMap::new(),
+        instructions: 0,
+      }],
+      current_frame: 0,
+      just_jumped: false,
+    }
+  }
+}
+
+impl Profile {
+  pub fn new() -> Self {
+    Profile::default()
+  }
+
+  // Records one executed instruction, given the instruction pointer and
+  //   relative base from before and after it ran
+  pub(crate) fn record(
+    &mut self,
+    instruction_pointer: usize,
+    instruction: &Instruction,
+    relative_base: isize,
+    next_instruction_pointer: usize,
+    next_relative_base: isize,
+  ) {
+    self.instructions += 1;
+    *self.address_counts.entry(instruction_pointer).or_insert(0) += 1;
+    let opcode_index = ALL_OPCODES.iter().position(|&opcode| opcode == instruction.opcode).unwrap();
+    self.opcode_counts[opcode_index] += 1;
+    self.frames[self.current_frame].instructions += 1;
+
+    let mut high_water = instruction_pointer + instruction.size();
+    for param in instruction.params() {
+      let address = match param.mode {
+        ParameterMode::Position => param.value,
+        // The interpreter fails on an overflowing address, so there's nothing
+        //   to count
+        ParameterMode::Relative => match relative_base.checked_add(param.value) {
+          Some(address) => address,
+          None => continue,
+        },
+        ParameterMode::Immediate => continue,
+      };
+      if address >= 0 {
+        high_water = high_water.max(address as usize + 1);
+      }
+    }
+    self.memory_high_water = self.memory_high_water.max(high_water);
+
+    let is_jump = matches!(instruction.opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse);
+    let jumped = is_jump && next_instruction_pointer != instruction_pointer + instruction.size();
+    if jumped && next_instruction_pointer <= instruction_pointer {
+      *self.loop_counts.entry(next_instruction_pointer).or_insert(0) += 1;
+    }
+
+    if next_relative_base > relative_base && self.just_jumped {
+      self.enter_frame(instruction_pointer, relative_base);
+    } else if next_relative_base < relative_base {
+      while self.current_frame != 0 && self.frames[self.current_frame].base >= next_relative_base {
+        self.current_frame = self.frames[self.current_frame].parent;
+      }
+    }
+    self.just_jumped = jumped;
+  }
+
+  fn enter_frame(&mut self, entry: usize, base: isize) {
+    let parent = self.current_frame;
+    let next_index = self.frames.len();
+    let index = *self.frames[parent].children.entry(entry).or_insert(next_index);
+    if index == next_index {
+      self.frames.push(Frame { parent, base, children: BTreeMap::new(), instructions: 0 });
+    }
+    // Same function, but maybe not the same relative base as last time
+    self.frames[index].base = base;
+    self.current_frame = index;
+  }
+
+  // Total number of instructions executed while profiling
+  pub fn instructions(&self) -> u64 {
+    self.instructions
+  }
+
+  pub fn address_count(&self, address: usize) -> u64 {
+    self.address_counts.get(&address).copied().unwrap_or(0)
+  }
+
+  pub fn opcode_count(&self, opcode: Opcode) -> u64 {
+    let index = ALL_OPCODES.iter().position(|&other| other == opcode).unwrap();
+    self.opcode_counts[index]
+  }
+
+  pub fn memory_high_water(&self) -> usize {
+    self.memory_high_water
+  }
+
+  // The `count` most executed addresses, most executed first
+  pub fn hottest_addresses(&self, count: usize) -> Vec<(usize, u64)> {
+    top(self.address_counts.iter().map(|(&address, &n)| (address, n)), count)
+  }
+
+  // The `count` backward jump targets jumped to the most, i.e. the loop
+  //   heads doing the most iterations
+  pub fn hottest_loops(&self, count: usize) -> Vec<(usize, u64)> {
+    top(self.loop_counts.iter().map(|(&address, &n)| (address, n)), count)
+  }
+
+  pub fn report(&self) -> String {
+    let percent = |n: u64| 100.0 * n as f64 / self.instructions.max(1) as f64;
+    let mut report = String::new();
+    writeln!(report, "instructions: {}", self.instructions).unwrap();
+    writeln!(report, "memory high-water mark: {}", self.memory_high_water).unwrap();
+
+    report.push_str("\nopcodes:\n");
+    let opcodes = ALL_OPCODES.iter().zip(self.opcode_counts.iter()).map(|(&opcode, &n)| (opcode, n));
+    for (opcode, n) in top(opcodes.filter(|&(_, n)| n > 0), ALL_OPCODES.len()) {
+      writeln!(report, "  {:<4} {:>10} {:>5.1}%", opcode.mnemonic(), n, percent(n)).unwrap();
+    }
+
+    report.push_str("\nhottest addresses:\n");
+    for (address, n) in self.hottest_addresses(REPORT_LENGTH) {
+      writeln!(report, "  {:>6}: {:>10} {:>5.1}%", address, n, percent(n)).unwrap();
+    }
+
+    report.push_str("\nhottest loops (backward jump targets):\n");
+    for (address, n) in self.hottest_loops(REPORT_LENGTH) {
+      writeln!(report, "  {:>6}: {:>10} iterations", address, n).unwrap();
+    }
+    report
+  }
+
+  // One line per call stack: frames separated by `;`, then the number of
+  //   instructions executed in the innermost frame
+  pub fn folded_stacks(&self) -> String {
+    let mut folded = String::new();
+    self.fold(0, "main".to_string(), &mut folded);
+    folded
+  }
+
+  pub fn save_folded_stacks(&self, filename: &str) -> io::Result<()> {
+    fs::write(filename, self.folded_stacks())
+  }
+
+  fn fold(&self, index: usize, stack: String, folded: &mut String) {
+    let frame = &self.frames[index];
+    if frame.instructions > 0 {
+      writeln!(folded, "{} {}", stack, frame.instructions).unwrap();
+    }
+    for (&entry, &child) in frame.children.iter() {
+      self.fold(child, format!("{};f{}", stack, entry), folded);
+    }
+  }
+}
+
+// Sorted by count, most first. Ties stay in the order they came in.
+fn top<K>(counts: impl Iterator<Item = (K, u64)>, count: usize) -> Vec<(K, u64)> {
+  let mut counts: Vec<(K, u64)> = counts.collect();
+  counts.sort_by_key(|&(_, n)| Reverse(n));
+  counts.truncate(count);
+  counts
+}
+
+
+#[cfg(test)]
+mod tests {
+  use crate::{parse_program_from_file, IntcodeError, Opcode, Program};
+
+  #[test]
+  fn counts_addresses_opcodes_and_loops() {
+    // Counts down from 3, outputting each number
+    let countdown = vec![4,11, 1001,11,-1,11, 1005,11,0, 99, 0, 3];
+    let mut program = Program::new(&countdown);
+    program.enable_profiler();
+    assert_eq!(program.run(&[]).unwrap(), vec![3, 2, 1]);
+
+    let profile = program.profile().unwrap();
+    assert_eq!(profile.instructions(), 10);
+    assert_eq!(profile.address_count(0), 3);
+    assert_eq!(profile.address_count(9), 1);
+    assert_eq!(profile.opcode_count(Opcode::JumpIfTrue), 3);
+    assert_eq!(profile.hottest_loops(10), vec![(0, 2)]);
+    assert_eq!(profile.hottest_addresses(2), vec![(0, 3), (2, 3)]);
+    assert_eq!(profile.memory_high_water(), 12);
+    assert_eq!(profile.folded_stacks(), "main 10\n");
+
+    let report = profile.report();
+    assert!(report.starts_with("instructions: 10\nmemory high-water mark: 12\n"));
+    assert!(report.contains("\n  jt            3  30.0%\n"));
+    assert!(report.contains("\n       0:          2 iterations\n"));
+  }
+
+  #[test]
+  fn shows_what_trips_the_loop_limit() {
+    let mut program = Program::new(&[1101,1,1,7, 1105,1,4, 0]);
+    program.enable_profiler();
+    assert!(matches!(program.run(&[]), Err(IntcodeError::LoopLimitExceeded { .. })));
+
+    let profile = program.take_profile().unwrap();
+    assert_eq!(profile.hottest_loops(1), vec![(4, profile.instructions() - 1)]);
+    assert!(program.profile().is_none());
+  }
+
+  #[test]
+  fn code_can_run_anywhere() {
+    let far_address = 1_000_000_000;
+    let mut program = Program::new(&[1101,0,99,far_address, 1105,1,far_address]);
+    program.enable_profiler();
+    assert_eq!(program.run(&[]), Ok(vec![]));
+    let profile = program.profile().unwrap();
+    assert_eq!(profile.address_count(far_address as usize), 1);
+    assert_eq!(profile.memory_high_water(), far_address as usize + 1);
+
+    // The jump isn't taken, so its overflowing relative address never matters
+    let mut program = Program::new(&[109,1, 2105,0,isize::MAX, 99]);
+    program.enable_profiler();
+    assert_eq!(program.run(&[]), Ok(vec![]));
+    assert_eq!(program.profile().unwrap().instructions(), 3);
+  }
+
+  #[test]
+  fn folds_boost_call_stacks() {
+    let boost = parse_program_from_file(
+      concat!(env!("CARGO_MANIFEST_DIR"), "/../day-09/puzzle-input.txt"),
+    );
+    let mut program = Program::new(&boost);
+    program.enable_compiler();
+    program.enable_profiler();
+    program.run(&[2]).unwrap();
+
+    let profile = program.profile().unwrap();
+    assert_eq!(profile.instructions() as usize, program.instruction_count());
+    let folded = profile.folded_stacks();
+    let lines: Vec<&str> = folded.lines().collect();
+    assert!(lines[0].starts_with("main "));
+    assert!(lines[1].starts_with("main;f922 "));
+    assert!(lines[2].starts_with("main;f922;f922 "));
+
+    // Every instruction is in exactly one stack
+    let total: u64 = lines
+      .iter()
+      .map(|line| line.rsplit(' ').next().unwrap().parse::<u64>().unwrap())
+      .sum();
+    assert_eq!(total, profile.instructions());
+  }
+}
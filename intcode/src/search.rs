@@ -0,0 +1,263 @@
+// Searches for memory patches that make a program produce some value, like
+//   the noun and verb from Day 2 part 2:
+//
+//   let search = PatchSearch::new(Target::Memory { address: 0, value: 19690720 })
+//     .patch(1, 0..100)
+//     .patch(2, 0..100);
+//   let patch = search.find(&Program::new(&input)).unwrap();  // [(1, noun), (2, verb)]
+//
+// Every combination of values is tried, the first patched address varying
+//   slowest, split up between a few threads. Each candidate is a clone of the
+//   one pristine program with its patch written on top, so nothing is parsed
+//   twice, and it runs with the search's limits. A candidate that fails (a
+//   patch can easily turn the program into garbage) just doesn't match.
+//
+// `find` returns the first match in that order, however the threads happen
+//   to finish, so it always gives the same answer.
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::computer::{Program, Yield};
+use crate::limits::Limits;
+
+// What a search is looking for. Symbolic execution (symbolic.rs) uses these
+//   too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+  // The value at an address once the program halts
+  Memory { address: usize, value: isize },
+  // The `index`th output (counting from 0), whatever happens after it
+  Output { index: usize, value: isize },
+  // Halting on the instruction at `address`
+  Halt { address: usize },
+}
+
+impl Target {
+  // Runs the program with the inputs, and checks whether it gets there.
+  //   Failing, or stopping for more input, means it doesn't.
+  pub(crate) fn reached(self, program: &mut Program, inputs: &[isize]) -> bool {
+    for &input in inputs {
+      program.push_input(input);
+    }
+    let mut outputs = 0;
+    loop {
+      match program.resume() {
+        Ok(Yield::Output(output)) => {
+          if let Target::Output { index, value } = self {
+            if outputs == index {
+              return output == value;
+            }
+          }
+          outputs += 1;
+        },
+        Ok(Yield::Halted) => {
+          return match self {
+            Target::Memory { address, value } => program.read(address) == value,
+            Target::Halt { address } => program.instruction_pointer() == address,
+            Target::Output { .. } => false,
+          };
+        },
+        Ok(Yield::NeedsInput) | Ok(Yield::BudgetExhausted(_)) | Err(_) => return false,
+      }
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct PatchSearch {
+  patches: Vec<(usize, Range<isize>)>,
+  target: Target,
+  inputs: Vec<isize>,
+  limits: Limits,
+  threads: usize,
+}
+
+impl PatchSearch {
+  pub fn new(target: Target) -> Self {
+    PatchSearch {
+      patches: Vec::new(),
+      target,
+      inputs: Vec::new(),
+      limits: Limits::default(),
+      threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+    }
+  }
+
+  // Tries every value in `values` at `address`. Panics if that makes more
+  //   candidates than a usize can count, since they couldn't all be tried.
+  pub fn patch(mut self, address: usize, values: Range<isize>) -> Self {
+    self.patches.push((address, values));
+    assert!(self.candidates().is_some(), "too many candidates in patch search");
+    self
+  }
+
+  pub fn inputs(mut self, inputs: &[isize]) -> Self {
+    self.inputs = inputs.to_vec();
+    self
+  }
+
+  // For each candidate. Without an instruction budget, a patch that sends the
+  //   program into a long loop waiting on input can take a while to give up.
+  pub fn limits(mut self, limits: Limits) -> Self {
+    self.limits = limits;
+    self
+  }
+
+  pub fn threads(mut self, threads: usize) -> Self {
+    self.threads = threads.max(1);
+    self
+  }
+
+  // Number of candidates the search tries, at most
+  pub fn len(&self) -> usize {
+    self.candidates().unwrap()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // The first matching patch, as (address, value) pairs in the order they
+  //   were added
+  pub fn find(&self, program: &Program) -> Option<Vec<(usize, isize)>> {
+    self.search(program, true).into_iter().next()
+  }
+
+  // Every matching patch, in order
+  pub fn find_all(&self, program: &Program) -> Vec<Vec<(usize, isize)>> {
+    self.search(program, false)
+  }
+
+  fn search(&self, program: &Program, first_only: bool) -> Vec<Vec<(usize, isize)>> {
+    let total = self.len();
+    // Index of the earliest match so far, so the other threads know when to
+    //   stop looking
+    let first_match = AtomicUsize::new(usize::MAX);
+
+    let mut matches: Vec<usize> = thread::scope(|scope| {
+      let workers: Vec<_> = (0..self.threads.min(total))
+        .map(|worker| {
+          let pristine = program.clone();
+          let first_match = &first_match;
+          scope.spawn(move || {
+            let mut found = Vec::new();
+            for index in (worker..total).step_by(self.threads) {
+              if first_only && index > first_match.load(Ordering::Relaxed) {
+                break;
+              }
+              if self.matches(&pristine, &self.candidate(index)) {
+                found.push(index);
+                if first_only {
+                  first_match.fetch_min(index, Ordering::Relaxed);
+                  break;
+                }
+              }
+            }
+            found
+          })
+        })
+        .collect();
+      workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+
+    matches.sort_unstable();
+    if first_only {
+      matches.truncate(1);
+    }
+    matches.into_iter().map(|index| self.candidate(index)).collect()
+  }
+
+  fn candidates(&self) -> Option<usize> {
+    self.patches.iter().try_fold(1usize, |total, (_, values)| total.checked_mul(values.len()))
+  }
+
+  // The `index`th patch, counting in mixed radix with the last address as
+  //   the lowest digit
+  fn candidate(&self, mut index: usize) -> Vec<(usize, isize)> {
+    let mut patch = vec![(0, 0); self.patches.len()];
+    for (i, (address, values)) in self.patches.iter().enumerate().rev() {
+      // Wrapping, since a range can be wider than isize::MAX
+      patch[i] = (*address, values.start.wrapping_add((index % values.len()) as isize));
+      index /= values.len();
+    }
+    patch
+  }
+
+  fn matches(&self, pristine: &Program, patch: &[(usize, isize)]) -> bool {
+    let mut program = pristine.clone();
+    for &(address, value) in patch {
+      program.write(address, value);
+    }
+    program.set_limits(self.limits);
+    self.target.reached(&mut program, &self.inputs)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parse_program_from_file;
+
+  #[test]
+  fn day_2_noun_and_verb() {
+    let program = Program::new(&parse_program_from_file(
+      concat!(env!("CARGO_MANIFEST_DIR"), "/../day-02/input.txt"),
+    ));
+    let search = PatchSearch::new(Target::Memory { address: 0, value: 19690720 })
+      .patch(1, 0..100)
+      .patch(2, 0..100);
+    assert_eq!(search.len(), 10_000);
+
+    let expected = vec![(1, 53), (2, 35)];
+    for &threads in [1, 3, 8].iter() {
+      let search = search.clone().threads(threads);
+      assert_eq!(search.find(&program), Some(expected.clone()));
+      assert_eq!(search.find_all(&program), vec![expected.clone()]);
+    }
+    // The pristine program is left alone
+    assert_eq!(program.read(0), 1);
+  }
+
+  #[test]
+  fn output_targets_and_no_match() {
+    // Outputs 7, then the input plus the value at address 13
+    let program = Program::new(&[3,12, 104,7, 1,12,13,14, 4,14, 99, 0, 0,0,0]);
+    let search = PatchSearch::new(Target::Output { index: 1, value: 15 }).inputs(&[10]).threads(2);
+    assert_eq!(search.clone().patch(13, -10..10).find(&program), Some(vec![(13, 5)]));
+
+    // Whatever the first output is doesn't matter
+    let patches = search.clone().patch(3, 0..2).patch(13, 0..10);
+    assert_eq!(patches.find_all(&program), vec![vec![(3, 0), (13, 5)], vec![(3, 1), (13, 5)]]);
+    assert_eq!(patches.find(&program), Some(vec![(3, 0), (13, 5)]));
+
+    assert_eq!(search.clone().patch(13, 100..200).find(&program), None);
+    // Halts at 10, unless the first output is patched into something else
+    let halt = PatchSearch::new(Target::Halt { address: 10 }).inputs(&[10]).patch(2, 100..105);
+    assert_eq!(halt.find_all(&program), vec![vec![(2, 104)]]);
+    assert!(search.patch(13, 5..5).is_empty());
+  }
+
+  #[test]
+  fn wide_patches() {
+    let target = Target::Output { index: 0, value: isize::MAX - 1 };
+    let search = PatchSearch::new(target).patch(3, isize::MIN..isize::MAX);
+    assert_eq!(search.len(), usize::MAX);
+    assert_eq!(search.candidate(usize::MAX - 1), vec![(3, isize::MAX - 1)]);
+
+    let search = PatchSearch::new(target).patch(0, 0..1 << 20).patch(1, 0..1 << 20);
+    assert_eq!(search.len(), 1 << 40);
+    assert_eq!(search.candidate((1 << 40) - 1), vec![(0, (1 << 20) - 1), (1, (1 << 20) - 1)]);
+  }
+
+  #[test]
+  #[should_panic(expected = "too many candidates")]
+  fn rejects_too_many_candidates() {
+    PatchSearch::new(Target::Memory { address: 0, value: 0 })
+      .patch(0, 0..1 << 30)
+      .patch(1, 0..1 << 30)
+      .patch(2, 0..1 << 30);
+  }
+}
@@ -0,0 +1,335 @@
+// The complete state of an Intcode machine, for forking a program at a
+//   decision point or checkpointing a long run. Get one with
+//   `Program::snapshot`, turn it back into a program with `Program::restore`.
+//
+// Snapshots save to a plain text file, one field per line:
+//
+//   intcode-snapshot 1
+//   halted false
+//   instruction_pointer 25
+//   relative_base 1000
+//   instruction_count 3712
+//   memory_limit none
+//   inputs 2,7
+//   memory 1102,34463338,34463338,63,...
+//   sparse_memory 2000000:5,3000000:-1
+//
+// `sparse_memory` holds the (address:value) pairs that live outside the
+//   dense region, see memory.rs. Inputs and memory are words, so a snapshot
+//   of a `Program<i128>` is a `Snapshot<i128>`, see word.rs.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+use crate::word::Word;
+
+const HEADER: &str = "intcode-snapshot 1";
+
+const FIELDS: [&str; 8] = [
+  "halted",
+  "instruction_pointer",
+  "relative_base",
+  "instruction_count",
+  "memory_limit",
+  "inputs",
+  "memory",
+  "sparse_memory",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<W: Word = isize> {
+  pub halted: bool,
+  pub instruction_pointer: usize,
+  pub relative_base: isize,
+  pub instruction_count: usize,
+  pub memory_limit: Option<usize>,
+  // Pushed but not yet read by the program, oldest first
+  pub inputs: Vec<W>,
+  pub memory: Vec<W>,
+  pub sparse_memory: Vec<(usize, W)>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+  Io(io::Error),
+  MissingHeader,
+  MissingField(&'static str),
+  // 1-based line number, and the text of the line
+  InvalidLine { line: usize, text: String },
+}
+
+impl fmt::Display for SnapshotError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SnapshotError::Io(err) => write!(f, "{}", err),
+      SnapshotError::MissingHeader => write!(f, "not a snapshot (expected {:?} first)", HEADER),
+      SnapshotError::MissingField(name) => write!(f, "missing field {:?}", name),
+      SnapshotError::InvalidLine { line, text } => write!(f, "line {}: invalid line {:?}", line, text),
+    }
+  }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+  fn from(err: io::Error) -> Self {
+    SnapshotError::Io(err)
+  }
+}
+
+impl<W: Word> Snapshot<W> {
+  pub fn to_text(&self) -> String {
+    let memory_limit = match self.memory_limit {
+      Some(limit) => limit.to_string(),
+      None => "none".to_string(),
+    };
+    let sparse_memory: Vec<String> = self.sparse_memory
+      .iter()
+      .map(|(address, value)| format!("{}:{}", address, value))
+      .collect();
+
+    [
+      HEADER.to_string(),
+      format!("halted {}", self.halted),
+      format!("instruction_pointer {}", self.instruction_pointer),
+      format!("relative_base {}", self.relative_base),
+      format!("instruction_count {}", self.instruction_count),
+      format!("memory_limit {}", memory_limit),
+      format!("inputs {}", join(&self.inputs)),
+      format!("memory {}", join(&self.memory)),
+      format!("sparse_memory {}", sparse_memory.join(",")),
+    ].join("\n") + "\n"
+  }
+
+  pub fn parse(text: &str) -> Result<Self, SnapshotError> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+      Some((_, line)) if line.trim() == HEADER => {},
+      _ => return Err(SnapshotError::MissingHeader),
+    }
+
+    let mut fields: HashMap<&str, (usize, &str)> = HashMap::new();
+    for (index, line) in lines {
+      let line_number = index + 1;
+      let line = line.trim();
+      if line.is_empty() {
+        continue;
+      }
+      let (name, value) = match line.find(' ') {
+        Some(space) => (&line[..space], line[space + 1..].trim()),
+        None => (line, ""),
+      };
+      let is_known = FIELDS.contains(&name);
+      if !is_known || fields.insert(name, (line_number, value)).is_some() {
+        return Err(SnapshotError::InvalidLine { line: line_number, text: line.to_string() });
+      }
+    }
+
+    let fields = Fields { text, fields };
+    let memory_limit = match fields.get("memory_limit")? {
+      (_, "none") => None,
+      _ => Some(fields.number("memory_limit")?),
+    };
+
+    let (sparse_line, sparse_text) = fields.get("sparse_memory")?;
+    let mut sparse_memory = Vec::new();
+    for pair in sparse_text.split(',').filter(|pair| !pair.is_empty()) {
+      let mut parts = pair.splitn(2, ':');
+      let address = parts.next().and_then(|address| address.trim().parse().ok());
+      let value = parts.next().and_then(|value| value.trim().parse().ok());
+      match (address, value) {
+        (Some(address), Some(value)) => sparse_memory.push((address, value)),
+        _ => return Err(fields.invalid(sparse_line)),
+      }
+    }
+
+    Ok(Snapshot {
+      halted: fields.number("halted")?,
+      instruction_pointer: fields.number("instruction_pointer")?,
+      relative_base: fields.number("relative_base")?,
+      instruction_count: fields.number("instruction_count")?,
+      memory_limit,
+      inputs: fields.list("inputs")?,
+      memory: fields.list("memory")?,
+      sparse_memory,
+    })
+  }
+
+  pub fn save(&self, filename: &str) -> io::Result<()> {
+    fs::write(filename, self.to_text())
+  }
+
+  pub fn load(filename: &str) -> Result<Self, SnapshotError> {
+    Snapshot::parse(&fs::read_to_string(filename)?)
+  }
+}
+
+struct Fields<'a> {
+  text: &'a str,
+  // Field name -> (line number, value)
+  fields: HashMap<&'a str, (usize, &'a str)>,
+}
+
+impl<'a> Fields<'a> {
+  fn get(&self, name: &'static str) -> Result<(usize, &'a str), SnapshotError> {
+    self.fields.get(name).copied().ok_or(SnapshotError::MissingField(name))
+  }
+
+  fn number<T: FromStr>(&self, name: &'static str) -> Result<T, SnapshotError> {
+    let (line, value) = self.get(name)?;
+    value.parse().map_err(|_| self.invalid(line))
+  }
+
+  fn list<T: FromStr>(&self, name: &'static str) -> Result<Vec<T>, SnapshotError> {
+    let (line, value) = self.get(name)?;
+    value
+      .split(',')
+      .filter(|value| !value.is_empty())
+      .map(|value| value.trim().parse().map_err(|_| self.invalid(line)))
+      .collect()
+  }
+
+  fn invalid(&self, line: usize) -> SnapshotError {
+    SnapshotError::InvalidLine {
+      line,
+      text: self.text.lines().nth(line - 1).unwrap_or("").trim().to_string(),
+    }
+  }
+}
+
+fn join<W: Word>(values: &[W]) -> String {
+  let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+  values.join(",")
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{parse_words, BigInt, Limit, Limits, Program, Yield};
+
+  // Day 9 quine
+  const QUINE: [isize; 16] = [109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+
+  #[test]
+  fn clones_run_independently() {
+    let mut original = Program::new(&QUINE);
+    for _ in 0..5 {
+      original.resume().unwrap();
+    }
+
+    let mut fork = original.clone();
+    assert_eq!(fork.run(&[]).unwrap(), QUINE[5..].to_vec());
+    assert!(fork.is_halted());
+
+    assert!(!original.is_halted());
+    assert_eq!(original.resume().unwrap(), Yield::Output(QUINE[5]));
+  }
+
+  #[test]
+  fn round_trips_through_text() {
+    let mut program = Program::new(&[3,0,3,1,4,0,99]);
+    program.set_memory_limit(Some(10_000_000));
+    program.write(5_000_000, -3);
+    program.run(&[8]).unwrap();
+    program.push_input(9);
+    program.push_input(10);
+
+    let snapshot = program.snapshot();
+    assert_eq!(snapshot.inputs, vec![9, 10]);
+    assert_eq!(snapshot.sparse_memory, vec![(5_000_000, -3)]);
+
+    let text = snapshot.to_text();
+    assert!(text.starts_with("intcode-snapshot 1\nhalted false\ninstruction_pointer 2\n"));
+    assert_eq!(Snapshot::parse(&text).unwrap(), snapshot);
+
+    let mut restored: Program = Program::restore(&Snapshot::parse(&text).unwrap());
+    assert_eq!(restored.instruction_count(), 1);
+    assert_eq!(restored.memory_limit(), Some(10_000_000));
+    assert_eq!(restored.read(5_000_000), -3);
+    assert_eq!(restored.run(&[]).unwrap(), vec![8]);
+    assert!(restored.is_halted());
+    assert_eq!(restored.pending_inputs().len(), 1);
+  }
+
+  #[test]
+  fn restoring_resets_the_limits() {
+    // Counts down from 3, outputting each number
+    let countdown = [4,11, 1001,11,-1,11, 1005,11,0, 99, 0, 3];
+    let mut program = Program::new(&countdown);
+    let limits = Limits { outputs: Some(1), ..Limits::default() };
+    program.set_limits(limits);
+    assert_eq!(program.resume(), Ok(Yield::Output(3)));
+
+    let mut restored = Program::restore(&program.snapshot());
+    assert_eq!(*restored.limits(), Limits::default());
+    assert_eq!(restored.run(&[]), Ok(vec![2, 1]));
+
+    // Unlike a clone, which is still out of budget
+    let mut clone = program.clone();
+    assert_eq!(clone.resume(), Ok(Yield::BudgetExhausted(Limit::Outputs)));
+    let mut restored = Program::restore(&program.snapshot());
+    restored.set_limits(limits);
+    assert_eq!(restored.resume(), Ok(Yield::Output(2)));
+    assert_eq!(restored.resume(), Ok(Yield::BudgetExhausted(Limit::Outputs)));
+  }
+
+  #[test]
+  fn snapshots_any_word_type() {
+    let huge: BigInt = "123456789012345678901234567890".parse().unwrap();
+    let mut program: Program<BigInt> = Program::from_words(&parse_words("3,0,99"));
+    program.push_input(huge.clone());
+    program.push_input(-huge.clone());
+    program.resume().unwrap();
+
+    let text = program.snapshot().to_text();
+    assert!(text.contains("\ninputs -123456789012345678901234567890\n"));
+    let mut restored = Program::restore(&Snapshot::<BigInt>::parse(&text).unwrap());
+    assert_eq!(restored.read(0), huge);
+    assert_eq!(restored.resume(), Ok(Yield::Halted));
+    assert!(matches!(Snapshot::<i64>::parse(&text), Err(SnapshotError::InvalidLine { line: 7, .. })));
+  }
+
+  #[test]
+  fn saves_and_loads_files() {
+    let path = std::env::temp_dir().join(format!("intcode-snapshot-{}.txt", std::process::id()));
+    let filename = path.to_str().unwrap();
+
+    let mut program = Program::new(&QUINE);
+    program.run(&[]).unwrap();
+    program.snapshot().save(filename).unwrap();
+    let snapshot: Snapshot = Snapshot::load(filename).unwrap();
+    fs::remove_file(filename).unwrap();
+
+    assert!(snapshot.halted);
+    assert_eq!(snapshot.memory_limit, None);
+    assert!(Program::restore(&snapshot).is_halted());
+  }
+
+  #[test]
+  fn reports_bad_snapshots() {
+    let text = Program::new(&[99]).snapshot().to_text();
+
+    assert!(matches!(Snapshot::<isize>::parse("1,2,3"), Err(SnapshotError::MissingHeader)));
+    assert!(matches!(
+      Snapshot::<isize>::parse(&text.replace("relative_base 0\n", "")),
+      Err(SnapshotError::MissingField("relative_base")),
+    ));
+    match Snapshot::<isize>::parse(&text.replace("memory 99", "memory 99,x")) {
+      Err(SnapshotError::InvalidLine { line, text }) => {
+        assert_eq!(line, 8);
+        assert_eq!(text, "memory 99,x");
+      },
+      other => panic!("expected an invalid line, got {:?}", other),
+    }
+    assert!(matches!(
+      Snapshot::<isize>::parse(&(text + "bogus 1\n")),
+      Err(SnapshotError::InvalidLine { line: 10, .. }),
+    ));
+    assert!(matches!(Snapshot::<isize>::load("/nonexistent/snapshot.txt"), Err(SnapshotError::Io(_))));
+  }
+}
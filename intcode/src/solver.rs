@@ -0,0 +1,413 @@
+// A small solver for linear constraints over integers, for symbolic
+//   execution (symbolic.rs). Each constraint says a linear expression, like
+//   3*in0 - in1 + 5, is zero, isn't zero, or is at most zero.
+//
+// Every variable has a range of values it can take, which is what keeps
+//   this finite. The ranges are narrowed using each constraint in turn until
+//   nothing changes (if 3*x + y <= 10 and y >= 4, then x <= 2), and then the
+//   search splits the range of one of the variables and tries each part,
+//   values closest to zero first. A search that takes too long gives up
+//   rather than running forever.
+//
+// All of the arithmetic is checked. A constraint whose bounds overflow just
+//   doesn't narrow anything.
+
+use std::collections::BTreeMap;
+
+// Most ranges the search splits before giving up
+const MAX_NODES: usize = 20_000;
+// Most times the ranges are narrowed before splitting one anyway. Some
+//   constraints only narrow them a bit at a time (2*x == 2*y + 1).
+const MAX_PASSES: usize = 64;
+
+// A constant plus a coefficient for each variable. Coefficients are never
+//   zero, so equal expressions are equal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Linear<V: Ord> {
+  pub(crate) constant: i128,
+  pub(crate) terms: BTreeMap<V, i128>,
+}
+
+impl<V: Ord> Linear<V> {
+  pub fn constant(&self) -> i128 {
+    self.constant
+  }
+
+  // Variable -> coefficient
+  pub fn terms(&self) -> &BTreeMap<V, i128> {
+    &self.terms
+  }
+}
+
+impl<V: Ord + Copy> Linear<V> {
+  pub(crate) fn from_constant(constant: i128) -> Self {
+    Linear { constant, terms: BTreeMap::new() }
+  }
+
+  pub(crate) fn variable(variable: V) -> Self {
+    let mut terms = BTreeMap::new();
+    terms.insert(variable, 1);
+    Linear { constant: 0, terms }
+  }
+
+  pub(crate) fn as_constant(&self) -> Option<i128> {
+    if self.terms.is_empty() { Some(self.constant) } else { None }
+  }
+
+  pub(crate) fn checked_add(&self, other: &Self) -> Option<Self> {
+    let mut sum = self.clone();
+    sum.constant = sum.constant.checked_add(other.constant)?;
+    for (&variable, &coefficient) in other.terms.iter() {
+      let total = sum.terms.get(&variable).unwrap_or(&0).checked_add(coefficient)?;
+      if total == 0 {
+        sum.terms.remove(&variable);
+      } else {
+        sum.terms.insert(variable, total);
+      }
+    }
+    Some(sum)
+  }
+
+  pub(crate) fn checked_scale(&self, factor: i128) -> Option<Self> {
+    if factor == 0 {
+      return Some(Linear::from_constant(0));
+    }
+    let mut terms = BTreeMap::new();
+    for (&variable, &coefficient) in self.terms.iter() {
+      terms.insert(variable, coefficient.checked_mul(factor)?);
+    }
+    Some(Linear { constant: self.constant.checked_mul(factor)?, terms })
+  }
+
+  // `None` if it overflows, or if neither side is a constant, since then the
+  //   product isn't linear
+  pub(crate) fn checked_mul(&self, other: &Self) -> Option<Self> {
+    match (self.as_constant(), other.as_constant()) {
+      (Some(factor), _) => other.checked_scale(factor),
+      (_, Some(factor)) => self.checked_scale(factor),
+      _ => None,
+    }
+  }
+
+  // `None` if a variable is missing, or it overflows
+  pub(crate) fn eval(&self, values: &BTreeMap<V, isize>) -> Option<i128> {
+    self.terms.iter().try_fold(self.constant, |total, (variable, &coefficient)| {
+      total.checked_add(coefficient.checked_mul(*values.get(variable)? as i128)?)
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Kind {
+  Zero,
+  NonZero,
+  AtMostZero,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LinearConstraint<V: Ord> {
+  pub(crate) linear: Linear<V>,
+  pub(crate) kind: Kind,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Solved<V: Ord> {
+  // A value for every variable
+  Sat(BTreeMap<V, isize>),
+  Unsat,
+  // Took too long, or overflowed
+  Unknown,
+}
+
+// Variables are numbered in here, so the ranges can be a Vec
+type Range = (i128, i128);
+
+struct Bound {
+  terms: Vec<(usize, i128)>,
+  constant: i128,
+  // Otherwise it's <= 0
+  non_zero: bool,
+}
+
+// Finds values for `variables`, each within its range, that satisfy all of
+//   the constraints. The constraints can only use those variables.
+pub(crate) fn solve<V: Ord + Copy>(
+  constraints: &[LinearConstraint<V>],
+  variables: &[(V, (isize, isize))],
+) -> Solved<V> {
+  let index: BTreeMap<V, usize> = variables.iter().enumerate().map(|(i, &(variable, _))| (variable, i)).collect();
+  let ranges: Vec<Range> = variables.iter().map(|&(_, (lo, hi))| (lo as i128, hi as i128)).collect();
+
+  let mut bounds = Vec::new();
+  for constraint in constraints {
+    let terms: Vec<(usize, i128)> =
+      constraint.linear.terms.iter().map(|(variable, &coefficient)| (index[variable], coefficient)).collect();
+    let constant = constraint.linear.constant;
+    match constraint.kind {
+      Kind::AtMostZero => bounds.push(Bound { terms, constant, non_zero: false }),
+      Kind::NonZero => bounds.push(Bound { terms, constant, non_zero: true }),
+      Kind::Zero => {
+        // No integer solutions if the coefficients have a common factor
+        //   that the constant doesn't
+        let divisor = terms.iter().fold(0, |divisor, &(_, coefficient)| gcd(divisor, coefficient));
+        if divisor != 0 && constant % divisor != 0 {
+          return Solved::Unsat;
+        }
+        let negated: Option<Vec<(usize, i128)>> =
+          terms.iter().map(|&(i, coefficient)| Some((i, coefficient.checked_neg()?))).collect();
+        match (negated, constant.checked_neg()) {
+          (Some(negated), Some(negated_constant)) => {
+            bounds.push(Bound { terms, constant, non_zero: false });
+            bounds.push(Bound { terms: negated, constant: negated_constant, non_zero: false });
+          },
+          _ => return Solved::Unknown,
+        }
+      },
+    }
+  }
+
+  let mut nodes = 0;
+  match search(ranges, &bounds, &mut nodes) {
+    Ok(Some(values)) => Solved::Sat(
+      variables.iter().zip(values).map(|(&(variable, _), value)| (variable, value as isize)).collect(),
+    ),
+    Ok(None) => Solved::Unsat,
+    Err(GaveUp) => Solved::Unknown,
+  }
+}
+
+struct GaveUp;
+// A range that's become empty
+struct Empty;
+
+fn search(mut ranges: Vec<Range>, bounds: &[Bound], nodes: &mut usize) -> Result<Option<Vec<i128>>, GaveUp> {
+  *nodes += 1;
+  if *nodes > MAX_NODES {
+    return Err(GaveUp);
+  }
+  if narrow(&mut ranges, bounds).is_err() {
+    return Ok(None);
+  }
+
+  // Split the smallest range that isn't down to one value
+  let unfixed = ranges.iter().enumerate().filter(|(_, (lo, hi))| lo < hi).min_by_key(|(_, (lo, hi))| hi - lo);
+  let i = match unfixed {
+    Some((i, _)) => i,
+    None => {
+      let values: Vec<i128> = ranges.iter().map(|&(lo, _)| lo).collect();
+      return match bounds.iter().map(|bound| satisfied(bound, &values)).collect::<Option<Vec<bool>>>() {
+        Some(results) if results.iter().all(|&ok| ok) => Ok(Some(values)),
+        Some(_) => Ok(None),
+        None => Err(GaveUp),
+      };
+    },
+  };
+  for part in split(ranges[i]) {
+    let mut ranges = ranges.clone();
+    ranges[i] = part;
+    if let Some(values) = search(ranges, bounds, nodes)? {
+      return Ok(Some(values));
+    }
+  }
+  Ok(None)
+}
+
+// Values closest to zero first
+fn split((lo, hi): Range) -> Vec<Range> {
+  if lo <= 0 && 0 <= hi {
+    let mut parts = vec![(0, 0)];
+    if hi > 0 {
+      parts.push((1, hi));
+    }
+    if lo < 0 {
+      parts.push((lo, -1));
+    }
+    parts
+  } else {
+    let mid = lo + (hi - lo) / 2;
+    if lo > 0 { vec![(lo, mid), (mid + 1, hi)] } else { vec![(mid + 1, hi), (lo, mid)] }
+  }
+}
+
+fn narrow(ranges: &mut [Range], bounds: &[Bound]) -> Result<(), Empty> {
+  for _ in 0..MAX_PASSES {
+    let mut changed = false;
+    for bound in bounds {
+      changed |= if bound.non_zero { narrow_non_zero(ranges, bound)? } else { narrow_at_most_zero(ranges, bound)? };
+    }
+    if !changed {
+      break;
+    }
+  }
+  Ok(())
+}
+
+// The smallest `coefficient * x` can be, with x in `range`
+fn smallest_term(coefficient: i128, (lo, hi): Range) -> Option<i128> {
+  coefficient.checked_mul(if coefficient > 0 { lo } else { hi })
+}
+
+// Everything but term `i` has to leave room for it: with the others as small
+//   as they can be, coefficient * x <= -(the rest)
+fn narrow_at_most_zero(ranges: &mut [Range], bound: &Bound) -> Result<bool, Empty> {
+  let smallest = bound.terms.iter().try_fold(bound.constant, |total, &(i, coefficient)| {
+    total.checked_add(smallest_term(coefficient, ranges[i])?)
+  });
+  let smallest = match smallest {
+    Some(smallest) => smallest,
+    None => return Ok(false),
+  };
+  if smallest > 0 {
+    return Err(Empty);
+  }
+
+  let mut changed = false;
+  for &(i, coefficient) in bound.terms.iter() {
+    // Can't overflow, since `smallest` didn't
+    let room = -(smallest - smallest_term(coefficient, ranges[i]).unwrap());
+    let (lo, hi) = ranges[i];
+    if coefficient > 0 {
+      let new_hi = div_floor(room, coefficient);
+      if new_hi < hi {
+        ranges[i].1 = new_hi;
+        changed = true;
+      }
+    } else {
+      let new_lo = div_ceil(room, coefficient);
+      if new_lo > lo {
+        ranges[i].0 = new_lo;
+        changed = true;
+      }
+    }
+    if ranges[i].0 > ranges[i].1 {
+      return Err(Empty);
+    }
+  }
+  Ok(changed)
+}
+
+// Only helps once all but one of the variables are known, and then only if
+//   the one value it rules out is at the edge of the last one's range
+fn narrow_non_zero(ranges: &mut [Range], bound: &Bound) -> Result<bool, Empty> {
+  let mut unfixed = bound.terms.iter().filter(|&&(i, _)| ranges[i].0 < ranges[i].1);
+  let last = match (unfixed.next(), unfixed.next()) {
+    (None, _) => None,
+    (Some(&term), None) => Some(term),
+    _ => return Ok(false),
+  };
+
+  let rest = bound.terms.iter().filter(|&&term| Some(term) != last).try_fold(bound.constant, |total, &(i, coefficient)| {
+    total.checked_add(coefficient.checked_mul(ranges[i].0)?)
+  });
+  let rest = match rest {
+    Some(rest) => rest,
+    None => return Ok(false),
+  };
+  match last {
+    None if rest == 0 => Err(Empty),
+    None => Ok(false),
+    Some((i, coefficient)) => {
+      if rest % coefficient != 0 {
+        return Ok(false);
+      }
+      let ruled_out = -(rest / coefficient);
+      let (lo, hi) = ranges[i];
+      if ruled_out == lo {
+        ranges[i].0 += 1;
+      } else if ruled_out == hi {
+        ranges[i].1 -= 1;
+      } else {
+        return Ok(false);
+      }
+      if ranges[i].0 > ranges[i].1 { Err(Empty) } else { Ok(true) }
+    },
+  }
+}
+
+// `None` if it overflows
+fn satisfied(bound: &Bound, values: &[i128]) -> Option<bool> {
+  let total = bound.terms.iter().try_fold(bound.constant, |total, &(i, coefficient)| {
+    total.checked_add(coefficient.checked_mul(values[i])?)
+  })?;
+  Some(if bound.non_zero { total != 0 } else { total <= 0 })
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+  let quotient = a / b;
+  if a % b != 0 && (a < 0) != (b < 0) { quotient - 1 } else { quotient }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+  let quotient = a / b;
+  if a % b != 0 && (a < 0) == (b < 0) { quotient + 1 } else { quotient }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+  let (mut a, mut b) = (a.abs(), b.abs());
+  while b != 0 {
+    let rest = a % b;
+    a = b;
+    b = rest;
+  }
+  a
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // 2*x + 3*y + constant
+  fn linear(x: i128, y: i128, constant: i128) -> Linear<char> {
+    Linear::variable('x').checked_scale(x).unwrap()
+      .checked_add(&Linear::variable('y').checked_scale(y).unwrap()).unwrap()
+      .checked_add(&Linear::from_constant(constant)).unwrap()
+  }
+
+  fn constraint(x: i128, y: i128, constant: i128, kind: Kind) -> LinearConstraint<char> {
+    LinearConstraint { linear: linear(x, y, constant), kind }
+  }
+
+  fn values(x: isize, y: isize) -> BTreeMap<char, isize> {
+    vec![('x', x), ('y', y)].into_iter().collect()
+  }
+
+  const SMALL: [(char, (isize, isize)); 2] = [('x', (-100, 100)), ('y', (-100, 100))];
+  const HUGE: [(char, (isize, isize)); 2] = [('x', (isize::MIN, isize::MAX)), ('y', (isize::MIN, isize::MAX))];
+
+  #[test]
+  fn linear_arithmetic() {
+    let sum = linear(2, 3, 1).checked_add(&linear(-2, 1, 4)).unwrap();
+    assert_eq!(sum, Linear::variable('y').checked_scale(4).unwrap().checked_add(&Linear::from_constant(5)).unwrap());
+    assert_eq!(sum.eval(&values(7, 2)), Some(13));
+    assert_eq!(linear(1, 0, 0).checked_mul(&linear(0, 1, 0)), None);
+    assert_eq!(linear(1, 1, 0).checked_mul(&Linear::from_constant(0)), Some(Linear::from_constant(0)));
+    assert_eq!(Linear::<char>::from_constant(i128::MAX).checked_add(&Linear::from_constant(1)), None);
+  }
+
+  #[test]
+  fn finds_solutions_closest_to_zero() {
+    // 3x + 2y == 17, x < y
+    let constraints = [constraint(3, 2, -17, Kind::Zero), constraint(1, -1, 1, Kind::AtMostZero)];
+    assert_eq!(solve(&constraints, &SMALL), Solved::Sat(values(1, 7)));
+    assert_eq!(solve(&constraints, &HUGE), Solved::Sat(values(1, 7)));
+
+    // Nothing constrains y
+    assert_eq!(solve(&[constraint(1, 0, -5, Kind::Zero)], &HUGE), Solved::Sat(values(5, 0)));
+    assert_eq!(solve(&[constraint(1, 0, 0, Kind::NonZero)], &SMALL), Solved::Sat(values(1, 0)));
+    assert_eq!(solve(&[constraint(1, 0, -1000, Kind::Zero)], &HUGE), Solved::Sat(values(1000, 0)));
+  }
+
+  #[test]
+  fn proves_there_are_no_solutions() {
+    // 2x - 2y == 1
+    assert_eq!(solve(&[constraint(2, -2, -1, Kind::Zero)], &HUGE), Solved::Unsat);
+    // x + y >= 201, in [-100, 100]
+    assert_eq!(solve(&[constraint(-1, -1, 201, Kind::AtMostZero)], &SMALL), Solved::Unsat);
+    // x == 3 and x != 3
+    let constraints = [constraint(1, 0, -3, Kind::Zero), constraint(1, 0, -3, Kind::NonZero)];
+    assert_eq!(solve(&constraints, &HUGE), Solved::Unsat);
+    // x != y, both in [5, 5]
+    assert_eq!(solve(&[constraint(1, -1, 0, Kind::NonZero)], &[('x', (5, 5)), ('y', (5, 5))]), Solved::Unsat);
+  }
+}
@@ -0,0 +1,868 @@
+// Symbolic execution: runs a program with some of its values left unknown,
+//   and solves for the ones that get it somewhere, instead of trying them
+//   all like `PatchSearch` does. Day 2's noun and verb:
+//
+//   let solution = SymbolicExecution::new(&input)
+//     .symbolic_cell(1, 0..=99)
+//     .symbolic_cell(2, 0..=99)
+//     .find(Target::Memory { address: 0, value: 19690720 });
+//
+// Every input the program reads is a symbol (in0, in1, ...), and so is each
+//   cell passed to `symbolic_cell` (mem1, mem2, ...). Adding and multiplying
+//   them builds expressions. LessThan and Equals on expressions, and jumps
+//   on them, fork the path in two, one side assuming the condition and the
+//   other its opposite (in0 < 8, in0 >= 8), and a side the solver (see
+//   solver.rs) shows is impossible is dropped. When a path gets to the
+//   target, the target becomes one more constraint, and the solver picks
+//   values for the symbols.
+//
+// Where the program needs an actual number but has an expression -- an
+//   opcode, an address to write to, a jump target, a relative base offset --
+//   the path forks once for each value the expression can have, if there
+//   aren't too many. Reading from an address that isn't known gives an
+//   opaque value, and the solver ignores constraints it can't make sense of
+//   (those, and products of symbols).
+//
+// Symbolic arithmetic can't overflow the way the interpreter's can, either,
+//   since the solver works in i128s. So a solution might not actually work,
+//   and every one is checked by running the program for real, and thrown out
+//   if it doesn't.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::computer::Program;
+use crate::instruction::{Instruction, Opcode, ParameterMode};
+use crate::search::Target;
+use crate::solver::{self, Kind, Linear, LinearConstraint, Solved};
+
+const DEFAULT_MAX_PATHS: usize = 10_000;
+const DEFAULT_MAX_STEPS: usize = 100_000;
+// Most values an expression can have for a path to fork on it
+const MAX_CONCRETE_VALUES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Symbol {
+  // The nth input the program reads
+  Input(usize),
+  // The starting value of the cell at an address
+  Memory(usize),
+}
+
+impl fmt::Display for Symbol {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Symbol::Input(n) => write!(f, "in{}", n),
+      Symbol::Memory(address) => write!(f, "mem{}", address),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+  Const(isize),
+  Symbol(Symbol),
+  // Sums and products that are linear are kept in normal form as they're
+  //   built, so 2 * (in0 + 3) + in0 is 3*in0 + 6. That way the solver never
+  //   has to walk through everything that went into one.
+  Linear(Rc<Linear<Symbol>>),
+  // Only for what isn't linear: products of symbols, anything with a
+  //   `Load` in it, or coefficients too big for an i128
+  Add(Rc<Expr>, Rc<Expr>),
+  Mul(Rc<Expr>, Rc<Expr>),
+  // Whatever memory held at an address that depends on symbols. They're
+  //   numbered, since two reads from the same address can get different
+  //   values.
+  Load(usize, Rc<Expr>),
+}
+
+impl Expr {
+  pub fn as_const(&self) -> Option<isize> {
+    match self {
+      Expr::Const(value) => Some(*value),
+      _ => None,
+    }
+  }
+
+  // `None` if both are constants and it overflows, like the interpreter
+  fn add(a: Expr, b: Expr) -> Option<Expr> {
+    Some(match (a.as_const(), b.as_const()) {
+      (Some(a), Some(b)) => Expr::Const(a.checked_add(b)?),
+      (Some(0), _) => b,
+      (_, Some(0)) => a,
+      _ => match a.linear().zip(b.linear()).and_then(|(a, b)| a.checked_add(&b)) {
+        Some(sum) => Expr::from_linear(sum),
+        None => Expr::Add(Rc::new(a), Rc::new(b)),
+      },
+    })
+  }
+
+  fn mul(a: Expr, b: Expr) -> Option<Expr> {
+    Some(match (a.as_const(), b.as_const()) {
+      (Some(a), Some(b)) => Expr::Const(a.checked_mul(b)?),
+      (Some(0), _) | (_, Some(0)) => Expr::Const(0),
+      (Some(1), _) => b,
+      (_, Some(1)) => a,
+      _ => match a.linear().zip(b.linear()).and_then(|(a, b)| a.checked_mul(&b)) {
+        Some(product) => Expr::from_linear(product),
+        None => Expr::Mul(Rc::new(a), Rc::new(b)),
+      },
+    })
+  }
+
+  // The simplest expression for it
+  fn from_linear(linear: Linear<Symbol>) -> Expr {
+    if let Some(value) = linear.as_constant().and_then(|value| isize::try_from(value).ok()) {
+      return Expr::Const(value);
+    }
+    if linear.constant == 0 && linear.terms.len() == 1 {
+      if let Some((&symbol, 1)) = linear.terms.iter().next() {
+        return Expr::Symbol(symbol);
+      }
+    }
+    Expr::Linear(Rc::new(linear))
+  }
+
+  // `None` if it isn't linear
+  fn linear(&self) -> Option<Linear<Symbol>> {
+    match self {
+      Expr::Const(value) => Some(Linear::from_constant(*value as i128)),
+      Expr::Symbol(symbol) => Some(Linear::variable(*symbol)),
+      Expr::Linear(linear) => Some((**linear).clone()),
+      Expr::Add(..) | Expr::Mul(..) | Expr::Load(..) => None,
+    }
+  }
+}
+
+impl fmt::Display for Expr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Expr::Const(value) => write!(f, "{}", value),
+      Expr::Symbol(symbol) => write!(f, "{}", symbol),
+      Expr::Linear(linear) => {
+        let mut first = true;
+        for (symbol, &coefficient) in linear.terms.iter() {
+          match (first, coefficient < 0) {
+            (true, true) => write!(f, "-")?,
+            (true, false) => (),
+            (false, true) => write!(f, " - ")?,
+            (false, false) => write!(f, " + ")?,
+          }
+          match coefficient.abs() {
+            1 => write!(f, "{}", symbol)?,
+            magnitude => write!(f, "{}*{}", magnitude, symbol)?,
+          }
+          first = false;
+        }
+        match (first, linear.constant) {
+          (true, constant) => write!(f, "{}", constant),
+          (false, 0) => Ok(()),
+          (false, constant) if constant < 0 => write!(f, " - {}", -constant),
+          (false, constant) => write!(f, " + {}", constant),
+        }
+      },
+      Expr::Add(a, b) => match b.as_const() {
+        Some(value) if value < 0 => write!(f, "{} - {}", a, -(value as i128)),
+        _ => write!(f, "{} + {}", a, b),
+      },
+      Expr::Mul(a, b) => {
+        let factor = |expr: &Expr| match expr {
+          Expr::Add(..) => format!("({})", expr),
+          Expr::Linear(linear) if linear.terms.len() + (linear.constant != 0) as usize > 1 => {
+            format!("({})", expr)
+          },
+          _ => expr.to_string(),
+        };
+        write!(f, "{} * {}", factor(a), factor(b))
+      },
+      Expr::Load(_, address) => write!(f, "[{}]", address),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Relation {
+  Equal,
+  NotEqual,
+  LessThan,
+  AtLeast,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+  pub left: Expr,
+  pub relation: Relation,
+  pub right: Expr,
+}
+
+impl Constraint {
+  fn new(left: &Expr, relation: Relation, right: &Expr) -> Self {
+    Constraint { left: left.clone(), relation, right: right.clone() }
+  }
+
+  // `None` if either side isn't linear
+  fn linear(&self) -> Option<LinearConstraint<Symbol>> {
+    let difference = self.left.linear()?.checked_add(&self.right.linear()?.checked_scale(-1)?)?;
+    Some(match self.relation {
+      Relation::Equal => LinearConstraint { linear: difference, kind: Kind::Zero },
+      Relation::NotEqual => LinearConstraint { linear: difference, kind: Kind::NonZero },
+      // left < right is left - right + 1 <= 0
+      Relation::LessThan => LinearConstraint {
+        linear: difference.checked_add(&Linear::from_constant(1))?,
+        kind: Kind::AtMostZero,
+      },
+      // left >= right is right - left <= 0
+      Relation::AtLeast => LinearConstraint { linear: difference.checked_scale(-1)?, kind: Kind::AtMostZero },
+    })
+  }
+}
+
+impl fmt::Display for Constraint {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let relation = match self.relation {
+      Relation::Equal => "==",
+      Relation::NotEqual => "!=",
+      Relation::LessThan => "<",
+      Relation::AtLeast => ">=",
+    };
+    write!(f, "{} {} {}", self.left, relation, self.right)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+  // One for each input the program reads on the way
+  pub inputs: Vec<isize>,
+  // A value for each symbolic cell, by address
+  pub memory: Vec<(usize, isize)>,
+  // Everything the path assumed, the target included
+  pub constraints: Vec<Constraint>,
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolicReport {
+  // Every one of them checked, in the order the paths were explored
+  pub solutions: Vec<Solution>,
+  // Paths followed to the end: halting, failing, or getting to the target
+  pub paths: usize,
+  // Paths given up on, for taking too many steps, being one too many, having
+  //   too many values to fork on, or the solver giving up. Also solutions
+  //   that didn't check out.
+  pub abandoned: usize,
+}
+
+impl SymbolicReport {
+  // Whether `solutions` has every solution there is (up to one for each path)
+  pub fn exhaustive(&self) -> bool {
+    self.abandoned == 0
+  }
+}
+
+pub struct SymbolicExecution {
+  values: Vec<isize>,
+  symbolic_cells: Vec<(usize, RangeInclusive<isize>)>,
+  input_domain: RangeInclusive<isize>,
+  max_paths: usize,
+  max_steps: usize,
+}
+
+impl SymbolicExecution {
+  pub fn new(values: &[isize]) -> Self {
+    SymbolicExecution {
+      values: values.to_vec(),
+      symbolic_cells: Vec::new(),
+      input_domain: isize::MIN..=isize::MAX,
+      max_paths: DEFAULT_MAX_PATHS,
+      max_steps: DEFAULT_MAX_STEPS,
+    }
+  }
+
+  // Makes the cell at `address` a symbol, which can be anything in `domain`.
+  //   Making it one again just changes the domain.
+  pub fn symbolic_cell(mut self, address: usize, domain: RangeInclusive<isize>) -> Self {
+    match self.symbolic_cells.iter_mut().find(|(cell, _)| *cell == address) {
+      Some(cell) => cell.1 = domain,
+      None => self.symbolic_cells.push((address, domain)),
+    }
+    self
+  }
+
+  // What every input can be. Anything, by default.
+  pub fn input_domain(mut self, domain: RangeInclusive<isize>) -> Self {
+    self.input_domain = domain;
+    self
+  }
+
+  pub fn max_paths(mut self, max_paths: usize) -> Self {
+    self.max_paths = max_paths;
+    self
+  }
+
+  // For each path
+  pub fn max_steps(mut self, max_steps: usize) -> Self {
+    self.max_steps = max_steps;
+    self
+  }
+
+  pub fn find(&self, target: Target) -> Option<Solution> {
+    self.explore(target, true).solutions.into_iter().next()
+  }
+
+  pub fn find_all(&self, target: Target) -> SymbolicReport {
+    self.explore(target, false)
+  }
+
+  fn explore(&self, target: Target, first_only: bool) -> SymbolicReport {
+    let mut image: Vec<Expr> = self.values.iter().map(|&value| Expr::Const(value)).collect();
+    let mut writes = BTreeMap::new();
+    for (address, _) in self.symbolic_cells.iter() {
+      let symbol = Expr::Symbol(Symbol::Memory(*address));
+      match image.get_mut(*address) {
+        Some(cell) => *cell = symbol,
+        None => {
+          writes.insert(*address, symbol);
+        },
+      }
+    }
+    let start = Path {
+      image: Rc::new(image),
+      writes,
+      instruction_pointer: 0,
+      relative_base: 0,
+      constraints: Vec::new(),
+      inputs: 0,
+      outputs: 0,
+      steps: 0,
+    };
+
+    let mut explorer = Explorer { execution: self, target, loads: 0, report: SymbolicReport::default() };
+    let mut pending = vec![start];
+    let mut paths = 1;
+    while let Some(mut path) = pending.pop() {
+      if first_only && !explorer.report.solutions.is_empty() {
+        break;
+      }
+      loop {
+        if path.steps >= self.max_steps {
+          explorer.report.abandoned += 1;
+          break;
+        }
+        let forks = match explorer.step(&mut path) {
+          Ok(Next::Continue) => continue,
+          Ok(Next::Fork(forks)) => forks,
+          Ok(Next::Finished) => break,
+          Err(Stop::Concretize(cell)) => match explorer.concretize(&path, cell) {
+            Some(forks) => forks,
+            None => {
+              explorer.report.abandoned += 1;
+              break;
+            },
+          },
+          Err(Stop::Failed) => {
+            explorer.report.paths += 1;
+            break;
+          },
+        };
+
+        // Carry on with the first, and come back for the rest
+        let mut forks = forks.into_iter();
+        match forks.next() {
+          Some(first) => path = first,
+          // Every way it could go is impossible
+          None => {
+            explorer.report.paths += 1;
+            break;
+          },
+        }
+        for fork in forks.rev() {
+          if paths < self.max_paths {
+            paths += 1;
+            pending.push(fork);
+          } else {
+            explorer.report.abandoned += 1;
+          }
+        }
+      }
+    }
+    explorer.report
+  }
+
+  fn domain(&self, symbol: Symbol) -> (isize, isize) {
+    let domain = match symbol {
+      Symbol::Input(_) => &self.input_domain,
+      Symbol::Memory(address) => {
+        &self.symbolic_cells.iter().find(|(cell, _)| *cell == address).unwrap().1
+      },
+    };
+    (*domain.start(), *domain.end())
+  }
+}
+
+#[derive(Clone)]
+struct Path {
+  // The starting memory, which all the paths share, and what this path has
+  //   written on top of it
+  image: Rc<Vec<Expr>>,
+  writes: BTreeMap<usize, Expr>,
+  instruction_pointer: usize,
+  relative_base: isize,
+  constraints: Vec<Constraint>,
+  // How many of each there have been so far
+  inputs: usize,
+  outputs: usize,
+  steps: usize,
+}
+
+impl Path {
+  fn read(&self, address: usize) -> Expr {
+    match self.writes.get(&address) {
+      Some(value) => value.clone(),
+      None => self.image.get(address).cloned().unwrap_or(Expr::Const(0)),
+    }
+  }
+
+  fn write(&mut self, address: usize, value: Expr) {
+    self.writes.insert(address, value);
+  }
+
+  fn number(&self, address: usize) -> Result<isize, Stop> {
+    self.read(address).as_const().ok_or(Stop::Concretize(address))
+  }
+
+  // Where a param points, for one that has to be a concrete address
+  fn address(&self, mode: ParameterMode, cell: usize) -> Result<usize, Stop> {
+    let address = match mode {
+      ParameterMode::Position => self.number(cell)?,
+      ParameterMode::Relative => self.number(cell)?.checked_add(self.relative_base).ok_or(Stop::Failed)?,
+      ParameterMode::Immediate => return Err(Stop::Failed),
+    };
+    if address < 0 { Err(Stop::Failed) } else { Ok(address as usize) }
+  }
+
+  // A param that has to be a concrete value
+  fn number_param(&self, mode: ParameterMode, cell: usize) -> Result<isize, Stop> {
+    match mode {
+      ParameterMode::Immediate => self.number(cell),
+      _ => self.number(self.address(mode, cell)?),
+    }
+  }
+
+  // All the symbols the path has seen, in order
+  fn symbols(&self, execution: &SymbolicExecution) -> Vec<Symbol> {
+    let mut symbols: Vec<Symbol> = (0..self.inputs).map(Symbol::Input).collect();
+    symbols.extend(execution.symbolic_cells.iter().map(|(address, _)| Symbol::Memory(*address)));
+    symbols
+  }
+}
+
+enum Next {
+  Continue,
+  // Replaces the path
+  Fork(Vec<Path>),
+  // Got to the end, and that's been taken care of
+  Finished,
+}
+
+enum Stop {
+  // The cell at this address has an expression, where a number is needed
+  Concretize(usize),
+  // Failed the way the interpreter would: bad opcode, bad address, overflow
+  Failed,
+}
+
+struct Explorer<'a> {
+  execution: &'a SymbolicExecution,
+  target: Target,
+  // For numbering `Expr::Load`s
+  loads: usize,
+  report: SymbolicReport,
+}
+
+impl Explorer<'_> {
+  // Runs one instruction. On `Err`, the path is left as it was.
+  fn step(&mut self, path: &mut Path) -> Result<Next, Stop> {
+    let ip = path.instruction_pointer;
+    let instruction = Instruction::decode(path.number(ip)?, |_| 0).map_err(|_| Stop::Failed)?;
+    let opcode = instruction.opcode;
+    let modes: Vec<ParameterMode> = instruction.params().iter().map(|param| param.mode).collect();
+    let cell = |i: usize| ip + 1 + i;
+    let next = ip + instruction.size();
+
+    match opcode {
+      Opcode::Add | Opcode::Multiply => {
+        let destination = path.address(modes[2], cell(2))?;
+        let a = self.value(path, modes[0], cell(0))?;
+        let b = self.value(path, modes[1], cell(1))?;
+        let result = if opcode == Opcode::Add { Expr::add(a, b) } else { Expr::mul(a, b) };
+        path.write(destination, result.ok_or(Stop::Failed)?);
+      },
+      Opcode::Input => {
+        let destination = path.address(modes[0], cell(0))?;
+        path.write(destination, Expr::Symbol(Symbol::Input(path.inputs)));
+        path.inputs += 1;
+      },
+      Opcode::Output => {
+        let value = self.value(path, modes[0], cell(0))?;
+        if let Target::Output { index, value: wanted } = self.target {
+          if path.outputs == index {
+            self.finish(path, Some(Constraint::new(&value, Relation::Equal, &Expr::Const(wanted))));
+            return Ok(Next::Finished);
+          }
+        }
+        path.outputs += 1;
+      },
+      Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+        let condition = self.value(path, modes[0], cell(0))?;
+        let (when_true, when_false) = (Relation::NotEqual, Relation::Equal);
+        let (taken, not_taken) =
+          if opcode == Opcode::JumpIfTrue { (when_true, when_false) } else { (when_false, when_true) };
+        match condition.as_const() {
+          Some(value) => {
+            let jump = (value != 0) == (opcode == Opcode::JumpIfTrue);
+            path.instruction_pointer = if jump { self.jump_target(path, modes[1], cell(1))? } else { next };
+            path.steps += 1;
+            return Ok(Next::Continue);
+          },
+          None => {
+            // The target might only make sense if the jump is taken
+            let target = match path.number_param(modes[1], cell(1)) {
+              Err(Stop::Concretize(cell)) => return Err(Stop::Concretize(cell)),
+              target => target.and_then(|target| if target < 0 { Err(Stop::Failed) } else { Ok(target as usize) }),
+            };
+            let zero = Expr::Const(0);
+            let mut forks = Vec::new();
+            let mut failed = false;
+            if let Some(mut fork) = self.assume(path, Constraint::new(&condition, taken, &zero)) {
+              match target {
+                Ok(target) => {
+                  fork.instruction_pointer = target;
+                  fork.steps += 1;
+                  forks.push(fork);
+                },
+                Err(_) => failed = true,
+              }
+            }
+            if let Some(mut fork) = self.assume(path, Constraint::new(&condition, not_taken, &zero)) {
+              fork.instruction_pointer = next;
+              fork.steps += 1;
+              forks.push(fork);
+            }
+            if failed {
+              if forks.is_empty() {
+                return Err(Stop::Failed);
+              }
+              self.report.paths += 1;
+            }
+            return Ok(Next::Fork(forks));
+          },
+        }
+      },
+      Opcode::LessThan | Opcode::Equals => {
+        let destination = path.address(modes[2], cell(2))?;
+        let a = self.value(path, modes[0], cell(0))?;
+        let b = self.value(path, modes[1], cell(1))?;
+        let (holds, fails) = if opcode == Opcode::LessThan {
+          (Relation::LessThan, Relation::AtLeast)
+        } else {
+          (Relation::Equal, Relation::NotEqual)
+        };
+        if let (Some(a), Some(b)) = (a.as_const(), b.as_const()) {
+          let result = if opcode == Opcode::LessThan { a < b } else { a == b };
+          path.write(destination, Expr::Const(result as isize));
+        } else {
+          let mut forks = Vec::new();
+          for &(relation, result) in [(holds, 1), (fails, 0)].iter() {
+            if let Some(mut fork) = self.assume(path, Constraint::new(&a, relation, &b)) {
+              fork.write(destination, Expr::Const(result));
+              fork.instruction_pointer = next;
+              fork.steps += 1;
+              forks.push(fork);
+            }
+          }
+          return Ok(Next::Fork(forks));
+        }
+      },
+      Opcode::RelativeBaseOffset => {
+        let offset = path.number_param(modes[0], cell(0))?;
+        path.relative_base = path.relative_base.checked_add(offset).ok_or(Stop::Failed)?;
+      },
+      Opcode::Halt => {
+        match self.target {
+          Target::Memory { address, value } => {
+            let goal = Constraint::new(&path.read(address), Relation::Equal, &Expr::Const(value));
+            self.finish(path, Some(goal));
+          },
+          Target::Halt { address } if address == ip => self.finish(path, None),
+          _ => self.report.paths += 1,
+        }
+        return Ok(Next::Finished);
+      },
+    }
+    path.instruction_pointer = next;
+    path.steps += 1;
+    Ok(Next::Continue)
+  }
+
+  // A param's value, which can be an expression. Reading from an address
+  //   that's an expression gives an opaque value.
+  fn value(&mut self, path: &Path, mode: ParameterMode, cell: usize) -> Result<Expr, Stop> {
+    let address = match mode {
+      ParameterMode::Immediate => return Ok(path.read(cell)),
+      ParameterMode::Position => path.read(cell),
+      ParameterMode::Relative => Expr::add(path.read(cell), Expr::Const(path.relative_base)).ok_or(Stop::Failed)?,
+    };
+    match address.as_const() {
+      Some(address) if address < 0 => Err(Stop::Failed),
+      Some(address) => Ok(path.read(address as usize)),
+      None => {
+        self.loads += 1;
+        Ok(Expr::Load(self.loads, Rc::new(address)))
+      },
+    }
+  }
+
+  fn jump_target(&self, path: &Path, mode: ParameterMode, cell: usize) -> Result<usize, Stop> {
+    let target = path.number_param(mode, cell)?;
+    if target < 0 { Err(Stop::Failed) } else { Ok(target as usize) }
+  }
+
+  // A copy of the path that assumes the constraint too, unless the solver
+  //   says that's impossible
+  fn assume(&self, path: &Path, constraint: Constraint) -> Option<Path> {
+    let mut fork = path.clone();
+    fork.constraints.push(constraint);
+    match self.solve(&fork, &fork.constraints) {
+      Solved::Unsat => None,
+      _ => Some(fork),
+    }
+  }
+
+  // One path for each value the cell's expression can have, where the cell
+  //   holds that value. `None` if there are too many, or the solver can't
+  //   tell.
+  fn concretize(&self, path: &Path, cell: usize) -> Option<Vec<Path>> {
+    let expr = path.read(cell);
+    let linear = expr.linear()?;
+    let mut constraints = path.constraints.clone();
+    let mut values = Vec::new();
+    loop {
+      match self.solve(path, &constraints) {
+        Solved::Unsat => break,
+        Solved::Unknown => return None,
+        Solved::Sat(assignment) => {
+          if values.len() == MAX_CONCRETE_VALUES {
+            return None;
+          }
+          let value = isize::try_from(linear.eval(&assignment)?).ok()?;
+          values.push(value);
+          constraints.push(Constraint::new(&expr, Relation::NotEqual, &Expr::Const(value)));
+        },
+      }
+    }
+
+    let forks = values
+      .into_iter()
+      .map(|value| {
+        let mut fork = path.clone();
+        fork.constraints.push(Constraint::new(&expr, Relation::Equal, &Expr::Const(value)));
+        fork.write(cell, Expr::Const(value));
+        fork
+      })
+      .collect();
+    Some(forks)
+  }
+
+  // Constraints that aren't linear are left out
+  fn solve(&self, path: &Path, constraints: &[Constraint]) -> Solved<Symbol> {
+    let linear: Vec<LinearConstraint<Symbol>> = constraints.iter().filter_map(Constraint::linear).collect();
+    let variables: Vec<(Symbol, (isize, isize))> = path
+      .symbols(self.execution)
+      .into_iter()
+      .map(|symbol| (symbol, self.execution.domain(symbol)))
+      .collect();
+    solver::solve(&linear, &variables)
+  }
+
+  // The path got to the target, if `goal` can hold too
+  fn finish(&mut self, path: &Path, goal: Option<Constraint>) {
+    self.report.paths += 1;
+    let mut constraints = path.constraints.clone();
+    if let Some(goal) = goal {
+      // Ignoring the goal wouldn't leave much to solve
+      if goal.linear().is_none() {
+        self.report.abandoned += 1;
+        return;
+      }
+      constraints.push(goal);
+    }
+
+    let assignment = match self.solve(path, &constraints) {
+      Solved::Sat(assignment) => assignment,
+      Solved::Unsat => return,
+      Solved::Unknown => {
+        self.report.abandoned += 1;
+        return;
+      },
+    };
+    let solution = Solution {
+      inputs: (0..path.inputs).map(|n| assignment[&Symbol::Input(n)]).collect(),
+      memory: self
+        .execution
+        .symbolic_cells
+        .iter()
+        .map(|(address, _)| (*address, assignment[&Symbol::Memory(*address)]))
+        .collect(),
+      constraints,
+    };
+
+    let mut program = Program::new(&self.execution.values);
+    for &(address, value) in solution.memory.iter() {
+      program.write(address, value);
+    }
+    if self.target.reached(&mut program, &solution.inputs) {
+      self.report.solutions.push(solution);
+    } else {
+      self.report.abandoned += 1;
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parse_program_from_file;
+
+  // Reads x and y, and outputs 1 (halting at 32) if 3x + 2y == 17 and x < y,
+  //   otherwise 0 (halting at 35)
+  const PICKY: [isize; 36] = [
+    3,100, 3,101, 1002,100,3,102, 1002,101,2,103, 1,102,103,104, 1008,104,17,105, 1006,105,33,
+    7,100,101,106, 1006,106,33, 104,1, 99, 104,0, 99,
+  ];
+
+  fn puzzle_input(path: &str) -> Vec<isize> {
+    parse_program_from_file(&format!("{}/../{}", env!("CARGO_MANIFEST_DIR"), path))
+  }
+
+  fn show(constraints: &[Constraint]) -> Vec<String> {
+    constraints.iter().map(|constraint| constraint.to_string()).collect()
+  }
+
+  #[test]
+  fn solves_for_inputs() {
+    let execution = SymbolicExecution::new(&PICKY);
+    let solution = execution.find(Target::Output { index: 0, value: 1 }).unwrap();
+    assert_eq!(solution.inputs, vec![1, 7]);
+    assert_eq!(show(&solution.constraints), vec!["3*in0 + 2*in1 == 17", "in0 < in1", "1 == 1"]);
+    assert_eq!(execution.find(Target::Halt { address: 32 }).unwrap().inputs, vec![1, 7]);
+
+    // Three ways to go, and one of them outputs 1
+    let report = execution.find_all(Target::Output { index: 0, value: 0 });
+    let inputs: Vec<Vec<isize>> = report.solutions.iter().map(|solution| solution.inputs.clone()).collect();
+    assert_eq!(inputs, vec![vec![5, 1], vec![0, 0]]);
+    assert_eq!(report.paths, 3);
+    assert!(report.exhaustive());
+
+    let narrow = SymbolicExecution::new(&PICKY).input_domain(0..=4);
+    assert_eq!(narrow.find(Target::Output { index: 0, value: 1 }).unwrap().inputs, vec![3, 4]);
+    assert_eq!(narrow.input_domain(4..=100).find(Target::Output { index: 0, value: 1 }), None);
+  }
+
+  #[test]
+  fn day_2_noun_and_verb() {
+    let report = SymbolicExecution::new(&puzzle_input("day-02/input.txt"))
+      .symbolic_cell(1, 0..=99)
+      .symbolic_cell(2, 0..=99)
+      .find_all(Target::Memory { address: 0, value: 19690720 });
+    // Without trying them all, like `PatchSearch` does
+    assert_eq!(report.paths, 1);
+    assert!(report.exhaustive());
+    assert_eq!(report.solutions.len(), 1);
+    assert_eq!(report.solutions[0].memory, vec![(1, 53), (2, 35)]);
+
+    // Only the last domain counts
+    let solution = SymbolicExecution::new(&puzzle_input("day-02/input.txt"))
+      .symbolic_cell(1, 0..=99)
+      .symbolic_cell(2, 0..=99)
+      .symbolic_cell(1, 50..=60)
+      .find(Target::Memory { address: 0, value: 19690720 })
+      .unwrap();
+    assert_eq!(solution.memory, vec![(1, 53), (2, 35)]);
+  }
+
+  #[test]
+  fn long_chains_of_arithmetic() {
+    // Reads a number and doubles it 60 times, each doubling using the last
+    let doublings = 60;
+    let mut program = vec![3,0];
+    for _ in 0..doublings {
+      program.extend(&[1,0,0,0]);
+    }
+    program.extend(&[4,0, 99]);
+
+    let solution = SymbolicExecution::new(&program)
+      .input_domain(0..=100)
+      .find(Target::Output { index: 0, value: 5 << doublings })
+      .unwrap();
+    assert_eq!(solution.inputs, vec![5]);
+    assert_eq!(show(&solution.constraints), vec![format!("{}*in0 == {}", 1_i64 << doublings, 5_i64 << doublings)]);
+    match &solution.constraints[0].left {
+      Expr::Linear(linear) => {
+        assert_eq!(linear.constant(), 0);
+        assert_eq!(linear.terms().get(&Symbol::Input(0)), Some(&(1 << doublings)));
+      },
+      other => panic!("expected a linear expression, got {}", other),
+    }
+  }
+
+  #[test]
+  fn day_5_diagnostic_id() {
+    let program = puzzle_input("day-05/puzzle-input.txt");
+    let diagnostic_code = Program::new(&program).run(&[5]).unwrap()[0];
+
+    // The program adds its input to an opcode, so there's a path for each
+    //   value of it
+    let report = SymbolicExecution::new(&program)
+      .input_domain(0..=99)
+      .find_all(Target::Output { index: 0, value: diagnostic_code });
+    assert_eq!(report.solutions.len(), 1);
+    assert_eq!(report.solutions[0].inputs, vec![5]);
+    assert_eq!(report.paths, 100);
+    assert!(report.exhaustive());
+
+    // Too many values to try, with any input allowed
+    let report = SymbolicExecution::new(&program).find_all(Target::Output { index: 0, value: diagnostic_code });
+    assert_eq!((report.paths, report.abandoned), (0, 1));
+  }
+
+  #[test]
+  fn forks_on_symbolic_addresses() {
+    // Writes 42 to the address it reads, then outputs what's at 12
+    let program = [3,5, 1101,40,2,0, 4,12, 99, 0,0,0,0];
+    let solution = SymbolicExecution::new(&program)
+      .input_domain(0..=20)
+      .find(Target::Output { index: 0, value: 42 })
+      .unwrap();
+    assert_eq!(solution.inputs, vec![12]);
+    assert_eq!(show(&solution.constraints), vec!["in0 == 12", "42 == 42"]);
+
+    // Reading from one gives an opaque value, which can't be solved for
+    let program = [3,3, 4,0, 99];
+    let report = SymbolicExecution::new(&program).find_all(Target::Output { index: 0, value: 7 });
+    assert_eq!((report.paths, report.abandoned), (1, 1));
+  }
+
+  #[test]
+  fn shows_expressions() {
+    let (x, y) = (Expr::Symbol(Symbol::Input(0)), Expr::Symbol(Symbol::Memory(7)));
+    let sum = Expr::add(Expr::mul(x.clone(), Expr::Const(-2)).unwrap(), Expr::Const(5)).unwrap();
+    assert_eq!(sum.to_string(), "-2*in0 + 5");
+    assert_eq!(Expr::add(y.clone(), Expr::Const(-1)).unwrap().to_string(), "mem7 - 1");
+    let product = Expr::mul(Expr::add(x, Expr::Const(1)).unwrap(), y).unwrap();
+    assert_eq!(product.to_string(), "(in0 + 1) * mem7");
+    assert_eq!(Expr::Load(1, Rc::new(product)).to_string(), "[(in0 + 1) * mem7]");
+  }
+}
@@ -0,0 +1,157 @@
+// An interactive text session with an ASCII program, like the ones from
+//   Days 17, 21 and 25. Each line typed in goes to the program as character
+//   codes ending in a newline (10), and its output is printed as text, except
+//   for values outside of the ASCII range (usually the final answer), which
+//   are printed as numbers. See `AsciiInput` / `AsciiOutput` in device.rs.
+//
+// A session can start from a script, one line of input per line, that's
+//   played back before reading from the user. Script lines are echoed, so the
+//   output reads like the session was typed in. Every line the program gets
+//   can also be recorded to a transcript, which works as a script later on:
+//
+//   cargo run -p intcode --bin ascii -- day-25/input.txt --record session.txt
+//   cargo run -p intcode --bin ascii -- day-25/input.txt --script session.txt
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use crate::computer::{Program, Yield};
+use crate::device::{ascii_codes, AsciiInput, AsciiOutput, OutputDevice};
+use crate::error::IntcodeError;
+use crate::limits::Limit;
+
+#[derive(Debug, PartialEq)]
+pub enum SessionEnd {
+  Halted,
+  // The program wanted more input after the script and the user ran out
+  OutOfInput,
+  BudgetExhausted(Limit),
+  Failed(IntcodeError),
+}
+
+pub struct Terminal {
+  program: Program,
+  script: VecDeque<String>,
+  transcript: Option<Box<dyn Write>>,
+}
+
+impl Terminal {
+  pub fn new(program: Program) -> Self {
+    Terminal { program, script: VecDeque::new(), transcript: None }
+  }
+
+  pub fn with_script(mut self, script: &str) -> Self {
+    let mut lines = AsciiInput::new(script.as_bytes());
+    // Reading from a string can't fail
+    while let Some(line) = lines.read_line().unwrap() {
+      self.script.push_back(line);
+    }
+    self
+  }
+
+  pub fn record_to(mut self, transcript: Box<dyn Write>) -> Self {
+    self.transcript = Some(transcript);
+    self
+  }
+
+  pub fn program(&self) -> &Program {
+    &self.program
+  }
+
+  // Runs until the program halts or fails, or there's no input left for it.
+  //   Only problems talking to the terminal are errors here.
+  pub fn run(&mut self, input: impl BufRead, out: impl Write) -> io::Result<SessionEnd> {
+    let mut input = AsciiInput::new(input);
+    let mut out = AsciiOutput::new(out);
+    loop {
+      match self.program.resume() {
+        Ok(Yield::Output(value)) => out.write(value)?,
+        Ok(Yield::Halted) => return Ok(SessionEnd::Halted),
+        Ok(Yield::BudgetExhausted(limit)) => return Ok(SessionEnd::BudgetExhausted(limit)),
+        Err(err) => return Ok(SessionEnd::Failed(err)),
+        Ok(Yield::NeedsInput) => {
+          let line = match self.script.pop_front() {
+            Some(line) => {
+              for value in ascii_codes(&line) {
+                out.write(value)?;
+              }
+              line
+            },
+            None => match input.read_line()? {
+              Some(line) => line,
+              None => return Ok(SessionEnd::OutOfInput),
+            },
+          };
+          if let Some(transcript) = self.transcript.as_mut() {
+            writeln!(transcript, "{}", line)?;
+            transcript.flush()?;
+          }
+          for value in ascii_codes(&line) {
+            self.program.push_input(value);
+          }
+        },
+      }
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::{Arc, Mutex};
+
+  // Echoes its input until it reads a '.', then outputs 1234567 and halts
+  const ECHO_UNTIL_DOT: [isize; 19] = [
+    3,17, 1008,17,46,18, 1005,18,14, 4,17, 1105,1,0, 104,1234567, 99, 0,0,
+  ];
+
+  // Somewhere to record a transcript that can still be read afterwards
+  #[derive(Clone, Default)]
+  struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+  impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  fn session(terminal: &mut Terminal, input: &str) -> (SessionEnd, String) {
+    let mut out = Vec::new();
+    let end = terminal.run(input.as_bytes(), &mut out).unwrap();
+    (end, String::from_utf8(out).unwrap())
+  }
+
+  #[test]
+  fn text_in_text_and_numbers_out() {
+    let mut terminal = Terminal::new(Program::new(&ECHO_UNTIL_DOT));
+    assert_eq!(session(&mut terminal, "hi\r\nthere.\n"), (SessionEnd::Halted, "hi\nthere1234567\n".to_string()));
+  }
+
+  #[test]
+  fn scripts_replay_transcripts() {
+    let transcript = SharedBuffer::default();
+    let mut terminal = Terminal::new(Program::new(&ECHO_UNTIL_DOT))
+      .with_script("hi\n")
+      .record_to(Box::new(transcript.clone()));
+    // Script lines are echoed, the user's aren't (their terminal does that)
+    assert_eq!(session(&mut terminal, "there"), (SessionEnd::OutOfInput, "hi\nhi\nthere\n".to_string()));
+    let recorded = String::from_utf8(transcript.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(recorded, "hi\nthere\n");
+
+    let mut replay = Terminal::new(Program::new(&ECHO_UNTIL_DOT)).with_script(&recorded);
+    assert_eq!(session(&mut replay, "."), (SessionEnd::Halted, "hi\nhi\nthere\nthere\n1234567\n".to_string()));
+  }
+
+  #[test]
+  fn errors_end_the_session() {
+    let mut terminal = Terminal::new(Program::new(&[104, 65, 4, -1]));
+    let (end, out) = session(&mut terminal, "");
+    assert_eq!(out, "A");
+    assert!(matches!(end, SessionEnd::Failed(IntcodeError::NegativeAddress { .. })));
+  }
+}
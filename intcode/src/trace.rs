@@ -0,0 +1,194 @@
+// Structured execution traces. Hook a `TraceSink` up to a program with
+//   `Program::set_trace_sink` and it gets one `TraceEvent` per executed
+//   instruction.
+//
+// `JsonLinesTrace` writes the events to a file, one JSON object per line:
+//
+//   {"step":0,"ip":0,"op":"arb","operands":[1],"rb":1}
+//   {"step":1,"ip":2,"op":"out","operands":[109],"out":109}
+//   {"step":2,"ip":4,"op":"add","operands":[0,1,100],"write":[100,1]}
+//
+// Optional keys are left out when they don't apply. Since it's plain text, two
+//   traces can be compared with `diff`, or loaded into whatever analysis tool.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::instruction::Opcode;
+use crate::word::Word;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryWrite<W = isize> {
+  pub address: usize,
+  pub value: W,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent<W = isize> {
+  // 0-based count of instructions executed before this one
+  pub step: usize,
+  pub instruction_pointer: usize,
+  pub opcode: Opcode,
+  // The values the instruction actually used, after applying parameter
+  //   modes. The param that gets written to shows up as its address. A jump
+  //   that isn't taken only has its condition.
+  pub operands: Vec<W>,
+  pub write: Option<MemoryWrite<W>>,
+  // The new relative base, for RelativeBaseOffset instructions
+  pub relative_base: Option<isize>,
+  pub input: Option<W>,
+  pub output: Option<W>,
+}
+
+impl<W: Word> TraceEvent<W> {
+  pub fn to_json(&self) -> String {
+    let operands: Vec<String> = self.operands.iter().map(|value| value.to_string()).collect();
+    let mut json = format!(
+      "{{\"step\":{},\"ip\":{},\"op\":\"{}\",\"operands\":[{}]",
+      self.step,
+      self.instruction_pointer,
+      self.opcode.mnemonic(),
+      operands.join(","),
+    );
+    if let Some(write) = &self.write {
+      json += &format!(",\"write\":[{},{}]", write.address, write.value);
+    }
+    if let Some(relative_base) = self.relative_base {
+      json += &format!(",\"rb\":{}", relative_base);
+    }
+    if let Some(input) = &self.input {
+      json += &format!(",\"in\":{}", input);
+    }
+    if let Some(output) = &self.output {
+      json += &format!(",\"out\":{}", output);
+    }
+    json.push('}');
+    json
+  }
+}
+
+pub trait TraceSink<W = isize> {
+  fn record(&mut self, event: &TraceEvent<W>) -> io::Result<()>;
+}
+
+pub struct JsonLinesTrace<O: Write> {
+  writer: O,
+}
+
+impl JsonLinesTrace<BufWriter<File>> {
+  pub fn create(filename: &str) -> io::Result<Self> {
+    Ok(JsonLinesTrace::new(BufWriter::new(File::create(filename)?)))
+  }
+}
+
+impl<O: Write> JsonLinesTrace<O> {
+  pub fn new(writer: O) -> Self {
+    JsonLinesTrace { writer }
+  }
+}
+
+impl<O: Write, W: Word> TraceSink<W> for JsonLinesTrace<O> {
+  fn record(&mut self, event: &TraceEvent<W>) -> io::Result<()> {
+    writeln!(self.writer, "{}", event.to_json())
+  }
+}
+
+// Keeps events in memory. Cloning it gives another handle to the same events,
+//   so one clone can go to the program while the other is used to read them.
+#[derive(Clone)]
+pub struct MemoryTrace<W = isize> {
+  events: Arc<Mutex<Vec<TraceEvent<W>>>>,
+}
+
+impl<W> Default for MemoryTrace<W> {
+  fn default() -> Self {
+    MemoryTrace { events: Arc::new(Mutex::new(Vec::new())) }
+  }
+}
+
+impl<W: Word> MemoryTrace<W> {
+  pub fn new() -> Self {
+    MemoryTrace::default()
+  }
+
+  pub fn events(&self) -> Vec<TraceEvent<W>> {
+    self.events.lock().unwrap().clone()
+  }
+}
+
+impl<W: Word> TraceSink<W> for MemoryTrace<W> {
+  fn record(&mut self, event: &TraceEvent<W>) -> io::Result<()> {
+    self.events.lock().unwrap().push(event.clone());
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Program;
+
+  #[test]
+  fn records_every_instruction() {
+    // Day 9 quine
+    let quine = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+    let trace = MemoryTrace::new();
+    let mut program = Program::new(&quine);
+    program.set_trace_sink(Box::new(trace.clone()));
+    program.run(&[]).unwrap();
+
+    let events = trace.events();
+    assert_eq!(events.len(), program.instruction_count());
+    assert_eq!(events.iter().filter_map(|event| event.output).collect::<Vec<_>>(), quine);
+
+    assert_eq!(events[0], TraceEvent {
+      step: 0,
+      instruction_pointer: 0,
+      opcode: Opcode::RelativeBaseOffset,
+      operands: vec![1],
+      write: None,
+      relative_base: Some(1),
+      input: None,
+      output: None,
+    });
+    assert_eq!(events[2].write, Some(MemoryWrite { address: 100, value: 1 }));
+    // The last time around, the jump back to the start isn't taken
+    let last_jump = &events[events.len() - 2];
+    assert_eq!((last_jump.opcode, last_jump.operands.clone()), (Opcode::JumpIfFalse, vec![1]));
+    assert_eq!(events.last().unwrap().opcode, Opcode::Halt);
+  }
+
+  #[test]
+  fn writes_json_lines() {
+    let path = std::env::temp_dir().join(format!("intcode-trace-{}.jsonl", std::process::id()));
+    let filename = path.to_str().unwrap();
+
+    let mut program = Program::new(&[3,0,4,0,99]);
+    program.set_trace_sink(Box::new(JsonLinesTrace::create(filename).unwrap()));
+    program.run(&[7]).unwrap();
+    // Dropping the sink flushes it
+    drop(program.take_trace_sink());
+
+    let contents = std::fs::read_to_string(filename).unwrap();
+    std::fs::remove_file(filename).unwrap();
+    assert_eq!(contents, [
+      r#"{"step":0,"ip":0,"op":"in","operands":[0],"write":[0,7],"in":7}"#,
+      r#"{"step":1,"ip":2,"op":"out","operands":[7],"out":7}"#,
+      r#"{"step":2,"ip":4,"op":"hlt","operands":[]}"#,
+    ].join("\n") + "\n");
+  }
+
+  #[test]
+  fn input_wait_is_not_traced() {
+    let trace = MemoryTrace::new();
+    let mut program = Program::new(&[3,0,4,0,99]);
+    program.set_trace_sink(Box::new(trace.clone()));
+
+    program.run(&[]).unwrap();
+    assert!(trace.events().is_empty());
+    program.run(&[5]).unwrap();
+    assert_eq!(trace.events().len(), 3);
+  }
+}
@@ -0,0 +1,197 @@
+// The type of value a program stores in each memory cell. `Program` is
+//   `Program<isize>` unless asked for something else:
+//
+//   let words: Vec<i128> = parse_words(&text);
+//   let mut program = Program::from_words(&words);
+//
+// Day 9 only says the computer "should have support for large numbers".
+//   isize has been plenty for every puzzle so far, so it stays the default,
+//   but the word can also be i64 or i128 (the same on every platform), or a
+//   `BigInt` that can't overflow at all.
+//
+// Arithmetic on fixed-width words is checked. An Add or Multiply whose
+//   result doesn't fit is an `IntcodeError::Overflow`, never a wrapped value.
+//
+// Addresses, the instruction pointer and the relative base are still plain
+//   usize/isize, whatever the word. A word that's used as one of those but
+//   doesn't fit is an error, like any other bad address.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bigint::BigInt;
+
+pub trait Word: Clone + Ord + fmt::Debug + fmt::Display + FromStr + Send + 'static {
+  fn from_isize(value: isize) -> Self;
+
+  // `None` if it doesn't fit
+  fn to_isize(&self) -> Option<isize>;
+
+  // `None` on overflow
+  fn checked_add(&self, other: &Self) -> Option<Self>;
+  fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+  fn zero() -> Self {
+    Self::from_isize(0)
+  }
+
+  fn is_zero(&self) -> bool {
+    *self == Self::zero()
+  }
+
+  // Clamped to isize's range. Errors only have room for an isize.
+  fn saturating_to_isize(&self) -> isize {
+    match self.to_isize() {
+      Some(value) => value,
+      None if *self < Self::zero() => isize::MIN,
+      None => isize::MAX,
+    }
+  }
+}
+
+// All of these are at least as wide as isize, so `from_isize` can't lose
+//   anything
+macro_rules! fixed_width_word {
+  ($($word:ty),*) => {
+    $(
+      impl Word for $word {
+        fn from_isize(value: isize) -> Self {
+          value as $word
+        }
+
+        fn to_isize(&self) -> Option<isize> {
+          isize::try_from(*self).ok()
+        }
+
+        fn checked_add(&self, other: &Self) -> Option<Self> {
+          <$word>::checked_add(*self, *other)
+        }
+
+        fn checked_mul(&self, other: &Self) -> Option<Self> {
+          <$word>::checked_mul(*self, *other)
+        }
+
+        fn is_zero(&self) -> bool {
+          *self == 0
+        }
+      }
+    )*
+  };
+}
+
+fixed_width_word!(isize, i64, i128);
+
+impl Word for BigInt {
+  fn from_isize(value: isize) -> Self {
+    BigInt::from(value)
+  }
+
+  fn to_isize(&self) -> Option<isize> {
+    self.to_i128().and_then(|value| isize::try_from(value).ok())
+  }
+
+  fn checked_add(&self, other: &Self) -> Option<Self> {
+    Some(self + other)
+  }
+
+  fn checked_mul(&self, other: &Self) -> Option<Self> {
+    Some(self * other)
+  }
+
+  fn zero() -> Self {
+    BigInt::zero()
+  }
+
+  fn is_zero(&self) -> bool {
+    BigInt::is_zero(self)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use crate::{parse_words, BigInt, IntcodeError, Program, Word};
+
+  // Squares the input twice, then outputs it
+  const SQUARE_TWICE: &str = "3,13, 2,13,13,13, 2,13,13,13, 4,13, 99";
+
+  fn square_twice<W: Word>(input: W) -> Result<Vec<W>, IntcodeError> {
+    Program::from_words(&parse_words(SQUARE_TWICE)).run(&[input])
+  }
+
+  #[test]
+  fn day_9_large_numbers_on_every_word() {
+    let text = "1102,34915192,34915192,7,4,7,99,0";
+    let expected: i64 = 1219070632396864;
+    assert_eq!(Program::new(&parse_words(text)).run(&[]), Ok(vec![expected as isize]));
+    assert_eq!(Program::<i64>::from_words(&parse_words(text)).run(&[]), Ok(vec![expected]));
+    assert_eq!(Program::<i128>::from_words(&parse_words(text)).run(&[]), Ok(vec![expected as i128]));
+    assert_eq!(
+      Program::<BigInt>::from_words(&parse_words(text)).run(&[]),
+      Ok(vec![BigInt::from(expected)]),
+    );
+
+    let text = "104,1125899906842624,99";
+    assert_eq!(
+      Program::<BigInt>::from_words(&parse_words(text)).run(&[]),
+      Ok(vec!["1125899906842624".parse().unwrap()]),
+    );
+  }
+
+  #[test]
+  fn overflow_is_an_error() {
+    // 2^16 squared twice is 2^64
+    assert_eq!(
+      square_twice(1i64 << 16),
+      Err(IntcodeError::Overflow { instruction_pointer: 6, opcode: 2 }),
+    );
+    assert_eq!(square_twice(1i128 << 16), Ok(vec![1 << 64]));
+    assert_eq!(square_twice(1i128 << 32), Err(IntcodeError::Overflow { instruction_pointer: 6, opcode: 2 }));
+    assert_eq!(
+      square_twice(BigInt::from(1i128 << 32)),
+      Ok(vec!["340282366920938463463374607431768211456".parse().unwrap()]),
+    );
+  }
+
+  #[test]
+  fn boost_on_every_word() {
+    fn run<W: Word>(text: &str, compiled: bool) -> Vec<String> {
+      let mut program = Program::<W>::from_words(&parse_words(text));
+      if compiled {
+        program.enable_compiler();
+      }
+      program.run(&[W::from_isize(1)]).unwrap().iter().map(|value| value.to_string()).collect()
+    }
+
+    let text = std::fs::read_to_string(
+      concat!(env!("CARGO_MANIFEST_DIR"), "/../day-09/puzzle-input.txt"),
+    ).unwrap();
+    let expected = run::<isize>(&text, false);
+    for &compiled in [false, true].iter() {
+      assert_eq!(run::<i64>(&text, compiled), expected);
+      assert_eq!(run::<i128>(&text, compiled), expected);
+      assert_eq!(run::<BigInt>(&text, compiled), expected);
+    }
+  }
+
+  #[test]
+  fn huge_words_as_addresses() {
+    // Fine as an immediate, but not as an address
+    let huge: BigInt = "100000000000000000000000".parse().unwrap();
+    let immediate = vec![BigInt::from(104isize), huge.clone(), BigInt::from(99isize)];
+    assert_eq!(Program::from_words(&immediate).run(&[]), Ok(vec![huge.clone()]));
+
+    let position = vec![BigInt::from(4isize), huge.clone(), BigInt::from(99isize)];
+    assert_eq!(
+      Program::from_words(&position).run(&[]),
+      Err(IntcodeError::AddressOutOfRange { instruction_pointer: 0, opcode: 4, address: usize::MAX }),
+    );
+
+    let jump = vec![BigInt::from(1105isize), BigInt::from(1isize), -huge];
+    assert_eq!(
+      Program::from_words(&jump).run(&[]),
+      Err(IntcodeError::NegativeAddress { instruction_pointer: 0, opcode: 1105, address: isize::MIN }),
+    );
+  }
+}